@@ -0,0 +1,213 @@
+use axum::{
+    extract::{FromRequestParts, Path, Query, State},
+    http::request::Parts,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use classroom::{api::Course, chrono::Utc};
+use serde::de::DeserializeOwned;
+
+use crate::{
+    auth::{UserClient, UserId},
+    backend::Backend,
+    routes::{
+        self, Assignment, ClassGrades, ClassPage, MaterialPage, PaginationQuery, PeoplePage,
+        PostPage, TodoList,
+    },
+    AppState, Error,
+};
+
+/// The `/api/v1` routes, which return the same data as the HTML pages as JSON.
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/classes", get(classes))
+        .route("/class/:classid", get(class))
+        .route("/class/:classid/announcements", get(announcements))
+        .route("/class/:classid/material/:id", get(material))
+        .route("/class/:classid/grades", get(grades_for_class))
+        .route("/class/:classid/people", get(people))
+        .route("/grades", get(grades))
+        .route("/todo", get(todos_all))
+        .route("/todo/:class", get(todos_for_class))
+        .route("/assignment/:classid/:id", get(assignment))
+}
+
+/// An [`Error`] that responds with a JSON body instead of the HTML error page.
+pub struct ApiError(Error);
+
+impl<E: Into<Error>> From<E> for ApiError {
+    fn from(value: E) -> Self {
+        Self(value.into())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        self.0.to_json_response()
+    }
+}
+
+/// [`UserClient`], but rejecting with an [`ApiError`] rather than redirecting to `/oauth`.
+pub struct ApiClient(Backend);
+
+#[axum::async_trait]
+impl FromRequestParts<AppState> for ApiClient {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let UserClient(client) = UserClient::from_request_parts(parts, state).await?;
+        Ok(Self(client))
+    }
+}
+
+/// [`UserId`], rejecting with an [`ApiError`].
+pub struct ApiUserId(String);
+
+#[axum::async_trait]
+impl FromRequestParts<AppState> for ApiUserId {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let UserId(user) = UserId::from_request_parts(parts, state).await?;
+        Ok(Self(user))
+    }
+}
+
+/// [`Path`], rejecting with an [`ApiError`] rather than axum's plain text.
+pub struct ApiPath<T>(T);
+
+#[axum::async_trait]
+impl<T: DeserializeOwned + Send> FromRequestParts<AppState> for ApiPath<T> {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let Path(value) = Path::from_request_parts(parts, state)
+            .await
+            .map_err(|rejection| Error::InvalidRequest(rejection.body_text()))?;
+        Ok(Self(value))
+    }
+}
+
+/// [`Query`], rejecting with an [`ApiError`] rather than axum's plain text.
+pub struct ApiQuery<T>(T);
+
+#[axum::async_trait]
+impl<T: DeserializeOwned + Send> FromRequestParts<AppState> for ApiQuery<T> {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let Query(value) = Query::from_request_parts(parts, state)
+            .await
+            .map_err(|rejection| Error::InvalidRequest(rejection.body_text()))?;
+        Ok(Self(value))
+    }
+}
+
+async fn classes(
+    ApiClient(client): ApiClient,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<Course>>, ApiError> {
+    Ok(Json(routes::list_classes(&client, state.max_pages).await?))
+}
+
+async fn class(
+    ApiClient(client): ApiClient,
+    State(state): State<AppState>,
+    ApiPath(id): ApiPath<String>,
+) -> Result<Json<ClassPage>, ApiError> {
+    Ok(Json(
+        routes::get_class(&client, &id, state.max_pages).await?,
+    ))
+}
+
+async fn announcements(
+    ApiClient(client): ApiClient,
+    ApiPath(id): ApiPath<String>,
+    ApiQuery(pages): ApiQuery<PaginationQuery>,
+) -> Result<Json<PostPage>, ApiError> {
+    Ok(Json(
+        routes::get_announcements(&client, &id, pages.page).await?,
+    ))
+}
+
+async fn material(
+    ApiClient(client): ApiClient,
+    ApiPath((course_id, id)): ApiPath<(String, String)>,
+) -> Result<Json<MaterialPage>, ApiError> {
+    Ok(Json(routes::get_material(&client, &course_id, &id).await?))
+}
+
+async fn people(
+    ApiClient(client): ApiClient,
+    State(state): State<AppState>,
+    ApiPath(id): ApiPath<String>,
+    ApiQuery(pages): ApiQuery<PaginationQuery>,
+) -> Result<Json<PeoplePage>, ApiError> {
+    Ok(Json(
+        routes::get_people(&client, &id, pages.page, state.max_pages).await?,
+    ))
+}
+
+async fn grades(
+    ApiClient(client): ApiClient,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<ClassGrades>>, ApiError> {
+    Ok(Json(routes::all_grades(&client, state.max_pages).await?))
+}
+
+async fn grades_for_class(
+    ApiClient(client): ApiClient,
+    State(state): State<AppState>,
+    ApiPath(course_id): ApiPath<String>,
+) -> Result<Json<ClassGrades>, ApiError> {
+    Ok(Json(
+        routes::class_grades(&client, &course_id, state.max_pages).await?,
+    ))
+}
+
+async fn todos_all(
+    ApiClient(client): ApiClient,
+    ApiUserId(user): ApiUserId,
+    State(state): State<AppState>,
+) -> Result<Json<TodoList>, ApiError> {
+    let policy = state.completion_policy(&user).await;
+    let todos = routes::all_todos(&client, policy, state.max_pages).await?;
+    Ok(Json(
+        routes::mark_todos(&state, &user, todos, None, Utc::now()).await?,
+    ))
+}
+
+async fn todos_for_class(
+    ApiClient(client): ApiClient,
+    ApiUserId(user): ApiUserId,
+    State(state): State<AppState>,
+    ApiPath(course_id): ApiPath<String>,
+) -> Result<Json<TodoList>, ApiError> {
+    let policy = state.completion_policy(&user).await;
+    let todos = routes::class_todos(&client, &course_id, policy, state.max_pages).await?;
+    Ok(Json(
+        routes::mark_todos(&state, &user, todos, Some(&course_id), Utc::now()).await?,
+    ))
+}
+
+async fn assignment(
+    ApiClient(client): ApiClient,
+    ApiPath((course_id, id)): ApiPath<(String, String)>,
+) -> Result<Json<Assignment>, ApiError> {
+    Ok(Json(
+        routes::get_assignment(&client, &course_id, &id).await?,
+    ))
+}
@@ -1,10 +1,22 @@
-use axum::{extract::FromRequestParts, http::request::Parts};
-use oauth2::{reqwest::async_http_client, RefreshToken, TokenResponse};
-use tower_cookies::Cookies;
+use axum::{
+    extract::{FromRequestParts, Query},
+    http::request::Parts,
+};
+use oauth2::{
+    basic::BasicErrorResponseType, reqwest::async_http_client, RefreshToken, RequestTokenError,
+    TokenResponse,
+};
+use tower_cookies::{Cookie, Cookies, PrivateCookies};
 
-use crate::{state::ClassroomHyperClient, AppState, Error};
+use crate::{
+    backend::Backend,
+    oauth::{clear_tokens, store_tokens},
+    AppState, Error,
+};
 
-pub struct UserClient(pub classroom::Classroom<ClassroomHyperClient>);
+/// The Classroom API as the signed-in user, with list calls cached per user.
+/// A `refresh` query parameter skips the cache.
+pub struct UserClient(pub Backend);
 
 #[axum::async_trait]
 impl FromRequestParts<AppState> for UserClient {
@@ -25,14 +37,91 @@ impl FromRequestParts<AppState> for UserClient {
             let Some(refresh_token) = cookies.get("refresh") else {
                 return Err(Error::NoToken);
             };
-            let access = state
+            let access = match state
                 .oauth
-                .exchange_refresh_token(&RefreshToken::new(refresh_token.to_string()))
+                .exchange_refresh_token(&RefreshToken::new(refresh_token.value().to_string()))
                 .request_async(async_http_client)
-                .await?;
-            access.access_token().secret().to_string()
+                .await
+            {
+                Ok(v) => v,
+                Err(RequestTokenError::ServerResponse(resp))
+                    if *resp.error() == BasicErrorResponseType::InvalidGrant =>
+                {
+                    // The refresh token was revoked or expired, so the user has to sign in again
+                    clear_tokens(&cookies);
+                    return Err(Error::NoToken);
+                }
+                Err(e) => return Err(e.into()),
+            };
+            store_tokens(&cookies, &access)?;
+            access.access_token().secret().clone()
         };
-        let clrm = classroom::Classroom::new(state.client.clone(), access_token);
-        Ok(Self(clrm))
+        let user = user_id(&cookies, &state.backend(access_token.clone())).await?;
+        let refresh = Query::<RefreshQuery>::from_request_parts(parts, state)
+            .await
+            .is_ok_and(|Query(query)| query.refresh.is_some());
+        Ok(Self(state.cached_backend(access_token, &user, refresh)))
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct RefreshQuery {
+    refresh: Option<String>,
+}
+
+/// The user id in the private `user` cookie, looking it up with `client` if it isn't set yet.
+async fn user_id(cookies: &PrivateCookies<'_>, client: &Backend) -> Result<String, Error> {
+    if let Some(user) = cookies.get("user") {
+        return Ok(user.value().to_string());
+    }
+    let profile = client.get_user_profile("me").await?;
+    let id = profile.id.ok_or(Error::MissingField("userProfiles.id"))?;
+    let mut cookie = Cookie::new("user", id.clone());
+    cookie.set_path("/");
+    cookies.add(cookie);
+    Ok(id)
+}
+
+/// Whether the user has turned on teacher mode, which is only possible once they've granted
+/// css the teacher scopes. Stored in a private `teacher` cookie.
+pub struct TeacherMode(pub bool);
+
+#[axum::async_trait]
+impl FromRequestParts<AppState> for TeacherMode {
+    type Rejection = Error;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let cookies = match Cookies::from_request_parts(parts, state).await {
+            Ok(v) => v,
+            Err(e) => return Err(Error::Extractor(e.1)),
+        };
+        Ok(Self(cookies.private(&state.key).get("teacher").is_some()))
+    }
+}
+
+/// The signed-in user's Classroom user id, cached in a private `user` cookie.
+pub struct UserId(pub String);
+
+#[axum::async_trait]
+impl FromRequestParts<AppState> for UserId {
+    type Rejection = Error;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let cookies = match Cookies::from_request_parts(parts, state).await {
+            Ok(v) => v,
+            Err(e) => return Err(Error::Extractor(e.1)),
+        };
+        let cookies = cookies.private(&state.key);
+        if let Some(user) = cookies.get("user") {
+            return Ok(Self(user.value().to_string()));
+        }
+        let UserClient(client) = UserClient::from_request_parts(parts, state).await?;
+        Ok(Self(user_id(&cookies, &client).await?))
     }
 }
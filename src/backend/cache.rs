@@ -0,0 +1,606 @@
+use std::{
+    any::Any,
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use classroom::api::{
+    Attachment, Course, CourseWork, CourseWorkMaterial, ListAnnouncementsResponse,
+    ListCourseWorkMaterialResponse, ListCourseWorkResponse, ListCoursesResponse,
+    ListStudentSubmissionsResponse, ListStudentsResponse, ListTeachersResponse, ListTopicResponse,
+    ModifyCourseWorkAssigneesRequest, StudentSubmission, UserProfile,
+};
+use sha2::{Digest, Sha256};
+
+use super::{Backend, ClassroomBackend};
+use crate::Error;
+
+/// Recently fetched Classroom lists, shared between every user's [`CachedBackend`].
+pub struct Cache {
+    entries: Mutex<HashMap<(String, String), Entry>>,
+    /// How long an entry is served without asking Google again
+    ttl: Duration,
+    /// How long after `ttl` an entry may still be served while it's refetched in the background
+    stale: Duration,
+    /// The most entries kept at once, across every user
+    capacity: usize,
+}
+
+struct Entry {
+    value: Arc<dyn Any + Send + Sync>,
+    fetched: Instant,
+    revalidating: bool,
+}
+
+enum Lookup<T> {
+    Fresh(T),
+    /// Past its TTL; the caller should refetch it in the background
+    Stale(T),
+    Miss,
+}
+
+impl Cache {
+    #[must_use]
+    pub fn new(ttl: Duration, stale: Duration, capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            ttl,
+            stale,
+            capacity,
+        }
+    }
+
+    // the entry is borrowed from the guard, so the lock can't be released any sooner
+    #[allow(clippy::significant_drop_tightening)]
+    fn lookup<T: Clone + 'static>(&self, key: &(String, String)) -> Lookup<T> {
+        let mut entries = self.entries.lock().unwrap();
+        let Some(entry) = entries.get_mut(key) else {
+            return Lookup::Miss;
+        };
+        let Some(value) = entry.value.downcast_ref::<T>().cloned() else {
+            return Lookup::Miss;
+        };
+        let age = entry.fetched.elapsed();
+        if age < self.ttl {
+            Lookup::Fresh(value)
+        } else if age < self.ttl + self.stale {
+            if entry.revalidating {
+                // someone else is already refetching it
+                Lookup::Fresh(value)
+            } else {
+                entry.revalidating = true;
+                Lookup::Stale(value)
+            }
+        } else {
+            Lookup::Miss
+        }
+    }
+
+    fn insert<T: Send + Sync + 'static>(&self, key: (String, String), value: T) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            let max_age = self.ttl + self.stale;
+            entries.retain(|_, entry| entry.fetched.elapsed() < max_age);
+        }
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.fetched)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(
+            key,
+            Entry {
+                value: Arc::new(value),
+                fetched: Instant::now(),
+                revalidating: false,
+            },
+        );
+    }
+
+    /// Drop every entry of `user`'s whose key starts with `prefix`.
+    fn forget(&self, user: &str, prefix: &str) {
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|(owner, key), _| owner != user || !key.starts_with(prefix));
+    }
+
+    /// Let the next request for `key` try to revalidate it again, after a failed refetch.
+    fn revalidation_failed(&self, key: &(String, String)) {
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(key) {
+            entry.revalidating = false;
+        }
+    }
+}
+
+/// A [`ClassroomBackend`] that answers list calls from a [`Cache`] when it can.
+///
+/// Entries are keyed by a hash of the user's Classroom id, so cached data survives
+/// access token refreshes without the cache holding anything that identifies the user.
+pub struct CachedBackend {
+    inner: Backend,
+    cache: Arc<Cache>,
+    user: String,
+    /// Skip cached entries, for when the user asked for fresh data
+    refresh: bool,
+}
+
+impl CachedBackend {
+    #[must_use]
+    pub fn new(inner: Backend, cache: Arc<Cache>, user: &str, refresh: bool) -> Self {
+        Self {
+            inner,
+            cache,
+            user: hex::encode(Sha256::digest(user.as_bytes())),
+            refresh,
+        }
+    }
+
+    /// Forget the user's cached submissions in a course after changing one of them.
+    /// This runs even if the change failed, since it may have gone through anyway.
+    fn forget_submissions(&self, course_id: &str) {
+        self.cache
+            .forget(&self.user, &format!("courses/{course_id}/courseWork/"));
+    }
+
+    /// Forget the user's cached coursework, materials and submissions in a course after
+    /// changing its classwork, like [`Self::forget_submissions`].
+    fn forget_classwork(&self, course_id: &str) {
+        // also matches courseWorkMaterials
+        self.cache
+            .forget(&self.user, &format!("courses/{course_id}/courseWork"));
+    }
+
+    /// Serve `key` from the cache, calling `fetch` with the uncached backend on a miss.
+    async fn cached<T, F, Fut>(&self, key: String, fetch: F) -> Result<T, Error>
+    where
+        T: Clone + Send + Sync + 'static,
+        F: FnOnce(Backend) -> Fut + Send,
+        Fut: Future<Output = Result<T, Error>> + Send + 'static,
+    {
+        let key = (self.user.clone(), key);
+        if !self.refresh {
+            match self.cache.lookup(&key) {
+                Lookup::Fresh(value) => return Ok(value),
+                Lookup::Stale(value) => {
+                    let cache = self.cache.clone();
+                    let fetching = fetch(self.inner.clone());
+                    tokio::spawn(async move {
+                        match fetching.await {
+                            Ok(value) => cache.insert(key, value),
+                            Err(_) => cache.revalidation_failed(&key),
+                        }
+                    });
+                    return Ok(value);
+                }
+                Lookup::Miss => {}
+            }
+        }
+        let value = fetch(self.inner.clone()).await?;
+        self.cache.insert(key, value.clone());
+        Ok(value)
+    }
+}
+
+#[axum::async_trait]
+impl ClassroomBackend for CachedBackend {
+    async fn list_courses(&self, page: Option<&str>) -> Result<ListCoursesResponse, Error> {
+        let page = page.map(str::to_string);
+        self.cached(format!("courses?{page:?}"), move |inner| async move {
+            inner.list_courses(page.as_deref()).await
+        })
+        .await
+    }
+
+    async fn get_course(&self, id: &str) -> Result<Course, Error> {
+        self.inner.get_course(id).await
+    }
+
+    async fn list_course_work(
+        &self,
+        course_id: &str,
+        page: Option<&str>,
+        page_size: Option<i32>,
+    ) -> Result<ListCourseWorkResponse, Error> {
+        let course_id = course_id.to_string();
+        let page = page.map(str::to_string);
+        self.cached(
+            format!("courses/{course_id}/courseWork?{page:?}&{page_size:?}"),
+            move |inner| async move {
+                inner
+                    .list_course_work(&course_id, page.as_deref(), page_size)
+                    .await
+            },
+        )
+        .await
+    }
+
+    async fn get_course_work(&self, course_id: &str, id: &str) -> Result<CourseWork, Error> {
+        self.inner.get_course_work(course_id, id).await
+    }
+
+    async fn create_course_work(
+        &self,
+        course_id: &str,
+        course_work: CourseWork,
+    ) -> Result<CourseWork, Error> {
+        let result = self.inner.create_course_work(course_id, course_work).await;
+        self.forget_classwork(course_id);
+        result
+    }
+
+    async fn patch_course_work(
+        &self,
+        course_id: &str,
+        id: &str,
+        course_work: CourseWork,
+        update_mask: &str,
+    ) -> Result<CourseWork, Error> {
+        let result = self
+            .inner
+            .patch_course_work(course_id, id, course_work, update_mask)
+            .await;
+        self.forget_classwork(course_id);
+        result
+    }
+
+    async fn modify_course_work_assignees(
+        &self,
+        course_id: &str,
+        id: &str,
+        request: ModifyCourseWorkAssigneesRequest,
+    ) -> Result<(), Error> {
+        let result = self
+            .inner
+            .modify_course_work_assignees(course_id, id, request)
+            .await;
+        self.forget_classwork(course_id);
+        result
+    }
+
+    async fn delete_course_work(&self, course_id: &str, id: &str) -> Result<(), Error> {
+        let result = self.inner.delete_course_work(course_id, id).await;
+        self.forget_classwork(course_id);
+        result
+    }
+
+    async fn list_course_work_materials(
+        &self,
+        course_id: &str,
+        page: Option<&str>,
+    ) -> Result<ListCourseWorkMaterialResponse, Error> {
+        let course_id = course_id.to_string();
+        let page = page.map(str::to_string);
+        self.cached(
+            format!("courses/{course_id}/courseWorkMaterials?{page:?}"),
+            move |inner| async move {
+                inner
+                    .list_course_work_materials(&course_id, page.as_deref())
+                    .await
+            },
+        )
+        .await
+    }
+
+    async fn get_course_work_material(
+        &self,
+        course_id: &str,
+        id: &str,
+    ) -> Result<CourseWorkMaterial, Error> {
+        self.inner.get_course_work_material(course_id, id).await
+    }
+
+    async fn create_course_work_material(
+        &self,
+        course_id: &str,
+        material: CourseWorkMaterial,
+    ) -> Result<CourseWorkMaterial, Error> {
+        let result = self
+            .inner
+            .create_course_work_material(course_id, material)
+            .await;
+        self.forget_classwork(course_id);
+        result
+    }
+
+    async fn patch_course_work_material(
+        &self,
+        course_id: &str,
+        id: &str,
+        material: CourseWorkMaterial,
+        update_mask: &str,
+    ) -> Result<CourseWorkMaterial, Error> {
+        let result = self
+            .inner
+            .patch_course_work_material(course_id, id, material, update_mask)
+            .await;
+        self.forget_classwork(course_id);
+        result
+    }
+
+    async fn delete_course_work_material(&self, course_id: &str, id: &str) -> Result<(), Error> {
+        let result = self.inner.delete_course_work_material(course_id, id).await;
+        self.forget_classwork(course_id);
+        result
+    }
+
+    async fn list_topics(
+        &self,
+        course_id: &str,
+        page: Option<&str>,
+    ) -> Result<ListTopicResponse, Error> {
+        let course_id = course_id.to_string();
+        let page = page.map(str::to_string);
+        self.cached(
+            format!("courses/{course_id}/topics?{page:?}"),
+            move |inner| async move { inner.list_topics(&course_id, page.as_deref()).await },
+        )
+        .await
+    }
+
+    async fn list_submissions(
+        &self,
+        course_id: &str,
+        course_work_id: &str,
+        page: Option<&str>,
+    ) -> Result<ListStudentSubmissionsResponse, Error> {
+        let course_id = course_id.to_string();
+        let course_work_id = course_work_id.to_string();
+        let page = page.map(str::to_string);
+        self.cached(
+            format!("courses/{course_id}/courseWork/{course_work_id}/studentSubmissions?{page:?}"),
+            move |inner| async move {
+                inner
+                    .list_submissions(&course_id, &course_work_id, page.as_deref())
+                    .await
+            },
+        )
+        .await
+    }
+
+    async fn list_student_submissions(
+        &self,
+        course_id: &str,
+        course_work_id: &str,
+        page: Option<&str>,
+    ) -> Result<ListStudentSubmissionsResponse, Error> {
+        let course_id = course_id.to_string();
+        let course_work_id = course_work_id.to_string();
+        let page = page.map(str::to_string);
+        self.cached(
+            format!(
+                "courses/{course_id}/courseWork/{course_work_id}/studentSubmissions?all&{page:?}"
+            ),
+            move |inner| async move {
+                inner
+                    .list_student_submissions(&course_id, &course_work_id, page.as_deref())
+                    .await
+            },
+        )
+        .await
+    }
+
+    async fn turn_in(&self, course_id: &str, course_work_id: &str, id: &str) -> Result<(), Error> {
+        let result = self.inner.turn_in(course_id, course_work_id, id).await;
+        self.forget_submissions(course_id);
+        result
+    }
+
+    async fn reclaim(&self, course_id: &str, course_work_id: &str, id: &str) -> Result<(), Error> {
+        let result = self.inner.reclaim(course_id, course_work_id, id).await;
+        self.forget_submissions(course_id);
+        result
+    }
+
+    async fn add_attachments(
+        &self,
+        course_id: &str,
+        course_work_id: &str,
+        id: &str,
+        attachments: Vec<Attachment>,
+    ) -> Result<(), Error> {
+        let result = self
+            .inner
+            .add_attachments(course_id, course_work_id, id, attachments)
+            .await;
+        self.forget_submissions(course_id);
+        result
+    }
+
+    async fn patch_submission(
+        &self,
+        course_id: &str,
+        course_work_id: &str,
+        id: &str,
+        submission: StudentSubmission,
+        update_mask: &str,
+    ) -> Result<(), Error> {
+        let result = self
+            .inner
+            .patch_submission(course_id, course_work_id, id, submission, update_mask)
+            .await;
+        self.forget_submissions(course_id);
+        result
+    }
+
+    async fn return_submission(
+        &self,
+        course_id: &str,
+        course_work_id: &str,
+        id: &str,
+    ) -> Result<(), Error> {
+        let result = self
+            .inner
+            .return_submission(course_id, course_work_id, id)
+            .await;
+        self.forget_submissions(course_id);
+        result
+    }
+
+    async fn is_teacher(&self, course_id: &str) -> Result<bool, Error> {
+        self.inner.is_teacher(course_id).await
+    }
+
+    async fn list_students(
+        &self,
+        course_id: &str,
+        page: Option<&str>,
+    ) -> Result<ListStudentsResponse, Error> {
+        let course_id = course_id.to_string();
+        let page = page.map(str::to_string);
+        self.cached(
+            format!("courses/{course_id}/students?{page:?}"),
+            move |inner| async move { inner.list_students(&course_id, page.as_deref()).await },
+        )
+        .await
+    }
+
+    async fn list_teachers(
+        &self,
+        course_id: &str,
+        page: Option<&str>,
+    ) -> Result<ListTeachersResponse, Error> {
+        let course_id = course_id.to_string();
+        let page = page.map(str::to_string);
+        self.cached(
+            format!("courses/{course_id}/teachers?{page:?}"),
+            move |inner| async move { inner.list_teachers(&course_id, page.as_deref()).await },
+        )
+        .await
+    }
+
+    async fn list_announcements(
+        &self,
+        course_id: &str,
+        page: Option<&str>,
+        page_size: Option<i32>,
+    ) -> Result<ListAnnouncementsResponse, Error> {
+        let course_id = course_id.to_string();
+        let page = page.map(str::to_string);
+        self.cached(
+            format!("courses/{course_id}/announcements?{page:?}&{page_size:?}"),
+            move |inner| async move {
+                inner
+                    .list_announcements(&course_id, page.as_deref(), page_size)
+                    .await
+            },
+        )
+        .await
+    }
+
+    async fn get_user_profile(&self, user_id: &str) -> Result<UserProfile, Error> {
+        // other people's names hardly ever change, and posts repeat the same few authors
+        let user_id = user_id.to_string();
+        self.cached(format!("userProfiles/{user_id}"), move |inner| async move {
+            inner.get_user_profile(&user_id).await
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::memory::MemoryBackend;
+
+    const HOUR: Duration = Duration::from_hours(1);
+
+    fn served(memory: &MemoryBackend) -> usize {
+        memory
+            .pages_served
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    fn backend(memory: &Arc<MemoryBackend>, cache: &Arc<Cache>, user: &str) -> CachedBackend {
+        CachedBackend::new(memory.clone(), cache.clone(), user, false)
+    }
+
+    #[tokio::test]
+    async fn serves_fresh_entries_from_cache() {
+        let memory = Arc::new(MemoryBackend::fixture());
+        let cache = Arc::new(Cache::new(HOUR, HOUR, 100));
+        let client = backend(&memory, &cache, "student");
+        client.list_courses(None).await.unwrap();
+        let cached = client.list_courses(None).await.unwrap();
+        assert_eq!(cached.courses.unwrap().len(), 2);
+        client.list_course_work("math", None, None).await.unwrap();
+        assert_eq!(served(&memory), 2);
+        // a different user doesn't get someone else's classes
+        backend(&memory, &cache, "other")
+            .list_courses(None)
+            .await
+            .unwrap();
+        assert_eq!(served(&memory), 3);
+    }
+
+    #[tokio::test]
+    async fn refresh_skips_cache() {
+        let memory = Arc::new(MemoryBackend::fixture());
+        let cache = Arc::new(Cache::new(HOUR, HOUR, 100));
+        backend(&memory, &cache, "student")
+            .list_courses(None)
+            .await
+            .unwrap();
+        let refreshing = CachedBackend::new(memory.clone(), cache.clone(), "student", true);
+        refreshing.list_courses(None).await.unwrap();
+        assert_eq!(served(&memory), 2);
+        // the refreshed copy is cached for everyone after
+        backend(&memory, &cache, "student")
+            .list_courses(None)
+            .await
+            .unwrap();
+        assert_eq!(served(&memory), 2);
+    }
+
+    #[tokio::test]
+    async fn revalidates_stale_entries_in_background() {
+        let memory = Arc::new(MemoryBackend::fixture());
+        let cache = Arc::new(Cache::new(Duration::ZERO, HOUR, 100));
+        let client = backend(&memory, &cache, "student");
+        client.list_courses(None).await.unwrap();
+        let stale = client.list_courses(None).await.unwrap();
+        assert!(stale.courses.is_some());
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(served(&memory), 2);
+    }
+
+    #[tokio::test]
+    async fn evicts_oldest_entries_when_full() {
+        let memory = Arc::new(MemoryBackend::fixture());
+        let cache = Arc::new(Cache::new(HOUR, HOUR, 1));
+        let client = backend(&memory, &cache, "student");
+        client.list_courses(None).await.unwrap();
+        client.list_course_work("math", None, None).await.unwrap();
+        client.list_courses(None).await.unwrap();
+        assert_eq!(served(&memory), 3);
+        assert_eq!(cache.entries.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn forgets_classwork_after_changing_it() {
+        let mut memory = MemoryBackend::fixture();
+        memory.teaching.push("math".to_string());
+        let memory = Arc::new(memory);
+        let cache = Arc::new(Cache::new(HOUR, HOUR, 100));
+        let client = backend(&memory, &cache, "teacher");
+        client.list_course_work("math", None, None).await.unwrap();
+        client.list_topics("math", None).await.unwrap();
+        client.delete_course_work("math", "hw2").await.unwrap();
+        let work = client.list_course_work("math", None, None).await.unwrap();
+        assert_eq!(work.course_work.unwrap().len(), 2);
+        client.list_topics("math", None).await.unwrap();
+        // only the coursework list was fetched again
+        assert_eq!(served(&memory), 3);
+    }
+}
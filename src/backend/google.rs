@@ -0,0 +1,440 @@
+use axum::http::StatusCode;
+use classroom::{
+    api::{
+        Attachment, Course, CourseWork, CourseWorkMaterial, ListAnnouncementsResponse,
+        ListCourseWorkMaterialResponse, ListCourseWorkResponse, ListCoursesResponse,
+        ListStudentSubmissionsResponse, ListStudentsResponse, ListTeachersResponse,
+        ListTopicResponse, ModifyAttachmentsRequest, ModifyCourseWorkAssigneesRequest,
+        ReclaimStudentSubmissionRequest, ReturnStudentSubmissionRequest, StudentSubmission,
+        TurnInStudentSubmissionRequest, UserProfile,
+    },
+    Classroom, FieldMask,
+};
+
+use super::ClassroomBackend;
+use crate::{
+    state::{ClassroomHttpClient, ClassroomHyperClient},
+    Error,
+};
+
+/// The real Classroom API, acting as the user who owns `access_token`.
+pub struct GoogleBackend(Classroom<ClassroomHyperClient>);
+
+impl GoogleBackend {
+    /// `base_url` is normally `https://classroom.googleapis.com/`, and must end with a slash.
+    pub fn new(client: ClassroomHttpClient, base_url: &str, access_token: String) -> Self {
+        let mut classroom = Classroom::new(client, access_token);
+        classroom.base_url(base_url.to_string());
+        classroom.root_url(base_url.to_string());
+        Self(classroom)
+    }
+
+    /// List the submissions the user can see, or only `user_id`'s.
+    async fn submissions(
+        &self,
+        course_id: &str,
+        course_work_id: &str,
+        page: Option<&str>,
+        user_id: Option<&str>,
+    ) -> Result<ListStudentSubmissionsResponse, Error> {
+        let mut req = self
+            .0
+            .courses()
+            .course_work_student_submissions_list(course_id, course_work_id)
+            .param(
+                "fields",
+                "nextPageToken,studentSubmissions(courseWorkId,userId,state,late,id,assignedGrade,draftGrade,alternateLink,assignmentSubmission,shortAnswerSubmission,multipleChoiceSubmission,submissionHistory)",
+            );
+        if let Some(page) = page {
+            req = req.page_token(page);
+        }
+        if let Some(user_id) = user_id {
+            req = req.user_id(user_id);
+        }
+        Ok(req.doit().await?.1)
+    }
+}
+
+#[axum::async_trait]
+impl ClassroomBackend for GoogleBackend {
+    async fn list_courses(&self, page: Option<&str>) -> Result<ListCoursesResponse, Error> {
+        let mut req = self
+            .0
+            .courses()
+            .list()
+            .param("fields", "nextPageToken,courses(id,name,gradebookSettings)");
+        if let Some(page) = page {
+            req = req.page_token(page);
+        }
+        Ok(req.doit().await?.1)
+    }
+
+    async fn get_course(&self, id: &str) -> Result<Course, Error> {
+        let req = self
+            .0
+            .courses()
+            .get(id)
+            .param("fields", "id,name,gradebookSettings");
+        Ok(req.doit().await?.1)
+    }
+
+    async fn list_course_work(
+        &self,
+        course_id: &str,
+        page: Option<&str>,
+        page_size: Option<i32>,
+    ) -> Result<ListCourseWorkResponse, Error> {
+        let mut req = self.0.courses().course_work_list(course_id).param(
+            "fields",
+            "nextPageToken,courseWork(id,title,description,dueDate,dueTime,maxPoints,gradeCategory,alternateLink,topicId,creationTime)",
+        );
+        if let Some(page) = page {
+            req = req.page_token(page);
+        }
+        if let Some(page_size) = page_size {
+            req = req.page_size(page_size);
+        }
+        Ok(req.doit().await?.1)
+    }
+
+    async fn get_course_work(&self, course_id: &str, id: &str) -> Result<CourseWork, Error> {
+        let req = self.0.courses().course_work_get(course_id, id).param(
+            "fields",
+            "id,title,description,dueDate,dueTime,maxPoints,workType,multipleChoiceQuestion,materials,alternateLink,topicId,state,scheduledTime,assigneeMode,individualStudentsOptions,associatedWithDeveloper",
+        );
+        Ok(req.doit().await?.1)
+    }
+
+    async fn create_course_work(
+        &self,
+        course_id: &str,
+        course_work: CourseWork,
+    ) -> Result<CourseWork, Error> {
+        let req = self
+            .0
+            .courses()
+            .course_work_create(course_work, course_id)
+            .param("fields", "id");
+        Ok(req.doit().await?.1)
+    }
+
+    async fn patch_course_work(
+        &self,
+        course_id: &str,
+        id: &str,
+        course_work: CourseWork,
+        update_mask: &str,
+    ) -> Result<CourseWork, Error> {
+        let Ok(update_mask) = update_mask.parse::<FieldMask>();
+        let req = self
+            .0
+            .courses()
+            .course_work_patch(course_work, course_id, id)
+            .update_mask(update_mask)
+            .param("fields", "id");
+        Ok(req.doit().await?.1)
+    }
+
+    async fn modify_course_work_assignees(
+        &self,
+        course_id: &str,
+        id: &str,
+        request: ModifyCourseWorkAssigneesRequest,
+    ) -> Result<(), Error> {
+        self.0
+            .courses()
+            .course_work_modify_assignees(request, course_id, id)
+            .param("fields", "id")
+            .doit()
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_course_work(&self, course_id: &str, id: &str) -> Result<(), Error> {
+        self.0
+            .courses()
+            .course_work_delete(course_id, id)
+            .doit()
+            .await?;
+        Ok(())
+    }
+
+    async fn list_course_work_materials(
+        &self,
+        course_id: &str,
+        page: Option<&str>,
+    ) -> Result<ListCourseWorkMaterialResponse, Error> {
+        let mut req = self
+            .0
+            .courses()
+            .course_work_materials_list(course_id)
+            .param(
+                "fields",
+                "nextPageToken,courseWorkMaterial(id,title,description,topicId,creationTime)",
+            );
+        if let Some(page) = page {
+            req = req.page_token(page);
+        }
+        Ok(req.doit().await?.1)
+    }
+
+    async fn get_course_work_material(
+        &self,
+        course_id: &str,
+        id: &str,
+    ) -> Result<CourseWorkMaterial, Error> {
+        let req = self
+            .0
+            .courses()
+            .course_work_materials_get(course_id, id)
+            .param(
+            "fields",
+            "id,title,description,materials,alternateLink,creationTime,topicId,state,scheduledTime",
+        );
+        Ok(req.doit().await?.1)
+    }
+
+    async fn create_course_work_material(
+        &self,
+        course_id: &str,
+        material: CourseWorkMaterial,
+    ) -> Result<CourseWorkMaterial, Error> {
+        let req = self
+            .0
+            .courses()
+            .course_work_materials_create(material, course_id)
+            .param("fields", "id");
+        Ok(req.doit().await?.1)
+    }
+
+    async fn patch_course_work_material(
+        &self,
+        course_id: &str,
+        id: &str,
+        material: CourseWorkMaterial,
+        update_mask: &str,
+    ) -> Result<CourseWorkMaterial, Error> {
+        let Ok(update_mask) = update_mask.parse::<FieldMask>();
+        let req = self
+            .0
+            .courses()
+            .course_work_materials_patch(material, course_id, id)
+            .update_mask(update_mask)
+            .param("fields", "id");
+        Ok(req.doit().await?.1)
+    }
+
+    async fn delete_course_work_material(&self, course_id: &str, id: &str) -> Result<(), Error> {
+        self.0
+            .courses()
+            .course_work_materials_delete(course_id, id)
+            .doit()
+            .await?;
+        Ok(())
+    }
+
+    async fn list_topics(
+        &self,
+        course_id: &str,
+        page: Option<&str>,
+    ) -> Result<ListTopicResponse, Error> {
+        let mut req = self
+            .0
+            .courses()
+            .topics_list(course_id)
+            .param("fields", "nextPageToken,topic(topicId,name)");
+        if let Some(page) = page {
+            req = req.page_token(page);
+        }
+        Ok(req.doit().await?.1)
+    }
+
+    async fn list_submissions(
+        &self,
+        course_id: &str,
+        course_work_id: &str,
+        page: Option<&str>,
+    ) -> Result<ListStudentSubmissionsResponse, Error> {
+        // teachers would get every student's submissions otherwise
+        self.submissions(course_id, course_work_id, page, Some("me"))
+            .await
+    }
+
+    async fn list_student_submissions(
+        &self,
+        course_id: &str,
+        course_work_id: &str,
+        page: Option<&str>,
+    ) -> Result<ListStudentSubmissionsResponse, Error> {
+        self.submissions(course_id, course_work_id, page, None)
+            .await
+    }
+
+    async fn turn_in(&self, course_id: &str, course_work_id: &str, id: &str) -> Result<(), Error> {
+        self.0
+            .courses()
+            .course_work_student_submissions_turn_in(
+                TurnInStudentSubmissionRequest::default(),
+                course_id,
+                course_work_id,
+                id,
+            )
+            .doit()
+            .await?;
+        Ok(())
+    }
+
+    async fn reclaim(&self, course_id: &str, course_work_id: &str, id: &str) -> Result<(), Error> {
+        self.0
+            .courses()
+            .course_work_student_submissions_reclaim(
+                ReclaimStudentSubmissionRequest::default(),
+                course_id,
+                course_work_id,
+                id,
+            )
+            .doit()
+            .await?;
+        Ok(())
+    }
+
+    async fn add_attachments(
+        &self,
+        course_id: &str,
+        course_work_id: &str,
+        id: &str,
+        attachments: Vec<Attachment>,
+    ) -> Result<(), Error> {
+        let request = ModifyAttachmentsRequest {
+            add_attachments: Some(attachments),
+        };
+        self.0
+            .courses()
+            .course_work_student_submissions_modify_attachments(
+                request,
+                course_id,
+                course_work_id,
+                id,
+            )
+            .param("fields", "id")
+            .doit()
+            .await?;
+        Ok(())
+    }
+
+    async fn patch_submission(
+        &self,
+        course_id: &str,
+        course_work_id: &str,
+        id: &str,
+        submission: StudentSubmission,
+        update_mask: &str,
+    ) -> Result<(), Error> {
+        let Ok(update_mask) = update_mask.parse::<FieldMask>();
+        self.0
+            .courses()
+            .course_work_student_submissions_patch(submission, course_id, course_work_id, id)
+            .update_mask(update_mask)
+            .param("fields", "id")
+            .doit()
+            .await?;
+        Ok(())
+    }
+
+    async fn return_submission(
+        &self,
+        course_id: &str,
+        course_work_id: &str,
+        id: &str,
+    ) -> Result<(), Error> {
+        self.0
+            .courses()
+            .course_work_student_submissions_return(
+                ReturnStudentSubmissionRequest::default(),
+                course_id,
+                course_work_id,
+                id,
+            )
+            .doit()
+            .await?;
+        Ok(())
+    }
+
+    async fn is_teacher(&self, course_id: &str) -> Result<bool, Error> {
+        let req = self
+            .0
+            .courses()
+            .teachers_get(course_id, "me")
+            .param("fields", "userId");
+        match req.doit().await {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                let e = Error::from(e);
+                // Classroom says students aren't there at all
+                if e.status_code() == StatusCode::NOT_FOUND {
+                    Ok(false)
+                } else {
+                    Err(e)
+                }
+            }
+        }
+    }
+
+    async fn list_students(
+        &self,
+        course_id: &str,
+        page: Option<&str>,
+    ) -> Result<ListStudentsResponse, Error> {
+        let mut req = self.0.courses().students_list(course_id).param(
+            "fields",
+            "nextPageToken,students(userId,profile(id,name(fullName),emailAddress,photoUrl))",
+        );
+        if let Some(page) = page {
+            req = req.page_token(page);
+        }
+        Ok(req.doit().await?.1)
+    }
+
+    async fn list_teachers(
+        &self,
+        course_id: &str,
+        page: Option<&str>,
+    ) -> Result<ListTeachersResponse, Error> {
+        let mut req = self.0.courses().teachers_list(course_id).param(
+            "fields",
+            "nextPageToken,teachers(userId,profile(id,name(fullName),emailAddress,photoUrl))",
+        );
+        if let Some(page) = page {
+            req = req.page_token(page);
+        }
+        Ok(req.doit().await?.1)
+    }
+
+    async fn list_announcements(
+        &self,
+        course_id: &str,
+        page: Option<&str>,
+        page_size: Option<i32>,
+    ) -> Result<ListAnnouncementsResponse, Error> {
+        let mut req = self.0.courses().announcements_list(course_id).param(
+            "fields",
+            "nextPageToken,announcements(id,text,materials,creatorUserId,creationTime,alternateLink)",
+        );
+        if let Some(page) = page {
+            req = req.page_token(page);
+        }
+        if let Some(page_size) = page_size {
+            req = req.page_size(page_size);
+        }
+        Ok(req.doit().await?.1)
+    }
+
+    async fn get_user_profile(&self, user_id: &str) -> Result<UserProfile, Error> {
+        let req = self
+            .0
+            .user_profiles()
+            .get(user_id)
+            .param("fields", "id,name(fullName)");
+        Ok(req.doit().await?.1)
+    }
+}
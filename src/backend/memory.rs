@@ -0,0 +1,1213 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use classroom::{
+    api::{
+        Announcement, AssignmentSubmission, Attachment, Course, CourseWork, CourseWorkMaterial,
+        Date, IndividualStudentsOptions, Link, ListAnnouncementsResponse,
+        ListCourseWorkMaterialResponse, ListCourseWorkResponse, ListCoursesResponse,
+        ListStudentSubmissionsResponse, ListStudentsResponse, ListTeachersResponse,
+        ListTopicResponse, Material, ModifyCourseWorkAssigneesRequest, MultipleChoiceQuestion,
+        Name, Student, StudentSubmission, Teacher, TimeOfDay, Topic, UserProfile,
+    },
+    chrono::{DateTime, Duration, TimeZone, Utc},
+};
+
+use super::ClassroomBackend;
+use crate::Error;
+
+/// A [`ClassroomBackend`] serving fixed data, for tests.
+pub struct MemoryBackend {
+    pub courses: Vec<Course>,
+    /// Coursework, keyed by course id and behind a lock so teachers can change it
+    pub course_work: Mutex<HashMap<String, Vec<CourseWork>>>,
+    /// Course work materials, keyed by course id and behind a lock like `course_work`
+    pub materials: Mutex<HashMap<String, Vec<CourseWorkMaterial>>>,
+    /// `(course id, id)` of the materials created through css's Google Cloud project.
+    /// Unlike coursework, materials don't say whether they were.
+    pub css_materials: Mutex<Vec<(String, String)>>,
+    /// How much classwork has been created through the API, for numbering it
+    pub created: AtomicUsize,
+    /// Topics, keyed by course id
+    pub topics: HashMap<String, Vec<Topic>>,
+    /// Submissions, keyed by course id and behind a lock so they can be turned in.
+    /// The current user can only list other students' submissions in courses they teach.
+    pub submissions: Mutex<HashMap<String, Vec<StudentSubmission>>>,
+    /// Ids of the courses the current user teaches
+    pub teaching: Vec<String>,
+    /// The user ids of each course's students, keyed by course id
+    pub students: HashMap<String, Vec<String>>,
+    /// The user ids of each course's teachers, keyed by course id
+    pub teachers: HashMap<String, Vec<String>>,
+    /// Announcements, keyed by course id, newest first
+    pub announcements: HashMap<String, Vec<Announcement>>,
+    pub profile: UserProfile,
+    /// Everyone else whose profile can be looked up
+    pub users: Vec<UserProfile>,
+    /// How many items each page of a list holds
+    pub page_size: usize,
+    /// How many pages of lists have been requested so far
+    pub pages_served: AtomicUsize,
+}
+
+impl Default for MemoryBackend {
+    fn default() -> Self {
+        Self {
+            courses: Vec::new(),
+            course_work: Mutex::new(HashMap::new()),
+            materials: Mutex::new(HashMap::new()),
+            css_materials: Mutex::new(Vec::new()),
+            created: AtomicUsize::new(0),
+            topics: HashMap::new(),
+            submissions: Mutex::new(HashMap::new()),
+            teaching: Vec::new(),
+            students: HashMap::new(),
+            teachers: HashMap::new(),
+            announcements: HashMap::new(),
+            profile: UserProfile::default(),
+            users: Vec::new(),
+            page_size: 100,
+            pages_served: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl MemoryBackend {
+    /// Two courses with a few assignments in various states:
+    ///
+    /// - `math`: "Homework 1" (due 2023-09-01 23:59, turned in), "Homework 2"
+    ///   (due 2023-09-08 23:59, assigned) and "Project" (no due date, late)
+    /// - `history`: "Essay" (due 2023-09-05, returned without a grade) and
+    ///   "Reading" (due 2023-09-04, returned with a grade)
+    ///
+    /// In `math`, both homeworks and the "Lecture notes" material (linking to slides)
+    /// are under the "Unit 1" topic, "Unit 2" is empty, and the "Syllabus" material
+    /// has no topic. Classwork is created an hour apart in the order it's added.
+    ///
+    /// `math` also has two announcements from its teacher, Ms. Frizzle, the newest
+    /// of which links to a study guide. The current user is its only student.
+    ///
+    /// "Homework 2" was created through css's Google Cloud project, so it's the only
+    /// coursework whose submissions css may change. The rest was made in Classroom.
+    pub fn fixture() -> Self {
+        let mut backend = Self {
+            profile: UserProfile {
+                id: Some("student".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        backend.add_course("math", "Math");
+        backend.add_work("math", "hw1", "Homework 1", Some((2023, 9, 1, 23, 59)));
+        backend.add_work("math", "hw2", "Homework 2", Some((2023, 9, 8, 23, 59)));
+        backend.add_work("math", "project", "Project", None);
+        backend.add_submission("math", "hw1", "TURNED_IN", false, None);
+        backend.add_submission("math", "hw2", "CREATED", false, None);
+        backend.add_submission("math", "project", "CREATED", true, None);
+        backend.made_by_css("math", "hw2");
+        backend.add_material("math", "syllabus", "Syllabus", None);
+        backend.add_material(
+            "math",
+            "notes",
+            "Lecture notes",
+            Some(("Slides", "https://example.com/slides")),
+        );
+        backend.add_topic("math", "unit1", "Unit 1");
+        backend.add_topic("math", "unit2", "Unit 2");
+        for id in ["hw1", "hw2", "notes"] {
+            backend.set_topic("math", id, "unit1");
+        }
+        backend.add_user("teacher", "Ms. Frizzle");
+        backend.add_teacher("math", "teacher");
+        backend.add_student("math", "student");
+        backend.add_announcement("math", "welcome", "Welcome to math!", "teacher", None);
+        backend.add_announcement(
+            "math",
+            "quiz",
+            "Quiz on Friday",
+            "teacher",
+            Some(("Study guide", "https://example.com/guide")),
+        );
+        backend.add_course("history", "History");
+        backend.add_work("history", "essay", "Essay", Some((2023, 9, 5, 0, 0)));
+        backend.add_work("history", "reading", "Reading", Some((2023, 9, 4, 0, 0)));
+        backend.add_submission("history", "essay", "RETURNED", false, None);
+        backend.add_submission("history", "reading", "RETURNED", false, Some(10.0));
+        backend
+    }
+
+    pub fn add_course(&mut self, id: &str, name: &str) {
+        self.courses.push(Course {
+            id: Some(id.to_string()),
+            name: Some(name.to_string()),
+            ..Default::default()
+        });
+    }
+
+    /// Add coursework, optionally due at `(year, month, day, hours, minutes)` UTC.
+    pub fn add_work(
+        &mut self,
+        course_id: &str,
+        id: &str,
+        title: &str,
+        due: Option<(i32, i32, i32, i32, i32)>,
+    ) {
+        let (due_date, due_time) =
+            due.map_or((None, None), |(year, month, day, hours, minutes)| {
+                (
+                    Some(Date {
+                        year: Some(year),
+                        month: Some(month),
+                        day: Some(day),
+                    }),
+                    Some(TimeOfDay {
+                        hours: Some(hours),
+                        minutes: Some(minutes),
+                        seconds: None,
+                        nanos: None,
+                    }),
+                )
+            });
+        let creation_time = self.next_creation_time(course_id);
+        self.course_work
+            .get_mut()
+            .unwrap()
+            .entry(course_id.to_string())
+            .or_default()
+            .push(CourseWork {
+                course_id: Some(course_id.to_string()),
+                id: Some(id.to_string()),
+                title: Some(title.to_string()),
+                work_type: Some("ASSIGNMENT".to_string()),
+                due_date,
+                due_time,
+                creation_time,
+                ..Default::default()
+            });
+    }
+
+    /// Add a question with no due date: multiple choice if there are `choices`, otherwise
+    /// short answer.
+    pub fn add_question(&mut self, course_id: &str, id: &str, title: &str, choices: &[&str]) {
+        self.add_work(course_id, id, title, None);
+        let work = self.work_mut(course_id, id).unwrap();
+        if choices.is_empty() {
+            work.work_type = Some("SHORT_ANSWER_QUESTION".to_string());
+        } else {
+            work.work_type = Some("MULTIPLE_CHOICE_QUESTION".to_string());
+            work.multiple_choice_question = Some(MultipleChoiceQuestion {
+                choices: Some(choices.iter().map(ToString::to_string).collect()),
+            });
+        }
+    }
+
+    /// Mark coursework as created through css's Google Cloud project, as if css had made it.
+    pub fn made_by_css(&mut self, course_id: &str, id: &str) {
+        self.work_mut(course_id, id)
+            .unwrap()
+            .associated_with_developer = Some(true);
+    }
+
+    /// Mark a material as created through css's Google Cloud project.
+    pub fn material_made_by_css(&mut self, course_id: &str, id: &str) {
+        self.css_materials
+            .get_mut()
+            .unwrap()
+            .push((course_id.to_string(), id.to_string()));
+    }
+
+    pub fn work_mut(&mut self, course_id: &str, id: &str) -> Option<&mut CourseWork> {
+        self.course_work
+            .get_mut()
+            .unwrap()
+            .get_mut(course_id)?
+            .iter_mut()
+            .find(|work| work.id.as_deref() == Some(id))
+    }
+
+    /// The current user's submission for a piece of coursework.
+    pub fn submission_mut(
+        &mut self,
+        course_id: &str,
+        course_work_id: &str,
+    ) -> Option<&mut StudentSubmission> {
+        self.submissions
+            .get_mut()
+            .unwrap()
+            .get_mut(course_id)?
+            .iter_mut()
+            .find(|sub| sub.course_work_id.as_deref() == Some(course_work_id))
+    }
+
+    /// Add a course work material, optionally with a `(title, url)` link attached.
+    pub fn add_material(
+        &mut self,
+        course_id: &str,
+        id: &str,
+        title: &str,
+        link: Option<(&str, &str)>,
+    ) {
+        let creation_time = self.next_creation_time(course_id);
+        self.materials
+            .get_mut()
+            .unwrap()
+            .entry(course_id.to_string())
+            .or_default()
+            .push(CourseWorkMaterial {
+                course_id: Some(course_id.to_string()),
+                id: Some(id.to_string()),
+                title: Some(title.to_string()),
+                creation_time,
+                materials: link.map(|link| vec![link_material(link)]),
+                ..Default::default()
+            });
+    }
+
+    pub fn add_topic(&mut self, course_id: &str, id: &str, name: &str) {
+        self.topics
+            .entry(course_id.to_string())
+            .or_default()
+            .push(Topic {
+                course_id: Some(course_id.to_string()),
+                topic_id: Some(id.to_string()),
+                name: Some(name.to_string()),
+                ..Default::default()
+            });
+    }
+
+    /// Move the coursework or material `id` under a topic.
+    pub fn set_topic(&mut self, course_id: &str, id: &str, topic_id: &str) {
+        let topic = Some(topic_id.to_string());
+        let course_work = self.course_work.get_mut().unwrap();
+        for work in course_work.get_mut(course_id).into_iter().flatten() {
+            if work.id.as_deref() == Some(id) {
+                work.topic_id.clone_from(&topic);
+            }
+        }
+        let materials = self.materials.get_mut().unwrap();
+        for material in materials.get_mut(course_id).into_iter().flatten() {
+            if material.id.as_deref() == Some(id) {
+                material.topic_id.clone_from(&topic);
+            }
+        }
+    }
+
+    /// An hour after the last classwork was added to the course.
+    fn next_creation_time(&self, course_id: &str) -> Option<DateTime<Utc>> {
+        let count = self
+            .course_work
+            .lock()
+            .unwrap()
+            .get(course_id)
+            .map_or(0, Vec::len)
+            + self
+                .materials
+                .lock()
+                .unwrap()
+                .get(course_id)
+                .map_or(0, Vec::len);
+        let start = Utc.with_ymd_and_hms(2023, 8, 1, 0, 0, 0).single()?;
+        Some(start + Duration::hours(i64::try_from(count).ok()?))
+    }
+
+    /// Add the current user's submission for a piece of coursework.
+    pub fn add_submission(
+        &mut self,
+        course_id: &str,
+        course_work_id: &str,
+        state: &str,
+        late: bool,
+        assigned_grade: Option<f64>,
+    ) {
+        self.submissions
+            .get_mut()
+            .unwrap()
+            .entry(course_id.to_string())
+            .or_default()
+            .push(StudentSubmission {
+                course_id: Some(course_id.to_string()),
+                course_work_id: Some(course_work_id.to_string()),
+                id: Some(format!("{course_work_id}-submission")),
+                state: Some(state.to_string()),
+                late: Some(late),
+                assigned_grade,
+                user_id: self.profile.id.clone(),
+                ..Default::default()
+            });
+    }
+
+    /// Add another student's submission, which the current user sees if they teach the course.
+    pub fn add_student_submission(
+        &mut self,
+        course_id: &str,
+        course_work_id: &str,
+        user_id: &str,
+        state: &str,
+    ) {
+        self.submissions
+            .get_mut()
+            .unwrap()
+            .entry(course_id.to_string())
+            .or_default()
+            .push(StudentSubmission {
+                course_id: Some(course_id.to_string()),
+                course_work_id: Some(course_work_id.to_string()),
+                id: Some(format!("{course_work_id}-{user_id}")),
+                state: Some(state.to_string()),
+                late: Some(false),
+                user_id: Some(user_id.to_string()),
+                ..Default::default()
+            });
+    }
+
+    /// A page of submissions to `course_work_id` (or `-` for all), only `user_id`'s if given.
+    fn submissions(
+        &self,
+        course_id: &str,
+        course_work_id: &str,
+        page: Option<&str>,
+        user_id: Option<&str>,
+    ) -> Result<ListStudentSubmissionsResponse, Error> {
+        let submissions: Vec<StudentSubmission> = self
+            .submissions
+            .lock()
+            .unwrap()
+            .get(course_id)
+            .into_iter()
+            .flatten()
+            .filter(|sub| {
+                course_work_id == "-" || sub.course_work_id.as_deref() == Some(course_work_id)
+            })
+            .filter(|sub| user_id.is_none() || sub.user_id.as_deref() == user_id)
+            .cloned()
+            .collect();
+        let (student_submissions, next_page_token) = self.page(&submissions, page, None)?;
+        Ok(ListStudentSubmissionsResponse {
+            next_page_token,
+            student_submissions,
+        })
+    }
+
+    fn teaches(&self, course_id: &str) -> bool {
+        self.teaching.iter().any(|id| id == course_id)
+    }
+
+    /// Enroll `user_id` as a student in a course.
+    pub fn add_student(&mut self, course_id: &str, user_id: &str) {
+        self.students
+            .entry(course_id.to_string())
+            .or_default()
+            .push(user_id.to_string());
+    }
+
+    /// Add `user_id` as one of a course's teachers.
+    pub fn add_teacher(&mut self, course_id: &str, user_id: &str) {
+        self.teachers
+            .entry(course_id.to_string())
+            .or_default()
+            .push(user_id.to_string());
+    }
+
+    /// The profile of the current user or anyone else in `users`.
+    fn find_profile(&self, user_id: &str) -> Option<UserProfile> {
+        std::iter::once(&self.profile)
+            .chain(&self.users)
+            .find(|user| user.id.as_deref() == Some(user_id))
+            .cloned()
+    }
+
+    /// An id for newly created classwork, like Google's numeric ones.
+    fn next_id(&self) -> String {
+        (self.created.fetch_add(1, Ordering::Relaxed) + 1_000_000).to_string()
+    }
+
+    /// Change the coursework or material `id` with `f`, if the user teaches the course.
+    #[allow(clippy::significant_drop_tightening)]
+    fn update_classwork<T: Classwork>(
+        &self,
+        classwork: &Mutex<HashMap<String, Vec<T>>>,
+        course_id: &str,
+        id: &str,
+        f: impl FnOnce(&mut T) -> Result<(), Error>,
+    ) -> Result<T, Error> {
+        if !self.teaches(course_id) {
+            return Err(permission_denied());
+        }
+        let mut classwork = classwork.lock().unwrap();
+        let item = classwork
+            .get_mut(course_id)
+            .into_iter()
+            .flatten()
+            .find(|item| item.id() == Some(id))
+            .ok_or_else(not_found)?;
+        f(item)?;
+        Ok(item.clone())
+    }
+
+    /// Delete the coursework or material `id`, if the user teaches the course.
+    fn delete_classwork<T: Classwork>(
+        &self,
+        classwork: &Mutex<HashMap<String, Vec<T>>>,
+        course_id: &str,
+        id: &str,
+    ) -> Result<(), Error> {
+        if !self.teaches(course_id) {
+            return Err(permission_denied());
+        }
+        let mut classwork = classwork.lock().unwrap();
+        let items = classwork.get_mut(course_id).ok_or_else(not_found)?;
+        let index = items
+            .iter()
+            .position(|item| item.id() == Some(id))
+            .ok_or_else(not_found)?;
+        items.remove(index);
+        drop(classwork);
+        Ok(())
+    }
+
+    pub fn add_user(&mut self, id: &str, full_name: &str) {
+        self.users.push(UserProfile {
+            id: Some(id.to_string()),
+            name: Some(Name {
+                full_name: Some(full_name.to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+    }
+
+    /// Post an announcement, optionally with a `(title, url)` link attached.
+    /// Each announcement is a day newer than the last one posted in its course.
+    pub fn add_announcement(
+        &mut self,
+        course_id: &str,
+        id: &str,
+        text: &str,
+        creator: &str,
+        link: Option<(&str, &str)>,
+    ) {
+        let posts = self.announcements.entry(course_id.to_string()).or_default();
+        let day = u32::try_from(posts.len()).unwrap() + 1;
+        posts.insert(
+            0,
+            Announcement {
+                course_id: Some(course_id.to_string()),
+                id: Some(id.to_string()),
+                text: Some(text.to_string()),
+                creator_user_id: Some(creator.to_string()),
+                creation_time: Utc.with_ymd_and_hms(2023, 8, day, 12, 0, 0).single(),
+                materials: link.map(|link| vec![link_material(link)]),
+                ..Default::default()
+            },
+        );
+    }
+
+    /// Change one of the submissions with `f`.
+    #[allow(clippy::significant_drop_tightening)]
+    fn update_submission(
+        &self,
+        course_id: &str,
+        course_work_id: &str,
+        id: &str,
+        f: impl FnOnce(&mut StudentSubmission) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let mut submissions = self.submissions.lock().unwrap();
+        let sub = submissions
+            .get_mut(course_id)
+            .into_iter()
+            .flatten()
+            .find(|sub| {
+                sub.course_work_id.as_deref() == Some(course_work_id)
+                    && sub.id.as_deref() == Some(id)
+            })
+            .ok_or_else(not_found)?;
+        f(sub)
+    }
+
+    /// Fail like Classroom does when asked to change coursework, or a submission for it, that
+    /// wasn't created through css's Google Cloud project.
+    fn require_developer(&self, course_id: &str, course_work_id: &str) -> Result<(), Error> {
+        let associated = self
+            .course_work
+            .lock()
+            .unwrap()
+            .get(course_id)
+            .and_then(|work| {
+                work.iter()
+                    .find(|work| work.id.as_deref() == Some(course_work_id))
+            })
+            .ok_or_else(not_found)?
+            .associated_with_developer
+            .unwrap_or(false);
+        if associated {
+            Ok(())
+        } else {
+            Err(project_permission_denied())
+        }
+    }
+
+    /// Like [`Self::require_developer`], for course work materials.
+    fn require_developer_material(&self, course_id: &str, id: &str) -> Result<(), Error> {
+        let created = self
+            .css_materials
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|(course, material)| course == course_id && material == id);
+        if created {
+            Ok(())
+        } else {
+            Err(project_permission_denied())
+        }
+    }
+
+    /// Move a submission to `state`, if `allowed` says it can go there from its current state.
+    fn set_state(
+        &self,
+        course_id: &str,
+        course_work_id: &str,
+        id: &str,
+        state: &str,
+        allowed: impl Fn(&str) -> bool,
+    ) -> Result<(), Error> {
+        self.update_submission(course_id, course_work_id, id, |sub| {
+            if !allowed(sub.state.as_deref().unwrap_or("NEW")) {
+                return Err(failed_precondition());
+            }
+            sub.state = Some(state.to_string());
+            Ok(())
+        })
+    }
+
+    /// Slice out one page of `items`. Page tokens are just the index of the first item.
+    fn page<T: Clone>(
+        &self,
+        items: &[T],
+        page: Option<&str>,
+        page_size: Option<i32>,
+    ) -> Result<(Option<Vec<T>>, Option<String>), Error> {
+        self.pages_served.fetch_add(1, Ordering::Relaxed);
+        let start = page.map_or(Ok(0), str::parse).map_err(|_| bad_request())?;
+        let size = page_size
+            .and_then(|size| usize::try_from(size).ok())
+            .filter(|size| *size > 0)
+            .unwrap_or(self.page_size);
+        let end = (start + size).min(items.len());
+        let page = items.get(start..end).ok_or_else(bad_request)?.to_vec();
+        let next = (end < items.len()).then(|| end.to_string());
+        // Google leaves out empty lists entirely
+        Ok(((!page.is_empty()).then_some(page), next))
+    }
+}
+
+#[axum::async_trait]
+impl ClassroomBackend for MemoryBackend {
+    async fn list_courses(&self, page: Option<&str>) -> Result<ListCoursesResponse, Error> {
+        let (courses, next_page_token) = self.page(&self.courses, page, None)?;
+        Ok(ListCoursesResponse {
+            courses,
+            next_page_token,
+        })
+    }
+
+    async fn get_course(&self, id: &str) -> Result<Course, Error> {
+        self.courses
+            .iter()
+            .find(|course| course.id.as_deref() == Some(id))
+            .cloned()
+            .ok_or_else(not_found)
+    }
+
+    async fn list_course_work(
+        &self,
+        course_id: &str,
+        page: Option<&str>,
+        page_size: Option<i32>,
+    ) -> Result<ListCourseWorkResponse, Error> {
+        self.get_course(course_id).await?;
+        let work = published(&self.course_work, course_id);
+        let (course_work, next_page_token) = self.page(&work, page, page_size)?;
+        Ok(ListCourseWorkResponse {
+            course_work,
+            next_page_token,
+        })
+    }
+
+    async fn get_course_work(&self, course_id: &str, id: &str) -> Result<CourseWork, Error> {
+        self.course_work
+            .lock()
+            .unwrap()
+            .get(course_id)
+            .and_then(|work| work.iter().find(|work| work.id.as_deref() == Some(id)))
+            .cloned()
+            .ok_or_else(not_found)
+    }
+
+    async fn create_course_work(
+        &self,
+        course_id: &str,
+        course_work: CourseWork,
+    ) -> Result<CourseWork, Error> {
+        self.get_course(course_id).await?;
+        if !self.teaches(course_id) {
+            return Err(permission_denied());
+        }
+        let valid_type = matches!(
+            course_work.work_type.as_deref(),
+            Some("ASSIGNMENT" | "SHORT_ANSWER_QUESTION" | "MULTIPLE_CHOICE_QUESTION")
+        );
+        if course_work.title.is_none()
+            || !valid_type
+            || !valid_state(course_work.state.as_deref())
+            || (course_work.due_time.is_some() && course_work.due_date.is_none())
+        {
+            return Err(bad_request());
+        }
+        let creation_time = self.next_creation_time(course_id);
+        let course_work = CourseWork {
+            course_id: Some(course_id.to_string()),
+            id: Some(self.next_id()),
+            creation_time,
+            associated_with_developer: Some(true),
+            assignee_mode: course_work
+                .assignee_mode
+                .or_else(|| Some("ALL_STUDENTS".to_string())),
+            ..course_work
+        };
+        self.course_work
+            .lock()
+            .unwrap()
+            .entry(course_id.to_string())
+            .or_default()
+            .push(course_work.clone());
+        Ok(course_work)
+    }
+
+    async fn patch_course_work(
+        &self,
+        course_id: &str,
+        id: &str,
+        course_work: CourseWork,
+        update_mask: &str,
+    ) -> Result<CourseWork, Error> {
+        self.require_developer(course_id, id)?;
+        self.update_classwork(&self.course_work, course_id, id, |work| {
+            for field in update_mask.split(',') {
+                match field {
+                    "title" => work.title.clone_from(&course_work.title),
+                    "description" => work.description.clone_from(&course_work.description),
+                    "dueDate" => work.due_date.clone_from(&course_work.due_date),
+                    "dueTime" => work.due_time.clone_from(&course_work.due_time),
+                    "maxPoints" => work.max_points = course_work.max_points,
+                    "topicId" => work.topic_id.clone_from(&course_work.topic_id),
+                    "scheduledTime" => work.scheduled_time = course_work.scheduled_time,
+                    "state" => set_classwork_state(&mut work.state, course_work.state.as_deref())?,
+                    _ => return Err(bad_request()),
+                }
+            }
+            if work.due_time.is_some() && work.due_date.is_none() {
+                return Err(bad_request());
+            }
+            Ok(())
+        })
+    }
+
+    async fn modify_course_work_assignees(
+        &self,
+        course_id: &str,
+        id: &str,
+        request: ModifyCourseWorkAssigneesRequest,
+    ) -> Result<(), Error> {
+        self.require_developer(course_id, id)?;
+        self.update_classwork(&self.course_work, course_id, id, |work| {
+            match request.assignee_mode.as_deref() {
+                Some("ALL_STUDENTS") => work.individual_students_options = None,
+                Some("INDIVIDUAL_STUDENTS") => {
+                    let options = request
+                        .modify_individual_students_options
+                        .unwrap_or_default();
+                    let students = work
+                        .individual_students_options
+                        .get_or_insert_with(IndividualStudentsOptions::default)
+                        .student_ids
+                        .get_or_insert_with(Vec::new);
+                    let remove = options.remove_student_ids.unwrap_or_default();
+                    students.retain(|student| !remove.contains(student));
+                    for student in options.add_student_ids.unwrap_or_default() {
+                        if !students.contains(&student) {
+                            students.push(student);
+                        }
+                    }
+                    // Classroom won't assign coursework to nobody
+                    if students.is_empty() {
+                        return Err(bad_request());
+                    }
+                }
+                _ => return Err(bad_request()),
+            }
+            work.assignee_mode.clone_from(&request.assignee_mode);
+            Ok(())
+        })
+        .map(|_| ())
+    }
+
+    async fn delete_course_work(&self, course_id: &str, id: &str) -> Result<(), Error> {
+        self.require_developer(course_id, id)?;
+        self.delete_classwork(&self.course_work, course_id, id)?;
+        if let Some(submissions) = self.submissions.lock().unwrap().get_mut(course_id) {
+            submissions.retain(|sub| sub.course_work_id.as_deref() != Some(id));
+        }
+        Ok(())
+    }
+
+    async fn list_course_work_materials(
+        &self,
+        course_id: &str,
+        page: Option<&str>,
+    ) -> Result<ListCourseWorkMaterialResponse, Error> {
+        self.get_course(course_id).await?;
+        let materials = published(&self.materials, course_id);
+        let (course_work_material, next_page_token) = self.page(&materials, page, None)?;
+        Ok(ListCourseWorkMaterialResponse {
+            course_work_material,
+            next_page_token,
+        })
+    }
+
+    async fn get_course_work_material(
+        &self,
+        course_id: &str,
+        id: &str,
+    ) -> Result<CourseWorkMaterial, Error> {
+        self.materials
+            .lock()
+            .unwrap()
+            .get(course_id)
+            .and_then(|materials| {
+                materials
+                    .iter()
+                    .find(|material| material.id.as_deref() == Some(id))
+            })
+            .cloned()
+            .ok_or_else(not_found)
+    }
+
+    async fn create_course_work_material(
+        &self,
+        course_id: &str,
+        material: CourseWorkMaterial,
+    ) -> Result<CourseWorkMaterial, Error> {
+        self.get_course(course_id).await?;
+        if !self.teaches(course_id) {
+            return Err(permission_denied());
+        }
+        let no_students = material.assignee_mode.as_deref() == Some("INDIVIDUAL_STUDENTS")
+            && material
+                .individual_students_options
+                .as_ref()
+                .and_then(|options| options.student_ids.as_ref())
+                .is_none_or(Vec::is_empty);
+        if material.title.is_none() || !valid_state(material.state.as_deref()) || no_students {
+            return Err(bad_request());
+        }
+        let creation_time = self.next_creation_time(course_id);
+        let material = CourseWorkMaterial {
+            course_id: Some(course_id.to_string()),
+            id: Some(self.next_id()),
+            creation_time,
+            assignee_mode: material
+                .assignee_mode
+                .or_else(|| Some("ALL_STUDENTS".to_string())),
+            ..material
+        };
+        self.materials
+            .lock()
+            .unwrap()
+            .entry(course_id.to_string())
+            .or_default()
+            .push(material.clone());
+        self.css_materials.lock().unwrap().push((
+            course_id.to_string(),
+            material.id.clone().unwrap_or_default(),
+        ));
+        Ok(material)
+    }
+
+    async fn patch_course_work_material(
+        &self,
+        course_id: &str,
+        id: &str,
+        material: CourseWorkMaterial,
+        update_mask: &str,
+    ) -> Result<CourseWorkMaterial, Error> {
+        self.require_developer_material(course_id, id)?;
+        self.update_classwork(&self.materials, course_id, id, |existing| {
+            for field in update_mask.split(',') {
+                match field {
+                    "title" => existing.title.clone_from(&material.title),
+                    "description" => existing.description.clone_from(&material.description),
+                    "topicId" => existing.topic_id.clone_from(&material.topic_id),
+                    "scheduledTime" => existing.scheduled_time = material.scheduled_time,
+                    "state" => set_classwork_state(&mut existing.state, material.state.as_deref())?,
+                    _ => return Err(bad_request()),
+                }
+            }
+            Ok(())
+        })
+    }
+
+    async fn delete_course_work_material(&self, course_id: &str, id: &str) -> Result<(), Error> {
+        self.require_developer_material(course_id, id)?;
+        self.delete_classwork(&self.materials, course_id, id)
+    }
+
+    async fn list_topics(
+        &self,
+        course_id: &str,
+        page: Option<&str>,
+    ) -> Result<ListTopicResponse, Error> {
+        self.get_course(course_id).await?;
+        let topics = self.topics.get(course_id).map_or(&[][..], Vec::as_slice);
+        let (topic, next_page_token) = self.page(topics, page, None)?;
+        Ok(ListTopicResponse {
+            next_page_token,
+            topic,
+        })
+    }
+
+    async fn list_submissions(
+        &self,
+        course_id: &str,
+        course_work_id: &str,
+        page: Option<&str>,
+    ) -> Result<ListStudentSubmissionsResponse, Error> {
+        self.get_course(course_id).await?;
+        self.submissions(course_id, course_work_id, page, self.profile.id.as_deref())
+    }
+
+    async fn list_student_submissions(
+        &self,
+        course_id: &str,
+        course_work_id: &str,
+        page: Option<&str>,
+    ) -> Result<ListStudentSubmissionsResponse, Error> {
+        self.get_course(course_id).await?;
+        if !self.teaches(course_id) {
+            return Err(permission_denied());
+        }
+        self.submissions(course_id, course_work_id, page, None)
+    }
+
+    async fn turn_in(&self, course_id: &str, course_work_id: &str, id: &str) -> Result<(), Error> {
+        self.require_developer(course_id, course_work_id)?;
+        self.set_state(course_id, course_work_id, id, "TURNED_IN", |state| {
+            state != "TURNED_IN"
+        })
+    }
+
+    async fn reclaim(&self, course_id: &str, course_work_id: &str, id: &str) -> Result<(), Error> {
+        self.require_developer(course_id, course_work_id)?;
+        self.set_state(
+            course_id,
+            course_work_id,
+            id,
+            "RECLAIMED_BY_STUDENT",
+            |state| state == "TURNED_IN",
+        )
+    }
+
+    async fn add_attachments(
+        &self,
+        course_id: &str,
+        course_work_id: &str,
+        id: &str,
+        attachments: Vec<Attachment>,
+    ) -> Result<(), Error> {
+        self.require_developer(course_id, course_work_id)?;
+        self.update_submission(course_id, course_work_id, id, |sub| {
+            if sub.state.as_deref() == Some("TURNED_IN") {
+                return Err(failed_precondition());
+            }
+            sub.assignment_submission
+                .get_or_insert_with(AssignmentSubmission::default)
+                .attachments
+                .get_or_insert_with(Vec::new)
+                .extend(attachments.into_iter().map(|mut attachment| {
+                    // Google fills in the rest of a Drive file from its id
+                    if let Some(file) = &mut attachment.drive_file {
+                        file.title.clone_from(&file.id);
+                        file.alternate_link = file
+                            .id
+                            .as_ref()
+                            .map(|id| format!("https://drive.google.com/file/d/{id}/view"));
+                    }
+                    attachment
+                }));
+            Ok(())
+        })
+    }
+
+    async fn patch_submission(
+        &self,
+        course_id: &str,
+        course_work_id: &str,
+        id: &str,
+        submission: StudentSubmission,
+        update_mask: &str,
+    ) -> Result<(), Error> {
+        self.require_developer(course_id, course_work_id)?;
+        let teaching = self.teaches(course_id);
+        self.update_submission(course_id, course_work_id, id, |sub| {
+            for field in update_mask.split(',') {
+                match field {
+                    "shortAnswerSubmission.answer" | "multipleChoiceSubmission.answer"
+                        if sub.user_id != self.profile.id =>
+                    {
+                        return Err(permission_denied());
+                    }
+                    "draftGrade" | "assignedGrade" if !teaching => {
+                        return Err(permission_denied());
+                    }
+                    "shortAnswerSubmission.answer" | "multipleChoiceSubmission.answer"
+                        if sub.state.as_deref() == Some("TURNED_IN") =>
+                    {
+                        return Err(failed_precondition());
+                    }
+                    "shortAnswerSubmission.answer" => {
+                        sub.short_answer_submission
+                            .clone_from(&submission.short_answer_submission);
+                    }
+                    "multipleChoiceSubmission.answer" => {
+                        sub.multiple_choice_submission
+                            .clone_from(&submission.multiple_choice_submission);
+                    }
+                    "draftGrade" => sub.draft_grade = submission.draft_grade,
+                    "assignedGrade" => sub.assigned_grade = submission.assigned_grade,
+                    _ => return Err(bad_request()),
+                }
+            }
+            Ok(())
+        })
+    }
+
+    async fn return_submission(
+        &self,
+        course_id: &str,
+        course_work_id: &str,
+        id: &str,
+    ) -> Result<(), Error> {
+        if !self.teaches(course_id) {
+            return Err(permission_denied());
+        }
+        self.require_developer(course_id, course_work_id)?;
+        self.update_submission(course_id, course_work_id, id, |sub| {
+            if sub.draft_grade.is_some() {
+                sub.assigned_grade = sub.draft_grade;
+            }
+            sub.state = Some("RETURNED".to_string());
+            Ok(())
+        })
+    }
+
+    async fn is_teacher(&self, course_id: &str) -> Result<bool, Error> {
+        self.get_course(course_id).await?;
+        Ok(self.teaches(course_id))
+    }
+
+    async fn list_students(
+        &self,
+        course_id: &str,
+        page: Option<&str>,
+    ) -> Result<ListStudentsResponse, Error> {
+        self.get_course(course_id).await?;
+        let students: Vec<Student> = self
+            .students
+            .get(course_id)
+            .into_iter()
+            .flatten()
+            .map(|user_id| Student {
+                course_id: Some(course_id.to_string()),
+                user_id: Some(user_id.clone()),
+                profile: self.find_profile(user_id),
+                ..Default::default()
+            })
+            .collect();
+        let (students, next_page_token) = self.page(&students, page, None)?;
+        Ok(ListStudentsResponse {
+            next_page_token,
+            students,
+        })
+    }
+
+    async fn list_teachers(
+        &self,
+        course_id: &str,
+        page: Option<&str>,
+    ) -> Result<ListTeachersResponse, Error> {
+        self.get_course(course_id).await?;
+        let teachers: Vec<Teacher> = self
+            .teachers
+            .get(course_id)
+            .into_iter()
+            .flatten()
+            .map(|user_id| Teacher {
+                course_id: Some(course_id.to_string()),
+                user_id: Some(user_id.clone()),
+                profile: self.find_profile(user_id),
+            })
+            .collect();
+        let (teachers, next_page_token) = self.page(&teachers, page, None)?;
+        Ok(ListTeachersResponse {
+            next_page_token,
+            teachers,
+        })
+    }
+
+    async fn list_announcements(
+        &self,
+        course_id: &str,
+        page: Option<&str>,
+        page_size: Option<i32>,
+    ) -> Result<ListAnnouncementsResponse, Error> {
+        self.get_course(course_id).await?;
+        let posts = self
+            .announcements
+            .get(course_id)
+            .map_or(&[][..], Vec::as_slice);
+        let (announcements, next_page_token) = self.page(posts, page, page_size)?;
+        Ok(ListAnnouncementsResponse {
+            announcements,
+            next_page_token,
+        })
+    }
+
+    async fn get_user_profile(&self, user_id: &str) -> Result<UserProfile, Error> {
+        if user_id == "me" || self.profile.id.as_deref() == Some(user_id) {
+            return Ok(self.profile.clone());
+        }
+        self.users
+            .iter()
+            .find(|user| user.id.as_deref() == Some(user_id))
+            .cloned()
+            .ok_or_else(not_found)
+    }
+}
+
+/// What coursework and materials have in common.
+trait Classwork: Clone {
+    fn id(&self) -> Option<&str>;
+    fn state(&self) -> Option<&str>;
+}
+
+impl Classwork for CourseWork {
+    fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    fn state(&self) -> Option<&str> {
+        self.state.as_deref()
+    }
+}
+
+impl Classwork for CourseWorkMaterial {
+    fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    fn state(&self) -> Option<&str> {
+        self.state.as_deref()
+    }
+}
+
+/// A course's published coursework or materials. Like Google, lists leave out drafts
+/// and anything scheduled; fixtures without a state count as published.
+fn published<T: Classwork>(classwork: &Mutex<HashMap<String, Vec<T>>>, course_id: &str) -> Vec<T> {
+    classwork
+        .lock()
+        .unwrap()
+        .get(course_id)
+        .into_iter()
+        .flatten()
+        .filter(|item| matches!(item.state(), None | Some("PUBLISHED")))
+        .cloned()
+        .collect()
+}
+
+/// Whether new classwork can be created in `state`.
+fn valid_state(state: Option<&str>) -> bool {
+    matches!(state, Some("PUBLISHED" | "DRAFT"))
+}
+
+/// Move classwork to a new state. Published classwork can't go back to being a draft.
+fn set_classwork_state(state: &mut Option<String>, new: Option<&str>) -> Result<(), Error> {
+    if !valid_state(new) {
+        return Err(bad_request());
+    }
+    let published = matches!(state.as_deref(), None | Some("PUBLISHED"));
+    if published && new == Some("DRAFT") {
+        return Err(failed_precondition());
+    }
+    *state = new.map(str::to_string);
+    Ok(())
+}
+
+fn link_material((title, url): (&str, &str)) -> Material {
+    Material {
+        link: Some(Link {
+            title: Some(title.to_string()),
+            url: Some(url.to_string()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// The error Google responds with for a missing entity.
+pub fn not_found() -> Error {
+    google_error(404, "NOT_FOUND", "Requested entity was not found.")
+}
+
+fn permission_denied() -> Error {
+    google_error(
+        403,
+        "PERMISSION_DENIED",
+        "The caller does not have permission",
+    )
+}
+
+/// What Classroom says when css changes work that another project created.
+fn project_permission_denied() -> Error {
+    google_error(
+        403,
+        "PERMISSION_DENIED",
+        "@ProjectPermissionDenied The Developer Console project is not permitted to make this request.",
+    )
+}
+
+fn failed_precondition() -> Error {
+    google_error(400, "FAILED_PRECONDITION", "Precondition check failed.")
+}
+
+fn bad_request() -> Error {
+    google_error(
+        400,
+        "INVALID_ARGUMENT",
+        "Request contains an invalid argument.",
+    )
+}
+
+fn google_error(code: u16, status: &str, message: &str) -> Error {
+    classroom::Error::BadRequest(serde_json::json!({
+        "error": {
+            "code": code,
+            "message": message,
+            "status": status,
+        }
+    }))
+    .into()
+}
@@ -0,0 +1,193 @@
+use std::sync::Arc;
+
+use classroom::api::{
+    Attachment, Course, CourseWork, CourseWorkMaterial, ListAnnouncementsResponse,
+    ListCourseWorkMaterialResponse, ListCourseWorkResponse, ListCoursesResponse,
+    ListStudentSubmissionsResponse, ListStudentsResponse, ListTeachersResponse, ListTopicResponse,
+    ModifyCourseWorkAssigneesRequest, StudentSubmission, UserProfile,
+};
+
+use crate::Error;
+
+mod cache;
+mod google;
+#[cfg(test)]
+pub mod memory;
+
+pub use cache::{Cache, CachedBackend};
+pub use google::GoogleBackend;
+
+/// A shared handle to whichever [`ClassroomBackend`] is serving the current user.
+pub type Backend = Arc<dyn ClassroomBackend>;
+
+/// The Classroom API calls css makes, so handlers can run against something other than Google.
+///
+/// List methods take the page token to fetch (`None` for the first page) and return
+/// a single page; use [`crate::paginate::paginate`] to walk all of them.
+#[axum::async_trait]
+pub trait ClassroomBackend: Send + Sync {
+    async fn list_courses(&self, page: Option<&str>) -> Result<ListCoursesResponse, Error>;
+
+    async fn get_course(&self, id: &str) -> Result<Course, Error>;
+
+    async fn list_course_work(
+        &self,
+        course_id: &str,
+        page: Option<&str>,
+        page_size: Option<i32>,
+    ) -> Result<ListCourseWorkResponse, Error>;
+
+    async fn get_course_work(&self, course_id: &str, id: &str) -> Result<CourseWork, Error>;
+
+    /// Create coursework in a course the current user teaches. Only coursework created this
+    /// way can be turned in through the API, as [`Self::turn_in`] explains.
+    async fn create_course_work(
+        &self,
+        course_id: &str,
+        course_work: CourseWork,
+    ) -> Result<CourseWork, Error>;
+
+    /// Change coursework. `update_mask` lists the fields of `course_work` to save, out of
+    /// `title`, `description`, `dueDate`, `dueTime`, `maxPoints`, `topicId`, `state`
+    /// and `scheduledTime`. Classroom only lets css change, reassign or delete coursework
+    /// created by css's project.
+    async fn patch_course_work(
+        &self,
+        course_id: &str,
+        id: &str,
+        course_work: CourseWork,
+        update_mask: &str,
+    ) -> Result<CourseWork, Error>;
+
+    /// Change who coursework is assigned to.
+    async fn modify_course_work_assignees(
+        &self,
+        course_id: &str,
+        id: &str,
+        request: ModifyCourseWorkAssigneesRequest,
+    ) -> Result<(), Error>;
+
+    /// Delete coursework, along with every submission to it.
+    async fn delete_course_work(&self, course_id: &str, id: &str) -> Result<(), Error>;
+
+    async fn list_course_work_materials(
+        &self,
+        course_id: &str,
+        page: Option<&str>,
+    ) -> Result<ListCourseWorkMaterialResponse, Error>;
+
+    async fn get_course_work_material(
+        &self,
+        course_id: &str,
+        id: &str,
+    ) -> Result<CourseWorkMaterial, Error>;
+
+    async fn create_course_work_material(
+        &self,
+        course_id: &str,
+        material: CourseWorkMaterial,
+    ) -> Result<CourseWorkMaterial, Error>;
+
+    /// Change a material css created, like [`Self::patch_course_work`] without the due date
+    /// and points. Classroom can't change who a material is assigned to after it's created.
+    async fn patch_course_work_material(
+        &self,
+        course_id: &str,
+        id: &str,
+        material: CourseWorkMaterial,
+        update_mask: &str,
+    ) -> Result<CourseWorkMaterial, Error>;
+
+    /// Delete a material. Like patching, this only works on materials css created.
+    async fn delete_course_work_material(&self, course_id: &str, id: &str) -> Result<(), Error>;
+
+    async fn list_topics(
+        &self,
+        course_id: &str,
+        page: Option<&str>,
+    ) -> Result<ListTopicResponse, Error>;
+
+    /// List the current user's submissions. `course_work_id` may be `-` for every
+    /// piece of coursework in the course.
+    async fn list_submissions(
+        &self,
+        course_id: &str,
+        course_work_id: &str,
+        page: Option<&str>,
+    ) -> Result<ListStudentSubmissionsResponse, Error>;
+
+    /// List every student's submissions in a course the current user teaches.
+    async fn list_student_submissions(
+        &self,
+        course_id: &str,
+        course_work_id: &str,
+        page: Option<&str>,
+    ) -> Result<ListStudentSubmissionsResponse, Error>;
+
+    /// Turn in the current user's submission. Classroom only allows this for coursework
+    /// created by the same Google Cloud project as css's OAuth client.
+    async fn turn_in(&self, course_id: &str, course_work_id: &str, id: &str) -> Result<(), Error>;
+
+    /// Unsubmit a turned in submission.
+    async fn reclaim(&self, course_id: &str, course_work_id: &str, id: &str) -> Result<(), Error>;
+
+    /// Add attachments to the current user's submission. Like [`Self::turn_in`], this only
+    /// works on coursework created by css's project, and Classroom has no way to remove them.
+    async fn add_attachments(
+        &self,
+        course_id: &str,
+        course_work_id: &str,
+        id: &str,
+        attachments: Vec<Attachment>,
+    ) -> Result<(), Error>;
+
+    /// Change a submission. `update_mask` lists the fields of `submission` to save: students
+    /// can only save `shortAnswerSubmission.answer` or `multipleChoiceSubmission.answer`,
+    /// and teachers can only save `draftGrade` and `assignedGrade`. Like [`Self::turn_in`],
+    /// this only works on coursework created by css's project.
+    async fn patch_submission(
+        &self,
+        course_id: &str,
+        course_work_id: &str,
+        id: &str,
+        submission: StudentSubmission,
+        update_mask: &str,
+    ) -> Result<(), Error>;
+
+    /// Return a submission to its student, making its draft grade the assigned grade.
+    /// Only teachers can do this, with the same restriction as [`Self::turn_in`].
+    async fn return_submission(
+        &self,
+        course_id: &str,
+        course_work_id: &str,
+        id: &str,
+    ) -> Result<(), Error>;
+
+    /// Whether the current user is one of the course's teachers.
+    async fn is_teacher(&self, course_id: &str) -> Result<bool, Error>;
+
+    /// List a course's students, with their profiles.
+    async fn list_students(
+        &self,
+        course_id: &str,
+        page: Option<&str>,
+    ) -> Result<ListStudentsResponse, Error>;
+
+    /// List a course's teachers, with their profiles.
+    async fn list_teachers(
+        &self,
+        course_id: &str,
+        page: Option<&str>,
+    ) -> Result<ListTeachersResponse, Error>;
+
+    /// List a course's announcements, newest first.
+    async fn list_announcements(
+        &self,
+        course_id: &str,
+        page: Option<&str>,
+        page_size: Option<i32>,
+    ) -> Result<ListAnnouncementsResponse, Error>;
+
+    /// Get a user's profile. `user_id` may be `me` for the current user.
+    async fn get_user_profile(&self, user_id: &str) -> Result<UserProfile, Error>;
+}
@@ -0,0 +1,153 @@
+use classroom::api::StudentSubmission;
+
+/// The rules for which work counts as done and is left off the to-do list. The server's
+/// defaults come from the `[completion]` table in the config, and each user can override
+/// them on the settings page with [`CompletionOverrides`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct CompletionPolicy {
+    /// Keep late work on the list even once it's been turned in or returned
+    pub late: bool,
+    /// Which returned work stays on the list
+    pub returned: Returned,
+    /// Keep work that has no due date on the list
+    pub no_due_date: bool,
+}
+
+impl Default for CompletionPolicy {
+    fn default() -> Self {
+        Self {
+            late: true,
+            returned: Returned::Ungraded,
+            no_due_date: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Returned {
+    /// Returned work is always still to do
+    All,
+    /// Returned work is done once it has a grade
+    #[default]
+    Ungraded,
+    /// Returned work is always done
+    None,
+}
+
+impl CompletionPolicy {
+    /// Whether `submission` still needs doing, going by its state. Work without a due
+    /// date is left off separately, once its due date is known, by checking [`Self::no_due_date`].
+    #[must_use]
+    pub fn is_incomplete(self, submission: &StudentSubmission) -> bool {
+        if self.late && submission.late.unwrap_or(false) {
+            return true;
+        }
+        match submission.state.as_deref() {
+            Some("TURNED_IN") => false,
+            Some("RETURNED") => match self.returned {
+                Returned::All => true,
+                Returned::Ungraded => submission.assigned_grade.is_none(),
+                Returned::None => false,
+            },
+            _ => true,
+        }
+    }
+}
+
+/// One user's changes to the server's [`CompletionPolicy`]. Anything left as `None`
+/// follows the server, so changing the config still reaches users who haven't set it.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CompletionOverrides {
+    pub late: Option<bool>,
+    pub returned: Option<Returned>,
+    pub no_due_date: Option<bool>,
+}
+
+impl CompletionOverrides {
+    /// `policy` with these overrides applied.
+    #[must_use]
+    pub fn over(&self, policy: CompletionPolicy) -> CompletionPolicy {
+        CompletionPolicy {
+            late: self.late.unwrap_or(policy.late),
+            returned: self.returned.unwrap_or(policy.returned),
+            no_due_date: self.no_due_date.unwrap_or(policy.no_due_date),
+        }
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn submission(state: &str, late: bool, grade: Option<f64>) -> StudentSubmission {
+        StudentSubmission {
+            state: Some(state.to_string()),
+            late: Some(late),
+            assigned_grade: grade,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn default_policy() {
+        let policy = CompletionPolicy::default();
+        assert!(policy.is_incomplete(&submission("CREATED", false, None)));
+        assert!(!policy.is_incomplete(&submission("TURNED_IN", false, None)));
+        assert!(policy.is_incomplete(&submission("TURNED_IN", true, None)));
+        assert!(policy.is_incomplete(&submission("RETURNED", false, None)));
+        assert!(!policy.is_incomplete(&submission("RETURNED", false, Some(9.0))));
+        assert!(policy.is_incomplete(&StudentSubmission::default()));
+    }
+
+    #[test]
+    fn configured_policy() {
+        let policy = CompletionPolicy {
+            late: false,
+            returned: Returned::None,
+            no_due_date: false,
+        };
+        assert!(!policy.is_incomplete(&submission("TURNED_IN", true, None)));
+        assert!(policy.is_incomplete(&submission("CREATED", true, None)));
+        assert!(!policy.is_incomplete(&submission("RETURNED", false, None)));
+        let returned = CompletionPolicy {
+            returned: Returned::All,
+            ..CompletionPolicy::default()
+        };
+        assert!(returned.is_incomplete(&submission("RETURNED", false, Some(9.0))));
+    }
+
+    #[test]
+    fn overrides_only_what_they_set() {
+        let server = CompletionPolicy {
+            late: false,
+            ..CompletionPolicy::default()
+        };
+        let overrides = CompletionOverrides {
+            no_due_date: Some(false),
+            ..CompletionOverrides::default()
+        };
+        let policy = overrides.over(server);
+        assert!(!policy.late && !policy.no_due_date);
+        assert_eq!(policy.returned, Returned::Ungraded);
+        assert_eq!(CompletionOverrides::default().over(server), server);
+    }
+
+    #[test]
+    fn reads_config_table() {
+        let policy: CompletionPolicy = toml::from_str("returned = \"none\"").unwrap();
+        assert_eq!(
+            policy,
+            CompletionPolicy {
+                returned: Returned::None,
+                ..CompletionPolicy::default()
+            }
+        );
+    }
+}
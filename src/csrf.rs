@@ -0,0 +1,64 @@
+use axum::{extract::FromRequestParts, http::request::Parts};
+use oauth2::CsrfToken;
+use tower_cookies::{Cookie, Cookies};
+
+use crate::{AppState, Error};
+
+/// The CSRF token for this browser, stored in a private `csrf` cookie.
+/// Render [`Csrf::token`] into a hidden `csrf` field of every form that POSTs,
+/// then check the submitted value with [`Csrf::verify`].
+pub struct Csrf(String);
+
+impl Csrf {
+    pub fn token(&self) -> &str {
+        &self.0
+    }
+
+    /// Check a token submitted with a form against the one in the cookie.
+    pub fn verify(&self, submitted: &str) -> Result<(), Error> {
+        if !submitted.is_empty() && submitted == self.0 {
+            Ok(())
+        } else {
+            Err(Error::InvalidCsrf)
+        }
+    }
+}
+
+#[cfg(test)]
+impl Csrf {
+    /// A [`Csrf`] with a fixed token, as if it came from the browser's cookie.
+    pub fn for_tests() -> Self {
+        Self("test-token".to_string())
+    }
+}
+
+#[axum::async_trait]
+impl FromRequestParts<AppState> for Csrf {
+    type Rejection = Error;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let cookies = match Cookies::from_request_parts(parts, state).await {
+            Ok(v) => v,
+            Err(e) => return Err(Error::Extractor(e.1)),
+        };
+        let cookies = cookies.private(&state.key);
+        if let Some(token) = cookies.get("csrf") {
+            return Ok(Self(token.value().to_string()));
+        }
+        let token = CsrfToken::new_random().secret().clone();
+        let mut cookie = Cookie::new("csrf", token.clone());
+        cookie.set_path("/");
+        cookie.set_http_only(true);
+        cookies.add(cookie);
+        Ok(Self(token))
+    }
+}
+
+/// A form with nothing in it but the CSRF token.
+#[derive(serde::Deserialize)]
+pub struct CsrfForm {
+    pub csrf: String,
+}
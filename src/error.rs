@@ -5,16 +5,25 @@ use once_cell::sync::OnceCell;
 use axum::{
     http::StatusCode,
     response::{Html, IntoResponse, Redirect, Response},
+    Json,
 };
 
 pub static ERROR_TERA: OnceCell<Arc<tera::Tera>> = OnceCell::new();
 
+/// What a failed token revocation request returns.
+pub type RevocationError = oauth2::RequestTokenError<
+    oauth2::reqwest::Error<reqwest::Error>,
+    oauth2::basic::BasicRevocationErrorResponse,
+>;
+
+/// The Classroom and token revocation errors are boxed. They're much bigger than every
+/// other variant, and would otherwise make every `Result<_, Error>` as big as them.
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Tera error: {0:?}")]
     Tera(#[from] tera::Error),
     #[error("Classroom API error: {0:?}")]
-    GoogleClassroom(#[from] google_classroom1::Error),
+    GoogleClassroom(Box<google_classroom1::Error>),
     #[error("SerdeJson error: {0:?}")]
     SerdeJson(#[from] serde_json::Error),
     #[error("Tokio task join error: {0:?}")]
@@ -23,43 +32,123 @@ pub enum Error {
     IntegerConversion(#[from] std::num::TryFromIntError),
     #[error("OAuth error")]
     OAuth(#[from] oauth2::basic::BasicRequestTokenError<oauth2::reqwest::Error<reqwest::Error>>),
+    #[error("OAuth configuration error: {0}")]
+    OAuthConfiguration(#[from] oauth2::ConfigurationError),
+    #[error("OAuth token revocation error")]
+    Revocation(Box<RevocationError>),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
     #[error("Extractor error: {0}")]
     Extractor(&'static str),
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
     #[error("Missing expected field: {0}")]
     MissingField(&'static str),
     #[error("Error converting URL-encoded string")]
     FromUtf8(#[from] std::string::FromUtf8Error),
     #[error("Tower-Cookies time error: {0}")]
     DurationOutOfRange(#[from] tower_cookies::cookie::time::error::ConversionRange),
+    #[error("Invalid or missing CSRF token")]
+    InvalidCsrf,
     #[error("Invalid OAuth State")]
     InvalidState,
     #[error("OAuth Code Exchange Failed")]
     CodeExchangeFailed,
     #[error("No token found - reauthenticating")]
     NoToken,
+    #[error("No refresh token found - please sign out and sign back in")]
+    NoRefreshToken,
+    #[error("Unknown or revoked feed token")]
+    UnknownFeed,
+    #[error("Can't attach that: {0}")]
+    InvalidAttachment(&'static str),
+    #[error("Can't save that answer: {0}")]
+    InvalidAnswer(&'static str),
+    #[error("Can't save that grade: {0}")]
+    InvalidGrade(&'static str),
+    #[error("Can't save that classwork: {0}")]
+    InvalidClasswork(&'static str),
+    #[error("Unknown time zone")]
+    UnknownTimezone,
+    #[error("Invalid setting: {0}")]
+    InvalidSetting(&'static str),
+    #[error("Can't mark that: {0}")]
+    InvalidMark(&'static str),
+    #[error("Teacher mode is off - turn it on in settings")]
+    TeacherModeOff,
+    #[error("You don't teach this class")]
+    NotTeacher,
     #[error("Invalid datetime detected")]
     InvalidDateTime,
     #[error("Once cell uninitialized, please make an issue")]
     UninitializedOnceCell,
 }
 
+impl From<google_classroom1::Error> for Error {
+    fn from(value: google_classroom1::Error) -> Self {
+        Self::GoogleClassroom(Box::new(value))
+    }
+}
+
+impl From<RevocationError> for Error {
+    fn from(value: RevocationError) -> Self {
+        Self::Revocation(Box::new(value))
+    }
+}
+
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
         if matches!(self, Self::NoToken) {
             return Redirect::to("/oauth").into_response();
         }
         let Some(tera) = ERROR_TERA.get() else {
-            return Self::UninitializedOnceCell.to_ugly_response()
+            return Self::UninitializedOnceCell.to_ugly_response();
         };
         let mut context = tera::Context::new();
         context.insert("error", &format!("{self:#?}"));
         match tera.render("error.jinja", &context) {
-            Ok(v) => Html(v).into_response(),
+            Ok(v) => (self.status_code(), Html(v)).into_response(),
             Err(e) => Self::Tera(e).to_ugly_response(),
         }
     }
 }
 impl Error {
+    #[must_use]
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NoToken => StatusCode::UNAUTHORIZED,
+            Self::InvalidCsrf | Self::TeacherModeOff | Self::NotTeacher => StatusCode::FORBIDDEN,
+            Self::UnknownFeed => StatusCode::NOT_FOUND,
+            Self::Extractor(_)
+            | Self::InvalidRequest(_)
+            | Self::InvalidState
+            | Self::CodeExchangeFailed
+            | Self::InvalidAttachment(_)
+            | Self::InvalidAnswer(_)
+            | Self::InvalidGrade(_)
+            | Self::InvalidClasswork(_)
+            | Self::UnknownTimezone
+            | Self::InvalidSetting(_)
+            | Self::InvalidMark(_) => StatusCode::BAD_REQUEST,
+            Self::GoogleClassroom(e) => google_status_code(e),
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Render this error as a JSON body, for API routes.
+    #[must_use]
+    pub fn to_json_response(&self) -> Response {
+        let status = self.status_code();
+        let body = serde_json::json!({
+            "error": {
+                "status": status.as_u16(),
+                "message": self.to_string(),
+            }
+        });
+        (status, Json(body)).into_response()
+    }
+
+    #[must_use]
     pub fn to_ugly_response(&self) -> Response {
         (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -73,3 +162,16 @@ an Error to nicely display the error:
             .into_response()
     }
 }
+
+/// Pass through the status Google responded with, or blame Google if there wasn't one.
+fn google_status_code(error: &google_classroom1::Error) -> StatusCode {
+    match error {
+        google_classroom1::Error::Failure(resp) => resp.status(),
+        google_classroom1::Error::BadRequest(body) => body["error"]["code"]
+            .as_u64()
+            .and_then(|code| u16::try_from(code).ok())
+            .and_then(|code| StatusCode::from_u16(code).ok())
+            .unwrap_or(StatusCode::BAD_GATEWAY),
+        _ => StatusCode::BAD_GATEWAY,
+    }
+}
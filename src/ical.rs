@@ -0,0 +1,134 @@
+use classroom::chrono::{DateTime, Utc};
+
+/// Longest a content line may be, in octets, before it has to be folded (RFC 5545 3.1).
+const MAX_LINE_OCTETS: usize = 75;
+
+/// A minimal RFC 5545 iCalendar writer.
+pub struct Calendar {
+    out: String,
+}
+
+impl Calendar {
+    pub fn new(name: &str) -> Self {
+        let mut calendar = Self { out: String::new() };
+        calendar.begin("VCALENDAR");
+        calendar.property("VERSION", "2.0");
+        calendar.property("PRODID", "-//css//Classroom, Server Side//EN");
+        calendar.property("CALSCALE", "GREGORIAN");
+        calendar.text("X-WR-CALNAME", name);
+        calendar
+    }
+
+    pub fn begin(&mut self, component: &str) {
+        self.property("BEGIN", component);
+    }
+
+    pub fn end(&mut self, component: &str) {
+        self.property("END", component);
+    }
+
+    /// Write a property with a value that is already in iCalendar format.
+    pub fn property(&mut self, name: &str, value: &str) {
+        let line = format!("{name}:{value}");
+        let mut octets = 0;
+        for c in line.chars() {
+            if octets + c.len_utf8() > MAX_LINE_OCTETS {
+                self.out.push_str("\r\n ");
+                // the leading space counts towards the continuation line's length
+                octets = 1;
+            }
+            self.out.push(c);
+            octets += c.len_utf8();
+        }
+        self.out.push_str("\r\n");
+    }
+
+    /// Write a property with a TEXT value, escaping it as needed.
+    pub fn text(&mut self, name: &str, value: &str) {
+        let mut escaped = String::with_capacity(value.len());
+        for c in value.chars() {
+            match c {
+                '\\' => escaped.push_str("\\\\"),
+                ';' => escaped.push_str("\\;"),
+                ',' => escaped.push_str("\\,"),
+                '\n' => escaped.push_str("\\n"),
+                '\r' => {}
+                c => escaped.push(c),
+            }
+        }
+        self.property(name, &escaped);
+    }
+
+    /// Write a property with a UTC DATE-TIME value.
+    pub fn date_time(&mut self, name: &str, value: &DateTime<Utc>) {
+        self.property(name, &value.format("%Y%m%dT%H%M%SZ").to_string());
+    }
+
+    pub fn finish(mut self) -> String {
+        self.end("VCALENDAR");
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The content lines of `calendar`, without the VCALENDAR header and footer.
+    fn body(calendar: Calendar) -> String {
+        let out = calendar.finish();
+        let start = out.find("X-WR-CALNAME:test\r\n").unwrap() + "X-WR-CALNAME:test\r\n".len();
+        out[start..out.len() - "END:VCALENDAR\r\n".len()].to_string()
+    }
+
+    #[test]
+    fn folds_long_lines_at_75_octets() {
+        let mut calendar = Calendar::new("test");
+        calendar.property("SUMMARY", &"a".repeat(100));
+        let body = body(calendar);
+        let lines: Vec<&str> = body.split("\r\n").filter(|line| !line.is_empty()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].len(), 75);
+        assert!(lines[1].starts_with(' '));
+        // the continuation line carries on exactly where the first one stopped
+        let unfolded = body.replace("\r\n ", "");
+        assert_eq!(unfolded, format!("SUMMARY:{}\r\n", "a".repeat(100)));
+    }
+
+    #[test]
+    fn short_lines_are_not_folded() {
+        let mut calendar = Calendar::new("test");
+        calendar.property("SUMMARY", &"a".repeat(75 - "SUMMARY:".len()));
+        assert!(!body(calendar).contains("\r\n "));
+    }
+
+    #[test]
+    fn never_splits_a_multibyte_character() {
+        let mut calendar = Calendar::new("test");
+        // "SUMMARY:" is 8 octets and each é is 2, so the 75 octet limit lands mid-character
+        let value = "é".repeat(40);
+        calendar.text("SUMMARY", &value);
+        let body = body(calendar);
+        for line in body.split("\r\n") {
+            assert!(line.len() <= 75, "{line:?} is {} octets", line.len());
+        }
+        let first = body.split("\r\n").next().unwrap();
+        assert_eq!(first.len(), 74);
+        assert_eq!(body.replace("\r\n ", ""), format!("SUMMARY:{value}\r\n"));
+    }
+
+    #[test]
+    fn escapes_text() {
+        let mut calendar = Calendar::new("test");
+        calendar.text("DESCRIPTION", "a;b,c\\d\r\ne\nf");
+        assert_eq!(body(calendar), "DESCRIPTION:a\\;b\\,c\\\\d\\ne\\nf\r\n");
+    }
+
+    #[test]
+    fn escapes_the_calendar_name() {
+        let calendar = Calendar::new("Math, period 2; room 4");
+        assert!(calendar
+            .finish()
+            .contains("X-WR-CALNAME:Math\\, period 2\\; room 4\r\n"));
+    }
+}
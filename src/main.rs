@@ -1,13 +1,23 @@
 #![warn(clippy::all, clippy::nursery, clippy::pedantic)]
+mod api;
 mod auth;
+mod backend;
+mod completion;
+mod csrf;
 mod error;
+mod ical;
+#[cfg(test)]
+mod mock;
 mod oauth;
+mod paginate;
 mod routes;
 mod state;
+mod store;
+mod timezone;
 
 extern crate google_classroom1 as classroom;
 
-use axum::routing::get;
+use axum::routing::{get, post};
 pub use error::Error;
 pub use state::AppState;
 use tower_http::services::ServeDir;
@@ -17,7 +27,16 @@ async fn main() {
     let config_string =
         std::fs::read_to_string("./config.toml").expect("Failed to read config file");
     let config: Config = toml::from_str(&config_string).expect("Invalid TOML");
-    let app = axum::Router::new()
+    let app = app(AppState::new(config));
+    println!("Listening on http://localhost:8080");
+    axum::Server::bind(&([0, 0, 0, 0], 8080).into())
+        .serve(app.into_make_service())
+        .await
+        .unwrap();
+}
+
+pub fn app(state: AppState) -> axum::Router {
+    axum::Router::new()
         .route("/", get(routes::about))
         .route("/privacy", get(routes::privacy))
         .route("/privacy/", get(routes::privacy))
@@ -26,20 +45,82 @@ async fn main() {
         .route("/classes", get(routes::classes))
         .route("/classes/", get(routes::classes))
         .route("/class/:classid", get(routes::class))
+        .route("/class/:classid/announcements", get(routes::announcements))
+        .route(
+            "/class/:classid/coursework/new",
+            get(routes::new_coursework).post(routes::create_coursework),
+        )
+        .route(
+            "/class/:classid/material/new",
+            get(routes::new_material).post(routes::create_material),
+        )
+        .route("/class/:classid/material/:id", get(routes::material))
+        .route(
+            "/class/:classid/material/:id/edit",
+            get(routes::edit_material).post(routes::update_material),
+        )
+        .route(
+            "/class/:classid/material/:id/delete",
+            get(routes::delete_material_page).post(routes::delete_material),
+        )
+        .route("/class/:classid/grades", get(routes::grades_for_class))
+        .route("/class/:classid/people", get(routes::people))
+        .route("/grades", get(routes::grades))
+        .route("/grades/", get(routes::grades))
         .route("/todo", get(routes::todos_all))
         .route("/todo/", get(routes::todos_all))
+        .route("/todo.ics", get(routes::todos_all_ics))
+        .route("/todo/options", post(routes::save_todo_options))
+        .route("/todo/mark", post(routes::mark_todo))
         .route("/todo/:class", get(routes::todos_for_class))
         .route("/assignment/:classid/:id", get(routes::assignment))
+        .route(
+            "/assignment/:classid/:id/turn-in",
+            get(routes::turn_in_page).post(routes::turn_in),
+        )
+        .route("/assignment/:classid/:id/answer", post(routes::answer))
+        .route(
+            "/assignment/:classid/:id/edit",
+            get(routes::edit_coursework).post(routes::update_coursework),
+        )
+        .route(
+            "/assignment/:classid/:id/delete",
+            get(routes::delete_coursework_page).post(routes::delete_coursework),
+        )
+        .route(
+            "/assignment/:classid/:id/submissions",
+            get(routes::student_work),
+        )
+        .route(
+            "/assignment/:classid/:id/submissions/:submission/grade",
+            post(routes::grade),
+        )
+        .route(
+            "/assignment/:classid/:id/submissions/:submission/return",
+            post(routes::return_work),
+        )
+        .route(
+            "/assignment/:classid/:id/attachments",
+            post(routes::add_attachment),
+        )
+        .route(
+            "/assignment/:classid/:id/unsubmit",
+            get(routes::unsubmit_page).post(routes::unsubmit),
+        )
+        .route("/settings", get(routes::settings))
+        .route("/settings/feeds", post(routes::create_feed))
+        .route("/settings/feeds/revoke", post(routes::revoke_feed))
+        .route("/settings/teacher/off", post(routes::teacher_mode_off))
+        .route("/settings/timezone", post(routes::set_timezone))
+        .route("/settings/completion", post(routes::set_completion))
+        .route("/feed/:token", get(routes::feed))
         .route("/oauth", get(oauth::redirect))
         .route("/oauth/callback", get(oauth::set_tokens))
+        .route("/logout", get(oauth::logout_page).post(oauth::logout))
+        .nest("/api/v1", api::router())
         .layer(tower_cookies::CookieManagerLayer::new())
         .nest_service("/assets", ServeDir::new("assets"))
-        .with_state(AppState::new(config));
-    println!("Listening on http://localhost:8080");
-    axum::Server::bind(&([0, 0, 0, 0], 8080).into())
-        .serve(app.into_make_service())
-        .await
-        .unwrap();
+        .with_state(state)
 }
 
 #[derive(serde::Deserialize)]
@@ -48,4 +129,20 @@ pub struct Config {
     root_url: String,
     client_id: String,
     client_secret: String,
+    max_pages: Option<usize>,
+    data_dir: Option<String>,
+    /// Overrides for Google's endpoints, for testing against a stand-in
+    classroom_url: Option<String>,
+    auth_url: Option<String>,
+    token_url: Option<String>,
+    revocation_url: Option<String>,
+    /// Seconds Classroom lists are cached for, and how long after that stale copies are
+    /// served while they're refetched
+    cache_ttl: Option<u64>,
+    cache_stale: Option<u64>,
+    /// The most Classroom responses cached at once
+    cache_entries: Option<usize>,
+    /// What counts as done on the to-do list, unless a user changes it in their settings
+    #[serde(default)]
+    completion: completion::CompletionPolicy,
 }
@@ -0,0 +1,1227 @@
+//! A local stand-in for Google's OAuth and Classroom REST v1 endpoints, and a harness that
+//! boots it alongside the real css router for end-to-end tests.
+
+use std::{
+    collections::{HashMap, HashSet},
+    net::{SocketAddr, TcpListener},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Form, Json, Router,
+};
+use classroom::api::{
+    CourseWork, CourseWorkMaterial, ModifyAttachmentsRequest, ModifyCourseWorkAssigneesRequest,
+    StudentSubmission,
+};
+use serde::Serialize;
+
+use crate::{
+    backend::{memory::MemoryBackend, ClassroomBackend},
+    AppState, Config, Error,
+};
+
+/// The refresh token the mock hands out, and accepts for refreshing.
+pub const REFRESH_TOKEN: &str = "mock-refresh";
+
+struct MockState {
+    backend: MemoryBackend,
+    access_tokens: Mutex<HashSet<String>>,
+    issued: AtomicUsize,
+    refresh_revoked: Mutex<bool>,
+    /// Every scope the user has agreed to so far, like Google with `include_granted_scopes`
+    granted_scopes: Mutex<HashSet<String>>,
+}
+
+impl MockState {
+    fn issue_access_token(&self) -> String {
+        let issued = self.issued.fetch_add(1, Ordering::Relaxed) + 1;
+        let token = format!("mock-access-{issued}");
+        self.access_tokens.lock().unwrap().insert(token.clone());
+        token
+    }
+
+    fn authorized(&self, headers: &HeaderMap) -> bool {
+        let token = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        token.is_some_and(|token| self.access_tokens.lock().unwrap().contains(token))
+    }
+}
+
+/// A running mock of Google's endpoints, backed by a [`MemoryBackend`].
+pub struct MockGoogle {
+    pub url: String,
+    state: Arc<MockState>,
+}
+
+impl MockGoogle {
+    pub fn start(backend: MemoryBackend) -> Self {
+        let state = Arc::new(MockState {
+            backend,
+            access_tokens: Mutex::new(HashSet::new()),
+            issued: AtomicUsize::new(0),
+            refresh_revoked: Mutex::new(false),
+            granted_scopes: Mutex::new(HashSet::new()),
+        });
+        let router = Router::new()
+            .route("/o/oauth2/v2/auth", get(authorize))
+            .route("/token", post(token))
+            .route("/revoke", post(revoke))
+            .route("/v1/courses", get(list_courses))
+            .route("/v1/courses/:course_id", get(get_course))
+            .route(
+                "/v1/courses/:course_id/courseWork",
+                get(list_course_work).post(create_course_work),
+            )
+            .route(
+                "/v1/courses/:course_id/courseWork/:work_id",
+                get(get_course_work)
+                    .post(course_work_action)
+                    .patch(patch_course_work)
+                    .delete(delete_course_work),
+            )
+            .route(
+                "/v1/courses/:course_id/courseWork/:work_id/studentSubmissions",
+                get(list_submissions),
+            )
+            .route(
+                "/v1/courses/:course_id/courseWork/:work_id/studentSubmissions/:action",
+                post(submission_action).patch(patch_submission),
+            )
+            .route(
+                "/v1/courses/:course_id/courseWorkMaterials",
+                get(list_course_work_materials).post(create_course_work_material),
+            )
+            .route(
+                "/v1/courses/:course_id/courseWorkMaterials/:id",
+                get(get_course_work_material)
+                    .patch(patch_course_work_material)
+                    .delete(delete_course_work_material),
+            )
+            .route("/v1/courses/:course_id/topics", get(list_topics))
+            .route("/v1/courses/:course_id/students", get(list_students))
+            .route(
+                "/v1/courses/:course_id/announcements",
+                get(list_announcements),
+            )
+            .route("/v1/courses/:course_id/teachers", get(list_teachers))
+            .route("/v1/courses/:course_id/teachers/:user_id", get(get_teacher))
+            .route("/v1/userProfiles/:user_id", get(get_user_profile))
+            .with_state(state.clone());
+        let addr = serve(router);
+        Self {
+            url: format!("http://{addr}"),
+            state,
+        }
+    }
+
+    /// Forget every access token handed out so far, as if they had all expired.
+    pub fn expire_access_tokens(&self) {
+        self.state.access_tokens.lock().unwrap().clear();
+    }
+
+    /// Refuse [`REFRESH_TOKEN`] from now on, as if the user had revoked css's access.
+    pub fn revoke_refresh_token(&self) {
+        *self.state.refresh_revoked.lock().unwrap() = true;
+    }
+
+    /// Whether [`REFRESH_TOKEN`] has been revoked, by css or by [`Self::revoke_refresh_token`].
+    pub fn refresh_token_revoked(&self) -> bool {
+        *self.state.refresh_revoked.lock().unwrap()
+    }
+}
+
+/// Serve `router` on a random local port in the background.
+fn serve(router: Router) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = axum::Server::from_tcp(listener)
+        .unwrap()
+        .serve(router.into_make_service());
+    tokio::spawn(server);
+    addr
+}
+
+fn google_error(code: u16, status: &str) -> Response {
+    let body = serde_json::json!({
+        "error": { "code": code, "message": status, "status": status }
+    });
+    (StatusCode::from_u16(code).unwrap(), Json(body)).into_response()
+}
+
+fn respond<T: Serialize>(result: Result<T, Error>) -> Response {
+    match result {
+        Ok(v) => Json(v).into_response(),
+        Err(Error::GoogleClassroom(e)) => match *e {
+            classroom::Error::BadRequest(body) => {
+                let status = body["error"]["code"].as_u64().unwrap_or(500);
+                let status = StatusCode::from_u16(u16::try_from(status).unwrap()).unwrap();
+                (status, Json(body)).into_response()
+            }
+            e => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        },
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct AuthorizeQuery {
+    redirect_uri: String,
+    state: String,
+    scope: String,
+}
+
+async fn authorize(
+    State(state): State<Arc<MockState>>,
+    Query(query): Query<AuthorizeQuery>,
+) -> Redirect {
+    state
+        .granted_scopes
+        .lock()
+        .unwrap()
+        .extend(query.scope.split(' ').map(str::to_string));
+    Redirect::to(&format!(
+        "{}?code=mock-code&state={}",
+        query.redirect_uri, query.state
+    ))
+}
+
+#[derive(serde::Deserialize)]
+struct RevokeForm {
+    token: String,
+}
+
+/// Like Google, revoking the refresh token also revokes every access token issued with it.
+async fn revoke(State(state): State<Arc<MockState>>, Form(form): Form<RevokeForm>) -> Response {
+    if form.token != REFRESH_TOKEN {
+        let body = serde_json::json!({ "error": "invalid_token" });
+        return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+    }
+    *state.refresh_revoked.lock().unwrap() = true;
+    state.access_tokens.lock().unwrap().clear();
+    StatusCode::OK.into_response()
+}
+
+#[derive(serde::Deserialize)]
+struct TokenForm {
+    grant_type: String,
+    code: Option<String>,
+    refresh_token: Option<String>,
+}
+
+async fn token(State(state): State<Arc<MockState>>, Form(form): Form<TokenForm>) -> Response {
+    let valid = match form.grant_type.as_str() {
+        "authorization_code" => form.code.as_deref() == Some("mock-code"),
+        "refresh_token" => {
+            form.refresh_token.as_deref() == Some(REFRESH_TOKEN)
+                && !*state.refresh_revoked.lock().unwrap()
+        }
+        _ => false,
+    };
+    if !valid {
+        let body = serde_json::json!({ "error": "invalid_grant" });
+        return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+    }
+    let mut body = serde_json::json!({
+        "access_token": state.issue_access_token(),
+        "token_type": "Bearer",
+        "expires_in": 3600,
+        "scope": state.granted_scopes.lock().unwrap().iter().cloned().collect::<Vec<_>>().join(" "),
+    });
+    if form.grant_type == "authorization_code" {
+        body["refresh_token"] = REFRESH_TOKEN.into();
+    }
+    Json(body).into_response()
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListQuery {
+    page_token: Option<String>,
+    page_size: Option<i32>,
+    user_id: Option<String>,
+}
+
+async fn list_courses(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Query(query): Query<ListQuery>,
+) -> Response {
+    if !state.authorized(&headers) {
+        return google_error(401, "UNAUTHENTICATED");
+    }
+    respond(
+        state
+            .backend
+            .list_courses(query.page_token.as_deref())
+            .await,
+    )
+}
+
+async fn get_course(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path(course_id): Path<String>,
+) -> Response {
+    if !state.authorized(&headers) {
+        return google_error(401, "UNAUTHENTICATED");
+    }
+    respond(state.backend.get_course(&course_id).await)
+}
+
+async fn list_course_work(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path(course_id): Path<String>,
+    Query(query): Query<ListQuery>,
+) -> Response {
+    if !state.authorized(&headers) {
+        return google_error(401, "UNAUTHENTICATED");
+    }
+    let page = query.page_token.as_deref();
+    respond(
+        state
+            .backend
+            .list_course_work(&course_id, page, query.page_size)
+            .await,
+    )
+}
+
+async fn get_course_work(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path((course_id, work_id)): Path<(String, String)>,
+) -> Response {
+    if !state.authorized(&headers) {
+        return google_error(401, "UNAUTHENTICATED");
+    }
+    respond(state.backend.get_course_work(&course_id, &work_id).await)
+}
+
+async fn create_course_work(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path(course_id): Path<String>,
+    Json(course_work): Json<CourseWork>,
+) -> Response {
+    if !state.authorized(&headers) {
+        return google_error(401, "UNAUTHENTICATED");
+    }
+    respond(
+        state
+            .backend
+            .create_course_work(&course_id, course_work)
+            .await,
+    )
+}
+
+async fn patch_course_work(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path((course_id, work_id)): Path<(String, String)>,
+    Query(query): Query<PatchQuery>,
+    Json(course_work): Json<CourseWork>,
+) -> Response {
+    if !state.authorized(&headers) {
+        return google_error(401, "UNAUTHENTICATED");
+    }
+    respond(
+        state
+            .backend
+            .patch_course_work(&course_id, &work_id, course_work, &query.update_mask)
+            .await,
+    )
+}
+
+/// `{id}:modifyAssignees`, the only custom method on coursework.
+async fn course_work_action(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path((course_id, action)): Path<(String, String)>,
+    Json(request): Json<ModifyCourseWorkAssigneesRequest>,
+) -> Response {
+    if !state.authorized(&headers) {
+        return google_error(401, "UNAUTHENTICATED");
+    }
+    let Some((id, "modifyAssignees")) = action.split_once(':') else {
+        return google_error(404, "NOT_FOUND");
+    };
+    respond(
+        state
+            .backend
+            .modify_course_work_assignees(&course_id, id, request)
+            .await
+            .map(|()| serde_json::json!({})),
+    )
+}
+
+async fn delete_course_work(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path((course_id, work_id)): Path<(String, String)>,
+) -> Response {
+    if !state.authorized(&headers) {
+        return google_error(401, "UNAUTHENTICATED");
+    }
+    respond(
+        state
+            .backend
+            .delete_course_work(&course_id, &work_id)
+            .await
+            .map(|()| serde_json::json!({})),
+    )
+}
+
+async fn list_submissions(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path((course_id, work_id)): Path<(String, String)>,
+    Query(query): Query<ListQuery>,
+) -> Response {
+    if !state.authorized(&headers) {
+        return google_error(401, "UNAUTHENTICATED");
+    }
+    let page = query.page_token.as_deref();
+    let backend = &state.backend;
+    respond(match query.user_id.as_deref() {
+        Some("me") => backend.list_submissions(&course_id, &work_id, page).await,
+        Some(_) => return google_error(400, "INVALID_ARGUMENT"),
+        None => {
+            backend
+                .list_student_submissions(&course_id, &work_id, page)
+                .await
+        }
+    })
+}
+
+async fn list_course_work_materials(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path(course_id): Path<String>,
+    Query(query): Query<ListQuery>,
+) -> Response {
+    if !state.authorized(&headers) {
+        return google_error(401, "UNAUTHENTICATED");
+    }
+    let page = query.page_token.as_deref();
+    respond(
+        state
+            .backend
+            .list_course_work_materials(&course_id, page)
+            .await,
+    )
+}
+
+async fn get_course_work_material(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path((course_id, id)): Path<(String, String)>,
+) -> Response {
+    if !state.authorized(&headers) {
+        return google_error(401, "UNAUTHENTICATED");
+    }
+    respond(
+        state
+            .backend
+            .get_course_work_material(&course_id, &id)
+            .await,
+    )
+}
+
+async fn create_course_work_material(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path(course_id): Path<String>,
+    Json(material): Json<CourseWorkMaterial>,
+) -> Response {
+    if !state.authorized(&headers) {
+        return google_error(401, "UNAUTHENTICATED");
+    }
+    respond(
+        state
+            .backend
+            .create_course_work_material(&course_id, material)
+            .await,
+    )
+}
+
+async fn patch_course_work_material(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path((course_id, id)): Path<(String, String)>,
+    Query(query): Query<PatchQuery>,
+    Json(material): Json<CourseWorkMaterial>,
+) -> Response {
+    if !state.authorized(&headers) {
+        return google_error(401, "UNAUTHENTICATED");
+    }
+    respond(
+        state
+            .backend
+            .patch_course_work_material(&course_id, &id, material, &query.update_mask)
+            .await,
+    )
+}
+
+async fn delete_course_work_material(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path((course_id, id)): Path<(String, String)>,
+) -> Response {
+    if !state.authorized(&headers) {
+        return google_error(401, "UNAUTHENTICATED");
+    }
+    respond(
+        state
+            .backend
+            .delete_course_work_material(&course_id, &id)
+            .await
+            .map(|()| serde_json::json!({})),
+    )
+}
+
+async fn list_topics(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path(course_id): Path<String>,
+    Query(query): Query<ListQuery>,
+) -> Response {
+    if !state.authorized(&headers) {
+        return google_error(401, "UNAUTHENTICATED");
+    }
+    let page = query.page_token.as_deref();
+    respond(state.backend.list_topics(&course_id, page).await)
+}
+
+/// Custom methods like `{id}:turnIn`, which matchit can't route on its own.
+async fn submission_action(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path((course_id, work_id, action)): Path<(String, String, String)>,
+    body: axum::body::Bytes,
+) -> Response {
+    if !state.authorized(&headers) {
+        return google_error(401, "UNAUTHENTICATED");
+    }
+    let backend = &state.backend;
+    let result = match action.split_once(':') {
+        Some((id, "turnIn")) => backend.turn_in(&course_id, &work_id, id).await,
+        Some((id, "reclaim")) => backend.reclaim(&course_id, &work_id, id).await,
+        Some((id, "return")) => backend.return_submission(&course_id, &work_id, id).await,
+        Some((id, "modifyAttachments")) => {
+            let Ok(request) = serde_json::from_slice::<ModifyAttachmentsRequest>(&body) else {
+                return google_error(400, "INVALID_ARGUMENT");
+            };
+            let attachments = request.add_attachments.unwrap_or_default();
+            backend
+                .add_attachments(&course_id, &work_id, id, attachments)
+                .await
+        }
+        _ => return google_error(404, "NOT_FOUND"),
+    };
+    respond(result.map(|()| serde_json::json!({})))
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PatchQuery {
+    update_mask: String,
+}
+
+async fn patch_submission(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path((course_id, work_id, id)): Path<(String, String, String)>,
+    Query(query): Query<PatchQuery>,
+    Json(submission): Json<StudentSubmission>,
+) -> Response {
+    if !state.authorized(&headers) {
+        return google_error(401, "UNAUTHENTICATED");
+    }
+    respond(
+        state
+            .backend
+            .patch_submission(&course_id, &work_id, &id, submission, &query.update_mask)
+            .await
+            .map(|()| serde_json::json!({})),
+    )
+}
+
+async fn list_students(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path(course_id): Path<String>,
+    Query(query): Query<ListQuery>,
+) -> Response {
+    if !state.authorized(&headers) {
+        return google_error(401, "UNAUTHENTICATED");
+    }
+    let page = query.page_token.as_deref();
+    respond(state.backend.list_students(&course_id, page).await)
+}
+
+async fn list_announcements(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path(course_id): Path<String>,
+    Query(query): Query<ListQuery>,
+) -> Response {
+    if !state.authorized(&headers) {
+        return google_error(401, "UNAUTHENTICATED");
+    }
+    let page = query.page_token.as_deref();
+    respond(
+        state
+            .backend
+            .list_announcements(&course_id, page, query.page_size)
+            .await,
+    )
+}
+
+async fn list_teachers(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path(course_id): Path<String>,
+    Query(query): Query<ListQuery>,
+) -> Response {
+    if !state.authorized(&headers) {
+        return google_error(401, "UNAUTHENTICATED");
+    }
+    let page = query.page_token.as_deref();
+    respond(state.backend.list_teachers(&course_id, page).await)
+}
+
+async fn get_teacher(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path((course_id, user_id)): Path<(String, String)>,
+) -> Response {
+    if !state.authorized(&headers) {
+        return google_error(401, "UNAUTHENTICATED");
+    }
+    match state.backend.is_teacher(&course_id).await {
+        Ok(true) if user_id == "me" => {
+            Json(serde_json::json!({ "userId": user_id })).into_response()
+        }
+        Ok(_) => google_error(404, "NOT_FOUND"),
+        Err(e) => respond::<()>(Err(e)),
+    }
+}
+
+async fn get_user_profile(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path(user_id): Path<String>,
+) -> Response {
+    if !state.authorized(&headers) {
+        return google_error(401, "UNAUTHENTICATED");
+    }
+    respond(state.backend.get_user_profile(&user_id).await)
+}
+
+/// The css router running against a [`MockGoogle`], with a browser-ish client that
+/// keeps cookies and doesn't follow redirects.
+pub struct TestApp {
+    pub url: String,
+    pub google: MockGoogle,
+    http: reqwest::Client,
+    cookies: HashMap<String, String>,
+}
+
+impl TestApp {
+    pub fn start(backend: MemoryBackend) -> Self {
+        let google = MockGoogle::start(backend);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let data_dir = std::env::temp_dir().join(format!(
+            "css-test-{}",
+            oauth2::CsrfToken::new_random().secret()
+        ));
+        let state = AppState::new(Config {
+            key: hex::encode([7; 64]),
+            root_url: url.clone(),
+            client_id: "client".to_string(),
+            client_secret: "secret".to_string(),
+            max_pages: None,
+            data_dir: Some(data_dir.to_string_lossy().into_owned()),
+            classroom_url: Some(google.url.clone()),
+            auth_url: Some(format!("{}/o/oauth2/v2/auth", google.url)),
+            token_url: Some(format!("{}/token", google.url)),
+            revocation_url: Some(format!("{}/revoke", google.url)),
+            cache_ttl: None,
+            cache_stale: None,
+            cache_entries: None,
+            completion: crate::completion::CompletionPolicy::default(),
+        });
+        let server = axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(crate::app(state).into_make_service());
+        tokio::spawn(server);
+        let http = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap();
+        Self {
+            url,
+            google,
+            http,
+            cookies: HashMap::new(),
+        }
+    }
+
+    /// GET `url`, which may be a path on the app or an absolute URL.
+    pub async fn get(&mut self, url: &str) -> reqwest::Response {
+        let request = self.http.get(self.absolute(url));
+        self.send(request).await
+    }
+
+    /// POST a form to a path on the app.
+    pub async fn post(&mut self, path: &str, form: &[(&str, &str)]) -> reqwest::Response {
+        let request = self.http.post(self.absolute(path)).form(form);
+        self.send(request).await
+    }
+
+    fn absolute(&self, url: &str) -> String {
+        if url.starts_with('/') {
+            format!("{}{url}", self.url)
+        } else {
+            url.to_string()
+        }
+    }
+
+    async fn send(&mut self, request: reqwest::RequestBuilder) -> reqwest::Response {
+        let cookie = self
+            .cookies
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join("; ");
+        let response = request.header(header::COOKIE, cookie).send().await.unwrap();
+        for set_cookie in response.headers().get_all(header::SET_COOKIE) {
+            let set_cookie = set_cookie.to_str().unwrap();
+            let pair = set_cookie.split(';').next().unwrap();
+            let (name, value) = pair.split_once('=').unwrap();
+            if value.is_empty() {
+                self.cookies.remove(name);
+            } else {
+                self.cookies.insert(name.to_string(), value.to_string());
+            }
+        }
+        response
+    }
+
+    pub fn has_cookie(&self, name: &str) -> bool {
+        self.cookies.contains_key(name)
+    }
+
+    /// Set a cookie the way the browser would, like the `tz` cookie.
+    pub fn set_cookie(&mut self, name: &str, value: &str) {
+        self.cookies.insert(name.to_string(), value.to_string());
+    }
+
+    pub fn remove_cookie(&mut self, name: &str) {
+        self.cookies.remove(name);
+    }
+
+    /// Go through the whole OAuth flow, ending up with access and refresh cookies.
+    pub async fn sign_in(&mut self) {
+        self.sign_in_from("/oauth").await;
+    }
+
+    /// Like [`Self::sign_in`], but opting in to teacher mode.
+    pub async fn sign_in_as_teacher(&mut self) {
+        self.sign_in_from("/oauth?teacher").await;
+    }
+
+    async fn sign_in_from(&mut self, start: &str) {
+        let mut response = self.get(start).await;
+        while response.status().is_redirection() {
+            let location = response.headers()[header::LOCATION].to_str().unwrap();
+            if location == "/classes" {
+                return;
+            }
+            let location = location.to_string();
+            response = self.get(&location).await;
+        }
+        panic!("sign in ended with {}", response.status());
+    }
+}
+
+mod tests {
+    use super::*;
+
+    fn location(response: &reqwest::Response) -> &str {
+        response.headers()[header::LOCATION].to_str().unwrap()
+    }
+
+    /// The CSRF token in the first form on a page.
+    fn csrf_token(html: &str) -> String {
+        html.split(r#"name="csrf" value=""#)
+            .nth(1)
+            .and_then(|rest| rest.split('"').next())
+            .unwrap()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn sign_in_and_view_todos() {
+        let mut app = TestApp::start(MemoryBackend::fixture());
+        let response = app.get("/todo").await;
+        assert_eq!(location(&response), "/oauth");
+        app.sign_in().await;
+        assert!(app.has_cookie("access") && app.has_cookie("refresh"));
+        let response = app.get("/todo").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let html = response.text().await.unwrap();
+        assert!(html.contains("Homework 2"));
+        assert!(!html.contains("Homework 1"));
+    }
+
+    #[tokio::test]
+    async fn logout_revokes_the_refresh_token() {
+        let mut app = TestApp::start(MemoryBackend::fixture());
+        app.sign_in().await;
+        let html = app.get("/logout").await.text().await.unwrap();
+        assert!(html.contains("calendar feeds use the same access"));
+        let csrf = csrf_token(&html);
+        for wrong in ["", "not-the-token"] {
+            let response = app.post("/logout", &[("csrf", wrong)]).await;
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        }
+        assert!(app.has_cookie("refresh") && !app.google.refresh_token_revoked());
+        let response = app.post("/logout", &[("csrf", &csrf)]).await;
+        assert_eq!(location(&response), "/");
+        assert!(app.google.refresh_token_revoked());
+        for cookie in ["access", "refresh", "user"] {
+            assert!(!app.has_cookie(cookie), "{cookie} cookie is kept");
+        }
+        let response = app.get("/todo").await;
+        assert_eq!(location(&response), "/oauth");
+    }
+
+    /// The token of the newest calendar feed listed on the settings page.
+    fn feed_token(html: &str) -> String {
+        let start = html.rfind("/feed/").unwrap() + "/feed/".len();
+        let end = start + html[start..].find(".ics").unwrap();
+        html[start..end].to_string()
+    }
+
+    /// Fetch a calendar feed the way a calendar app would, without any cookies.
+    async fn fetch_feed(app: &TestApp, token: &str) -> reqwest::Response {
+        reqwest::get(format!("{}/feed/{token}.ics", app.url))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn creates_fetches_and_revokes_feeds() {
+        let mut app = TestApp::start(MemoryBackend::fixture());
+        app.sign_in().await;
+        let html = app.get("/settings").await.text().await.unwrap();
+        assert!(html.contains("You don't have any calendar feeds yet."));
+        let csrf = csrf_token(&html);
+        let response = app.post("/settings/feeds", &[("csrf", &csrf)]).await;
+        assert_eq!(location(&response), "/settings");
+        let html = app.get("/settings").await.text().await.unwrap();
+        let token = feed_token(&html);
+        // css only keeps a hash of the token, so it's shown once and never again
+        let html = app.get("/settings").await.text().await.unwrap();
+        assert!(!html.contains(&token));
+        assert!(html.contains("Calendar feed created"));
+        let response = fetch_feed(&app, &token).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("text/calendar"));
+        let ics = response.text().await.unwrap();
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.contains("Homework 2"));
+        let response = fetch_feed(&app, "not-a-feed").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = app
+            .post(
+                "/settings/feeds/revoke",
+                &[("csrf", &csrf), ("id", &crate::routes::feed_id(&token))],
+            )
+            .await;
+        assert_eq!(location(&response), "/settings");
+        let response = fetch_feed(&app, &token).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let html = app.get("/settings").await.text().await.unwrap();
+        assert!(html.contains("You don't have any calendar feeds yet."));
+    }
+
+    #[tokio::test]
+    async fn feeds_stop_once_google_access_is_revoked() {
+        let mut app = TestApp::start(MemoryBackend::fixture());
+        app.sign_in().await;
+        let html = app.get("/settings").await.text().await.unwrap();
+        let csrf = csrf_token(&html);
+        app.post("/settings/feeds", &[("csrf", &csrf)]).await;
+        let html = app.get("/settings").await.text().await.unwrap();
+        let token = feed_token(&html);
+        app.google.revoke_refresh_token();
+        let response = fetch_feed(&app, &token).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        // the dead feed is forgotten rather than retried on every fetch
+        app.google.expire_access_tokens();
+        let html = app.get("/settings").await.text().await.unwrap();
+        assert!(html.contains("You don't have any calendar feeds yet."));
+    }
+
+    #[tokio::test]
+    async fn says_when_the_time_zone_is_unknown() {
+        let mut app = TestApp::start(MemoryBackend::fixture());
+        app.sign_in().await;
+        app.set_cookie("tz", "Europe/Kyiv");
+        let html = app.get("/settings").await.text().await.unwrap();
+        assert!(html.contains("<option selected>Europe&#x2F;Kyiv</option>"));
+        assert!(!html.contains("doesn't know the time zone"));
+        app.set_cookie("tz", "Mars/Olympus_Mons");
+        let html = app.get("/settings").await.text().await.unwrap();
+        assert!(html.contains("doesn't know the time zone Mars&#x2F;Olympus_Mons"));
+        assert!(html.contains("<option selected>UTC</option>"));
+    }
+
+    #[tokio::test]
+    async fn saves_todo_options() {
+        let mut app = TestApp::start(MemoryBackend::fixture());
+        app.sign_in().await;
+        let html = app
+            .get("/todo?class=history&group=due")
+            .await
+            .text()
+            .await
+            .unwrap();
+        assert!(html.contains("Essay") && !html.contains("Homework 2"));
+        let csrf = csrf_token(&html);
+        let response = app
+            .post(
+                "/todo/options",
+                &[("csrf", &csrf), ("class", "history"), ("group", "due")],
+            )
+            .await;
+        assert_eq!(location(&response), "/todo");
+        let html = app.get("/todo").await.text().await.unwrap();
+        assert!(html.contains("<h2>Overdue</h2>") && !html.contains("Homework 2"));
+        let html = app.get("/todo?class=").await.text().await.unwrap();
+        assert!(html.contains("Homework 2"));
+    }
+
+    #[tokio::test]
+    async fn marks_todos_done_and_snoozed() {
+        let backend = MemoryBackend::fixture();
+        let id = backend.submissions.lock().unwrap()["math"]
+            .iter()
+            .find(|submission| submission.course_work_id.as_deref() == Some("hw2"))
+            .and_then(|submission| submission.id.clone())
+            .unwrap();
+        let mut app = TestApp::start(backend);
+        app.sign_in().await;
+        let html = app.get("/todo/math").await.text().await.unwrap();
+        assert!(html.contains("Homework 2") && !html.contains("<details>"));
+        let csrf = csrf_token(&html);
+        let mark = |mark, until| {
+            [
+                ("csrf", csrf.as_str()),
+                ("class_id", "math"),
+                ("id", id.as_str()),
+                ("mark", mark),
+                ("until", until),
+                ("from_class", "true"),
+            ]
+        };
+        let response = app.post("/todo/mark", &mark("done", "")).await;
+        assert_eq!(location(&response), "/todo/math");
+        let html = app.get("/todo").await.text().await.unwrap();
+        let (list, marked) = html.split_once("<details>").unwrap();
+        assert!(!list.contains("Homework 2") && marked.contains("Marked done"));
+        // the JSON API leaves out the same work, and says why
+        for path in ["/api/v1/todo", "/api/v1/todo/math"] {
+            let json: serde_json::Value =
+                serde_json::from_str(&app.get(path).await.text().await.unwrap()).unwrap();
+            let listed = json["todos"].as_array().unwrap();
+            assert!(
+                listed.iter().all(|todo| todo["name"] != "Homework 2"),
+                "{path}"
+            );
+            assert_eq!(json["marked"][0]["todo"]["name"], "Homework 2");
+            assert_eq!(json["marked"][0]["mark"], "done");
+        }
+        // and so do the calendars
+        app.post("/settings/feeds", &[("csrf", &csrf)]).await;
+        let token = feed_token(&app.get("/settings").await.text().await.unwrap());
+        let calendars = [
+            app.get("/todo.ics").await.text().await.unwrap(),
+            app.get("/todo/math.ics").await.text().await.unwrap(),
+            fetch_feed(&app, &token).await.text().await.unwrap(),
+        ];
+        for ics in calendars {
+            assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+            assert!(!ics.contains("Homework 2"), "{ics}");
+        }
+        let response = app.post("/todo/mark", &mark("snoozed", "2023-01-01")).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        app.post("/todo/mark", &mark("snoozed", "2999-01-01")).await;
+        let html = app.get("/todo").await.text().await.unwrap();
+        assert!(html.contains("Snoozed until Jan 1, 2999"));
+        let json: serde_json::Value =
+            serde_json::from_str(&app.get("/api/v1/todo").await.text().await.unwrap()).unwrap();
+        assert_eq!(json["marked"][0]["mark"], "snoozed");
+        assert_eq!(json["marked"][0]["until"], "2999-01-01T00:00:00Z");
+        app.post("/todo/mark", &mark("none", "")).await;
+        let html = app.get("/todo").await.text().await.unwrap();
+        assert!(html.contains("Homework 2") && !html.contains("<details>"));
+    }
+
+    #[tokio::test]
+    async fn api_returns_json() {
+        let mut app = TestApp::start(MemoryBackend::fixture());
+        let response = app.get("/api/v1/todo").await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        app.sign_in().await;
+        let list: serde_json::Value =
+            serde_json::from_str(&app.get("/api/v1/todo").await.text().await.unwrap()).unwrap();
+        assert_eq!(list["todos"].as_array().unwrap().len(), 3);
+        assert_eq!(list["todos"][0]["name"], "Homework 2");
+        assert_eq!(list["marked"].as_array().unwrap().len(), 0);
+        let response = app.get("/api/v1/class/gym").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let error: serde_json::Value =
+            serde_json::from_str(&response.text().await.unwrap()).unwrap();
+        assert_eq!(error["error"]["status"], 404);
+        // bad paths and query strings get the same JSON errors, not axum's plain text
+        for path in [
+            "/api/v1/class/%FF",
+            "/api/v1/class/math/announcements?page=1&page=2",
+        ] {
+            let response = app.get(path).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{path}");
+            let error: serde_json::Value =
+                serde_json::from_str(&response.text().await.unwrap()).unwrap();
+            assert_eq!(error["error"]["status"], 400, "{path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn turns_in_work() {
+        let mut app = TestApp::start(MemoryBackend::fixture());
+        app.sign_in().await;
+        let html = app
+            .get("/assignment/math/hw2/turn-in")
+            .await
+            .text()
+            .await
+            .unwrap();
+        let csrf = csrf_token(&html);
+        let response = app
+            .post("/assignment/math/hw2/turn-in", &[("csrf", &csrf)])
+            .await;
+        assert_eq!(location(&response), "/assignment/math/hw2");
+        let html = app.get("/assignment/math/hw2").await.text().await.unwrap();
+        assert!(html.contains("Turned in"));
+        assert!(html.contains("Unsubmit"));
+    }
+
+    #[tokio::test]
+    async fn answers_questions() {
+        let mut backend = MemoryBackend::fixture();
+        backend.add_question("math", "q1", "What is 6 x 7?", &[]);
+        backend.add_submission("math", "q1", "CREATED", false, None);
+        backend.made_by_css("math", "q1");
+        let mut app = TestApp::start(backend);
+        app.sign_in().await;
+        let html = app.get("/assignment/math/q1").await.text().await.unwrap();
+        let csrf = csrf_token(&html);
+        let response = app
+            .post(
+                "/assignment/math/q1/answer",
+                &[("csrf", &csrf), ("answer", "42")],
+            )
+            .await;
+        assert_eq!(location(&response), "/assignment/math/q1");
+        let html = app.get("/assignment/math/q1").await.text().await.unwrap();
+        assert!(html.contains("Saved answer: 42"));
+    }
+
+    #[tokio::test]
+    async fn changes_what_counts_as_done() {
+        let mut app = TestApp::start(MemoryBackend::fixture());
+        app.sign_in().await;
+        let html = app.get("/settings").await.text().await.unwrap();
+        let csrf = csrf_token(&html);
+        let response = app
+            .post(
+                "/settings/completion",
+                &[
+                    ("csrf", &csrf),
+                    ("late", ""),
+                    ("returned", "all"),
+                    ("no_due_date", "false"),
+                ],
+            )
+            .await;
+        assert_eq!(location(&response), "/settings");
+        let html = app.get("/todo").await.text().await.unwrap();
+        assert!(html.contains("Reading") && !html.contains("Project"));
+        let html = app.get("/settings").await.text().await.unwrap();
+        assert!(html.contains(r#"<option value="all" selected>"#));
+        let response = app
+            .post(
+                "/settings/completion",
+                &[
+                    ("csrf", &csrf),
+                    ("late", "maybe"),
+                    ("returned", ""),
+                    ("no_due_date", ""),
+                ],
+            )
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        // the error page names the field
+        let html = response.text().await.unwrap();
+        assert!(
+            html.contains("InvalidSetting(\n    &quot;late&quot;"),
+            "{html}"
+        );
+    }
+
+    #[tokio::test]
+    async fn teacher_mode_is_opt_in() {
+        let mut backend = MemoryBackend::fixture();
+        backend.add_course("art", "Art");
+        backend.teaching.push("art".to_string());
+        backend.add_work("art", "mural", "Mural", None);
+        backend.add_user("arnold", "Arnold Perlstein");
+        backend.add_student_submission("art", "mural", "arnold", "TURNED_IN");
+        let mut app = TestApp::start(backend);
+        app.sign_in().await;
+        assert!(!app.has_cookie("teacher"));
+        let response = app.get("/assignment/art/mural/submissions").await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        app.sign_in_as_teacher().await;
+        assert!(app.has_cookie("teacher"));
+        let html = app
+            .get("/assignment/art/mural/submissions")
+            .await
+            .text()
+            .await
+            .unwrap();
+        assert!(html.contains("Arnold Perlstein"));
+        // students' work isn't the teacher's to do
+        let html = app.get("/todo").await.text().await.unwrap();
+        assert!(!html.contains("Mural"));
+        // signing in again keeps the scopes granted before
+        app.sign_in().await;
+        assert!(app.has_cookie("teacher"));
+        // but not teacher mode, once it's turned off
+        let html = app.get("/settings").await.text().await.unwrap();
+        let csrf = csrf_token(&html);
+        let _ = app.post("/settings/teacher/off", &[("csrf", &csrf)]).await;
+        assert!(!app.has_cookie("teacher"));
+        app.sign_in().await;
+        assert!(!app.has_cookie("teacher"));
+    }
+
+    #[tokio::test]
+    async fn teacher_manages_classwork() {
+        let mut backend = MemoryBackend::fixture();
+        backend.add_course("art", "Art");
+        backend.teaching.push("art".to_string());
+        backend.add_user("arnold", "Arnold Perlstein");
+        backend.add_student("art", "arnold");
+        let mut app = TestApp::start(backend);
+        app.sign_in_as_teacher().await;
+        let html = app
+            .get("/class/art/coursework/new")
+            .await
+            .text()
+            .await
+            .unwrap();
+        assert!(html.contains("Arnold Perlstein"));
+        let csrf = csrf_token(&html);
+        let response = app
+            .post(
+                "/class/art/coursework/new",
+                &[
+                    ("csrf", &csrf),
+                    ("title", "Mural"),
+                    ("due_date", "2023-09-01"),
+                    ("due_time", "17:00"),
+                    ("timezone", "America/New_York"),
+                    ("assignee_mode", "INDIVIDUAL_STUDENTS"),
+                    ("students", "arnold"),
+                ],
+            )
+            .await;
+        let assignment = location(&response).to_string();
+        let html = app.get(&assignment).await.text().await.unwrap();
+        assert!(html.contains("Due Sep 1, 2023 9:00 PM UTC"));
+        let response = app
+            .post(
+                "/settings/timezone",
+                &[("csrf", &csrf), ("timezone", "America/New_York")],
+            )
+            .await;
+        assert_eq!(location(&response), "/settings");
+        let html = app.get(&assignment).await.text().await.unwrap();
+        assert!(html.contains("Due Sep 1, 2023 5:00 PM EDT"));
+        assert!(html.contains("days overdue"));
+        let edit = format!("{assignment}/edit");
+        assert!(html.contains(&edit), "css made this, so it can edit it");
+        let html = app.get("/class/art").await.text().await.unwrap();
+        assert!(html.contains("Mural"));
+        let fields = [
+            ("csrf", csrf.as_str()),
+            ("title", "Big mural"),
+            ("assignee_mode", "ALL_STUDENTS"),
+        ];
+        let response = app.post(&edit, &fields).await;
+        assert_eq!(location(&response), assignment);
+        let html = app.get(&assignment).await.text().await.unwrap();
+        assert!(html.contains("Big mural"));
+        assert!(html.contains("No due date"));
+        let response = app
+            .post(&format!("{assignment}/delete"), &[("csrf", &csrf)])
+            .await;
+        assert_eq!(location(&response), "/class/art");
+        let html = app.get("/class/art").await.text().await.unwrap();
+        assert!(
+            !html.contains("mural"),
+            "deleted coursework is still cached"
+        );
+    }
+
+    #[tokio::test]
+    async fn refreshes_expired_access_token() {
+        let mut app = TestApp::start(MemoryBackend::fixture());
+        app.sign_in().await;
+        app.google.expire_access_tokens();
+        app.remove_cookie("access");
+        let response = app.get("/classes").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(app.has_cookie("access"), "refreshed token wasn't saved");
+        assert!(response.text().await.unwrap().contains("History"));
+    }
+
+    #[tokio::test]
+    async fn revoked_refresh_token_signs_out() {
+        let mut app = TestApp::start(MemoryBackend::fixture());
+        app.sign_in().await;
+        app.google.revoke_refresh_token();
+        app.remove_cookie("access");
+        let response = app.get("/classes").await;
+        assert_eq!(location(&response), "/oauth");
+        assert!(!app.has_cookie("refresh"));
+    }
+}
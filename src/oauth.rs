@@ -1,13 +1,23 @@
-use axum::extract::{Query, State};
-use axum::response::Redirect;
+use axum::extract::{Form, Query, State};
+use axum::response::{Html, Redirect};
+use oauth2::basic::BasicTokenResponse;
 use oauth2::reqwest::async_http_client;
-use oauth2::{AuthorizationCode, CsrfToken, PkceCodeChallenge, Scope, TokenResponse};
-use tower_cookies::cookie::time::OffsetDateTime;
-use tower_cookies::{Cookie, Cookies};
+use oauth2::{
+    http::{header, HeaderMap, HeaderValue, Method},
+    url::form_urlencoded,
+    AuthorizationCode, CsrfToken, HttpRequest, PkceCodeChallenge, Scope, TokenResponse,
+};
+use tower_cookies::cookie::time::{error::ConversionRange, OffsetDateTime};
+use tower_cookies::{Cookie, Cookies, PrivateCookies};
 
-use crate::{AppState, Error};
+use crate::{
+    csrf::{Csrf, CsrfForm},
+    error::RevocationError,
+    state::PendingSignIn,
+    AppState, Error,
+};
 
-const SCOPES: [&str; 11] = [
+const SCOPES: [&str; 12] = [
     "userinfo.email",
     "userinfo.profile",
     "classroom.announcements",
@@ -16,29 +26,56 @@ const SCOPES: [&str; 11] = [
     "classroom.topics",
     "classroom.guardianlinks.me.readonly",
     "classroom.courses",
+    "classroom.rosters.readonly",
     "classroom.addons.student",
     "classroom.profile.emails",
     "classroom.profile.photos",
 ];
 
-pub async fn redirect(State(state): State<AppState>) -> Result<Redirect, Error> {
+/// Extra scopes for teacher mode, only requested when the user turns it on, so students
+/// never have to grant css access to other students' work.
+const TEACHER_SCOPES: [&str; 1] = ["classroom.coursework.students"];
+
+fn scope_url(scope: &str) -> String {
+    format!("https://www.googleapis.com/auth/{scope}")
+}
+
+#[derive(serde::Deserialize)]
+pub struct RedirectQuery {
+    /// Set to ask for [`TEACHER_SCOPES`] too
+    teacher: Option<String>,
+}
+
+pub async fn redirect(
+    State(state): State<AppState>,
+    Query(query): Query<RedirectQuery>,
+) -> Result<Redirect, Error> {
     let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+    let teacher = query.teacher.is_some();
+    let teacher_scopes = if teacher { &TEACHER_SCOPES[..] } else { &[] };
     let scopes = SCOPES
         .iter()
-        .map(|scope| Scope::new(format!("https://www.googleapis.com/auth/{scope}")));
+        .chain(teacher_scopes)
+        .map(|scope| Scope::new(scope_url(scope)));
     let (auth_url, csrf_token) = state
         .oauth
         .authorize_url(CsrfToken::new_random)
         .set_pkce_challenge(pkce_challenge)
         .add_scopes(scopes)
+        // without this, Google never sends a refresh token
+        .add_extra_param("access_type", "offline")
+        // keep teacher scopes the user granted before when they sign in again without them
+        .add_extra_param("include_granted_scopes", "true")
         .url();
-    state
-        .tokens
-        .write()
-        .await
-        .insert(csrf_token.secret().to_string(), pkce_verifier);
+    state.tokens.write().await.insert(
+        csrf_token.secret().clone(),
+        PendingSignIn {
+            pkce_verifier,
+            teacher,
+        },
+    );
     tokio::spawn(async move {
-        tokio::time::sleep(std::time::Duration::from_secs(600)).await;
+        tokio::time::sleep(std::time::Duration::from_mins(10)).await;
         state.tokens.write().await.remove(csrf_token.secret());
     });
     Ok(Redirect::to(auth_url.as_str()))
@@ -49,7 +86,7 @@ pub async fn set_tokens(
     Query(query): Query<SetIdQuery>,
     encrypted_cookies: Cookies,
 ) -> Result<Redirect, Error> {
-    let pkce_verifier = state
+    let pending = state
         .tokens
         .write()
         .await
@@ -58,18 +95,43 @@ pub async fn set_tokens(
     let token_result = state
         .oauth
         .exchange_code(AuthorizationCode::new(query.code))
-        .set_pkce_verifier(pkce_verifier)
+        .set_pkce_verifier(pending.pkce_verifier)
         .request_async(async_http_client)
         .await
         .map_err(|_| Error::CodeExchangeFailed)?;
-    let access = token_result.access_token().secret().clone();
     let private_cookies = encrypted_cookies.private(&state.key);
+    // Google keeps granting teacher scopes after teacher mode is turned off, so only
+    // keep it on if the user asked for it
+    let teacher = pending.teacher || private_cookies.get("teacher").is_some();
+    // this might be a different account than before
+    clear_tokens(&private_cookies);
+    store_tokens(&private_cookies, &token_result)?;
+    // the user can untick scopes on Google's consent screen, so check what they actually granted
+    let granted = token_result.scopes().map_or(&[][..], Vec::as_slice);
+    let has_scopes = TEACHER_SCOPES
+        .iter()
+        .all(|scope| granted.iter().any(|granted| **granted == scope_url(scope)));
+    if teacher && has_scopes {
+        let mut cookie = Cookie::new("teacher", "1");
+        cookie.set_path("/");
+        private_cookies.add(cookie);
+    }
+    Ok(Redirect::to("/classes"))
+}
+
+/// Save the tokens from a token response into the private `access` and `refresh` cookies.
+/// The refresh cookie is left alone if Google didn't send a new refresh token.
+pub fn store_tokens(
+    private_cookies: &PrivateCookies,
+    token_result: &BasicTokenResponse,
+) -> Result<(), ConversionRange> {
+    let access = token_result.access_token().secret().clone();
     let mut access_cookie = Cookie::new("access", access);
     access_cookie.set_expires(
         OffsetDateTime::now_utc().saturating_add(
             token_result
                 .expires_in()
-                .unwrap_or_else(|| std::time::Duration::from_secs(3600))
+                .unwrap_or_else(|| std::time::Duration::from_hours(1))
                 .try_into()?,
         ),
     );
@@ -80,7 +142,73 @@ pub async fn set_tokens(
         refresh_cookie.set_path("/");
         private_cookies.add(refresh_cookie);
     }
-    Ok(Redirect::to("/classes"))
+    Ok(())
+}
+
+/// Remove the `access`, `refresh`, `user` and `teacher` cookies, signing the user out of css.
+pub fn clear_tokens(private_cookies: &PrivateCookies) {
+    for name in ["access", "refresh", "user", "teacher"] {
+        let mut cookie = Cookie::named(name);
+        cookie.set_path("/");
+        private_cookies.remove(cookie);
+    }
+}
+
+pub async fn logout_page(State(state): State<AppState>, csrf: Csrf) -> Result<Html<String>, Error> {
+    let mut context = tera::Context::new();
+    context.insert("csrf", csrf.token());
+    Ok(Html(state.tera.render("logout.jinja", &context)?))
+}
+
+pub async fn logout(
+    State(state): State<AppState>,
+    csrf: Csrf,
+    encrypted_cookies: Cookies,
+    Form(form): Form<CsrfForm>,
+) -> Result<Redirect, Error> {
+    csrf.verify(&form.csrf)?;
+    let private_cookies = encrypted_cookies.private(&state.key);
+    let refresh = private_cookies.get("refresh");
+    clear_tokens(&private_cookies);
+    if let Some(refresh) = refresh {
+        revoke_refresh_token(&state, refresh.value()).await?;
+    }
+    Ok(Redirect::to("/"))
+}
+
+/// Revoke `token` at Google's revocation endpoint, as in RFC 7009. oauth2's own revocation
+/// request refuses anything but HTTPS, which rules out the local stand-in for Google the
+/// endpoints can be pointed at, so the request is built here instead. The default endpoint
+/// is still HTTPS.
+async fn revoke_refresh_token(state: &AppState, token: &str) -> Result<(), Error> {
+    let url = state
+        .oauth
+        .revocation_url()
+        .ok_or(oauth2::ConfigurationError::MissingUrl("revocation"))?;
+    let body = form_urlencoded::Serializer::new(String::new())
+        .append_pair("token", token)
+        .append_pair("token_type_hint", "refresh_token")
+        .finish();
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/x-www-form-urlencoded"),
+    );
+    let response = async_http_client(HttpRequest {
+        url: url.url().clone(),
+        method: Method::POST,
+        headers,
+        body: body.into_bytes(),
+    })
+    .await
+    .map_err(RevocationError::Request)?;
+    match response.status_code.as_u16() {
+        // Google rejects tokens that were already revoked or expired, which is fine here
+        200..=299 | 400 => Ok(()),
+        status => {
+            Err(RevocationError::Other(format!("revocation endpoint returned {status}")).into())
+        }
+    }
 }
 
 #[derive(serde::Deserialize)]
@@ -0,0 +1,96 @@
+use std::future::Future;
+
+use classroom::api::{
+    ListCourseWorkMaterialResponse, ListCourseWorkResponse, ListCoursesResponse,
+    ListStudentSubmissionsResponse, ListStudentsResponse, ListTeachersResponse, ListTopicResponse,
+};
+use futures_util::{stream, Stream, TryStreamExt};
+
+use crate::Error;
+
+/// The default upper bound on pages fetched by [`paginate`], used when `max_pages`
+/// is not set in the config.
+pub const DEFAULT_MAX_PAGES: usize = 20;
+
+/// A single page of a Classroom `list` response.
+pub trait Page {
+    type Item;
+    /// Split this page into its items and the token for the next page, if any.
+    fn into_parts(self) -> (Option<Vec<Self::Item>>, Option<String>);
+}
+
+macro_rules! impl_page {
+    ($response:ty, $item:ty, $field:ident) => {
+        impl Page for $response {
+            type Item = $item;
+            fn into_parts(self) -> (Option<Vec<Self::Item>>, Option<String>) {
+                (self.$field, self.next_page_token)
+            }
+        }
+    };
+}
+
+impl_page!(ListCoursesResponse, classroom::api::Course, courses);
+impl_page!(
+    ListCourseWorkResponse,
+    classroom::api::CourseWork,
+    course_work
+);
+impl_page!(
+    ListCourseWorkMaterialResponse,
+    classroom::api::CourseWorkMaterial,
+    course_work_material
+);
+impl_page!(ListTopicResponse, classroom::api::Topic, topic);
+impl_page!(
+    ListStudentSubmissionsResponse,
+    classroom::api::StudentSubmission,
+    student_submissions
+);
+impl_page!(ListStudentsResponse, classroom::api::Student, students);
+impl_page!(ListTeachersResponse, classroom::api::Teacher, teachers);
+
+struct PageState<F> {
+    fetch: F,
+    next: Option<String>,
+    fetched: usize,
+    done: bool,
+}
+
+/// Stream every item of a paginated Classroom `list` call, following
+/// `nextPageToken` until it runs out or `max_pages` pages have been fetched.
+///
+/// `fetch` is called once per page with the page token to request (`None` for
+/// the first page), and is usually a call to one of the [`ClassroomBackend`] list methods.
+///
+/// [`ClassroomBackend`]: crate::backend::ClassroomBackend
+pub fn paginate<'a, P, F, Fut>(
+    max_pages: usize,
+    fetch: F,
+) -> impl Stream<Item = Result<P::Item, Error>> + 'a
+where
+    P: Page + 'a,
+    P::Item: 'a,
+    F: FnMut(Option<String>) -> Fut + 'a,
+    Fut: Future<Output = Result<P, Error>> + 'a,
+{
+    let state = PageState {
+        fetch,
+        next: None,
+        fetched: 0,
+        done: false,
+    };
+    stream::try_unfold(state, move |mut state| async move {
+        if state.done || state.fetched >= max_pages {
+            return Ok(None);
+        }
+        let page = (state.fetch)(state.next.take()).await?;
+        state.fetched += 1;
+        let (items, next) = page.into_parts();
+        state.done = next.is_none();
+        state.next = next;
+        let items = items.unwrap_or_default().into_iter().map(Ok);
+        Ok::<_, Error>(Some((stream::iter(items), state)))
+    })
+    .try_flatten()
+}
@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, Query, State},
+    response::Html,
+};
+use classroom::{
+    api::Course,
+    chrono::{DateTime, Utc},
+};
+use futures_util::future::join_all;
+use tokio::try_join;
+
+use super::{attachments, Attachment};
+use crate::{auth::UserClient, backend::Backend, timezone::Timezone, AppState, Error};
+
+pub async fn announcements(
+    UserClient(client): UserClient,
+    State(state): State<AppState>,
+    Timezone(timezone): Timezone,
+    Path(id): Path<String>,
+    Query(pages): Query<super::PaginationQuery>,
+) -> Result<Html<String>, Error> {
+    let mut context = tera::Context::new();
+    context.insert("timezone", timezone.name());
+    context.insert("is_first_page", &pages.page.is_none());
+    let page = get_announcements(&client, &id, pages.page).await?;
+    context.insert("class", &page.class);
+    context.insert("posts", &page.posts);
+    context.insert("pagination_token", &page.next_page);
+    Ok(Html(state.tera.render("announcements.jinja", &context)?))
+}
+
+/// An announcement, with its author's name looked up.
+#[derive(serde::Serialize)]
+pub struct Post {
+    id: Option<String>,
+    text: Option<String>,
+    /// The author's full name, if their profile is visible to the user
+    author: Option<String>,
+    created: Option<DateTime<Utc>>,
+    link: Option<String>,
+    attachments: Vec<Attachment>,
+}
+
+/// One page of a class's announcements.
+#[derive(serde::Serialize)]
+pub struct PostPage {
+    class: Course,
+    posts: Vec<Post>,
+    next_page: Option<String>,
+}
+
+pub async fn get_announcements(
+    client: &Backend,
+    id: &str,
+    page: Option<String>,
+) -> Result<PostPage, Error> {
+    let (class, (posts, next_page)) = try_join!(
+        client.get_course(id),
+        list_posts(client, id, page.as_deref(), 10)
+    )?;
+    Ok(PostPage {
+        class,
+        posts,
+        next_page,
+    })
+}
+
+/// The newest `count` announcements in a class.
+pub async fn latest_posts(client: &Backend, id: &str, count: i32) -> Result<Vec<Post>, Error> {
+    Ok(list_posts(client, id, None, count).await?.0)
+}
+
+async fn list_posts(
+    client: &Backend,
+    id: &str,
+    page: Option<&str>,
+    page_size: i32,
+) -> Result<(Vec<Post>, Option<String>), Error> {
+    let resp = client.list_announcements(id, page, Some(page_size)).await?;
+    let announcements = resp.announcements.unwrap_or_default();
+    let authors = user_names(
+        client,
+        announcements
+            .iter()
+            .filter_map(|post| post.creator_user_id.as_deref()),
+    )
+    .await;
+    let posts = announcements
+        .into_iter()
+        .map(|post| Post {
+            attachments: attachments(post.materials.as_deref()),
+            author: post
+                .creator_user_id
+                .as_ref()
+                .and_then(|id| authors.get(id).cloned()),
+            id: post.id,
+            text: post.text,
+            created: post.creation_time,
+            link: post.alternate_link,
+        })
+        .collect();
+    Ok((posts, resp.next_page_token))
+}
+
+/// Look up the full name of each of `ids`, at most once each.
+pub(super) async fn user_names<'a>(
+    client: &Backend,
+    ids: impl Iterator<Item = &'a str>,
+) -> HashMap<String, String> {
+    let mut ids: Vec<&str> = ids.collect();
+    ids.sort_unstable();
+    ids.dedup();
+    let profiles = join_all(ids.iter().map(|id| client.get_user_profile(id))).await;
+    // Classroom hides some profiles, like those of people who left the class,
+    // so a failed lookup just leaves that user out
+    ids.into_iter()
+        .zip(profiles)
+        .filter_map(|(id, profile)| Some((id.to_string(), profile.ok()?.name?.full_name?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::http::StatusCode;
+
+    use super::*;
+    use crate::backend::memory::MemoryBackend;
+
+    #[tokio::test]
+    async fn renders_announcements_newest_first() {
+        let backend = Arc::new(MemoryBackend::fixture());
+        let Html(html) = announcements(
+            UserClient(backend),
+            State(AppState::for_tests()),
+            Timezone(chrono_tz::Tz::UTC),
+            Path("math".to_string()),
+            Query(crate::routes::PaginationQuery { page: None }),
+        )
+        .await
+        .unwrap();
+        let quiz = html.find("Quiz on Friday").unwrap();
+        let welcome = html.find("Welcome to math!").unwrap();
+        assert!(quiz < welcome);
+        assert!(html.contains("Ms. Frizzle"));
+        assert!(html.contains("Study guide"));
+    }
+
+    #[tokio::test]
+    async fn leaves_out_unknown_authors() {
+        let mut backend = MemoryBackend::fixture();
+        backend.add_announcement("history", "gone", "Bye", "former-teacher", None);
+        let backend: Backend = Arc::new(backend);
+        let page = get_announcements(&backend, "history", None).await.unwrap();
+        assert_eq!(page.posts.len(), 1);
+        assert_eq!(page.posts[0].text.as_deref(), Some("Bye"));
+        assert!(page.posts[0].author.is_none());
+    }
+
+    #[tokio::test]
+    async fn pages_through_announcements() {
+        let mut backend = MemoryBackend::fixture();
+        for i in 0..10 {
+            backend.add_announcement("math", &format!("extra{i}"), "More", "teacher", None);
+        }
+        let backend: Backend = Arc::new(backend);
+        let first = get_announcements(&backend, "math", None).await.unwrap();
+        assert_eq!(first.posts.len(), 10);
+        let second = get_announcements(&backend, "math", first.next_page)
+            .await
+            .unwrap();
+        assert_eq!(second.posts.len(), 2);
+        assert_eq!(second.posts[1].text.as_deref(), Some("Welcome to math!"));
+        assert!(second.next_page.is_none());
+    }
+
+    #[tokio::test]
+    async fn unknown_class() {
+        let backend: Backend = Arc::new(MemoryBackend::fixture());
+        let err = get_announcements(&backend, "gym", None)
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+    }
+}
@@ -2,23 +2,341 @@ use axum::{
     extract::{Path, State},
     response::Html,
 };
+use classroom::{
+    api::{Course, CourseWork, StudentSubmission, SubmissionHistory},
+    chrono::{DateTime, Utc},
+};
 use tokio::try_join;
 
-use crate::{auth::UserClient, AppState, Error};
+use super::{attachments, due_date, Attachment};
+use crate::{
+    auth::{TeacherMode, UserClient},
+    backend::Backend,
+    csrf::Csrf,
+    timezone::Timezone,
+    AppState, Error,
+};
 
 pub async fn assignment(
     UserClient(client): UserClient,
     State(state): State<AppState>,
+    TeacherMode(teacher): TeacherMode,
+    Timezone(timezone): Timezone,
+    csrf: Csrf,
     Path((course_id, id)): Path<(String, String)>,
 ) -> Result<Html<String>, Error> {
     let mut context = tera::Context::new();
-    let req_general = client.courses().get(&course_id).param("fields", "id,name");
-    let req_work = client
-        .courses()
-        .course_work_get(&course_id, &id)
-        .param("fields", "nextPageToken,courseWork(id,title)");
-    let (general, work) = try_join!(req_general.doit(), req_work.doit())?;
-    context.insert("class", &general.1);
-    context.insert("coursework", &work.1);
+    context.insert("csrf", csrf.token());
+    context.insert("teacher", &teacher);
+    context.insert("timezone", timezone.name());
+    let assignment = get_assignment(&client, &course_id, &id).await?;
+    context.insert("class", &assignment.class);
+    context.insert("coursework", &assignment.coursework);
+    context.insert("due", &assignment.due);
+    context.insert("materials", &assignment.materials);
+    context.insert("submission", &assignment.submission);
     Ok(Html(state.tera.render("assignment.jinja", &context)?))
 }
+
+/// A piece of coursework, and what the user has done with it.
+#[derive(serde::Serialize)]
+pub struct Assignment {
+    class: Course,
+    coursework: CourseWork,
+    due: Option<DateTime<Utc>>,
+    materials: Vec<Attachment>,
+    /// `None` if the user isn't assigned this coursework, e.g. because they teach the class
+    submission: Option<Submission>,
+}
+
+/// The user's submission for an [`Assignment`].
+#[derive(serde::Serialize)]
+#[allow(clippy::struct_excessive_bools)] // flags for the template
+pub struct Submission {
+    id: Option<String>,
+    /// What the user would call the submission's state, like "Turned in"
+    status: &'static str,
+    /// Whether the coursework was created through css's Google Cloud project. Classroom
+    /// only lets css change submissions for that coursework; everything else has to be
+    /// done in Classroom.
+    by_css: bool,
+    can_turn_in: bool,
+    can_unsubmit: bool,
+    /// Whether css can add attachments, which Classroom only allows on unsubmitted assignments
+    /// that css created
+    can_attach: bool,
+    /// Whether the user can still change their answer to a question, which Classroom only
+    /// lets css do on questions css created
+    can_answer: bool,
+    /// The user's answer, if this is a question and they've answered it
+    answer: Option<String>,
+    late: bool,
+    assigned_grade: Option<f64>,
+    draft_grade: Option<f64>,
+    attachments: Vec<Attachment>,
+    /// Oldest first
+    history: Vec<HistoryEntry>,
+    link: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+pub struct HistoryEntry {
+    time: Option<DateTime<Utc>>,
+    description: String,
+}
+
+impl Submission {
+    fn new(sub: StudentSubmission, work: &CourseWork) -> Self {
+        let turned_in = sub.state.as_deref() == Some("TURNED_IN");
+        let by_css = work.associated_with_developer.unwrap_or(false);
+        let question = matches!(
+            work.work_type.as_deref(),
+            Some("SHORT_ANSWER_QUESTION" | "MULTIPLE_CHOICE_QUESTION")
+        );
+        Self {
+            id: sub.id,
+            status: status(sub.state.as_deref()),
+            by_css,
+            can_turn_in: by_css && !turned_in,
+            can_unsubmit: by_css && turned_in,
+            can_attach: by_css && !turned_in && work.work_type.as_deref() == Some("ASSIGNMENT"),
+            can_answer: by_css && !turned_in && question,
+            answer: sub
+                .short_answer_submission
+                .and_then(|answer| answer.answer)
+                .or_else(|| sub.multiple_choice_submission?.answer),
+            late: sub.late.unwrap_or(false),
+            assigned_grade: sub.assigned_grade,
+            draft_grade: sub.draft_grade,
+            attachments: sub
+                .assignment_submission
+                .and_then(|work| work.attachments)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(Attachment::from_attachment)
+                .collect(),
+            history: sub
+                .submission_history
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(HistoryEntry::new)
+                .collect(),
+            link: sub.alternate_link,
+        }
+    }
+}
+
+impl HistoryEntry {
+    fn new(history: SubmissionHistory) -> Option<Self> {
+        if let Some(state) = history.state_history {
+            return Some(Self {
+                time: state.state_timestamp,
+                description: status(state.state.as_deref()).to_string(),
+            });
+        }
+        let grade = history.grade_history?;
+        let points =
+            |points: Option<f64>| points.map_or_else(|| "-".to_string(), |p| p.to_string());
+        let description = match grade.grade_change_type.as_deref() {
+            Some("DRAFT_GRADE_POINTS_EARNED_CHANGE") => format!(
+                "Draft grade {}/{}",
+                points(grade.points_earned),
+                points(grade.max_points)
+            ),
+            Some("ASSIGNED_GRADE_POINTS_EARNED_CHANGE") => format!(
+                "Graded {}/{}",
+                points(grade.points_earned),
+                points(grade.max_points)
+            ),
+            Some("MAX_POINTS_CHANGE") => format!("Out of {} points", points(grade.max_points)),
+            _ => return None,
+        };
+        Some(Self {
+            time: grade.grade_timestamp,
+            description,
+        })
+    }
+}
+
+/// A submission state as Classroom's UI describes it.
+pub(super) fn status(state: Option<&str>) -> &'static str {
+    match state {
+        Some("TURNED_IN") => "Turned in",
+        Some("RETURNED") => "Returned",
+        Some("RECLAIMED_BY_STUDENT") => "Unsubmitted",
+        _ => "Assigned",
+    }
+}
+
+pub async fn get_assignment(
+    client: &Backend,
+    course_id: &str,
+    id: &str,
+) -> Result<Assignment, Error> {
+    let (class, coursework, submissions) = try_join!(
+        client.get_course(course_id),
+        client.get_course_work(course_id, id),
+        client.list_submissions(course_id, id, None)
+    )?;
+    // students can only see their own submission, so there's at most one
+    let submission = submissions
+        .student_submissions
+        .and_then(|subs| subs.into_iter().next())
+        .map(|sub| Submission::new(sub, &coursework));
+    Ok(Assignment {
+        due: due_date(&coursework),
+        materials: attachments(coursework.materials.as_deref()),
+        class,
+        coursework,
+        submission,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::http::StatusCode;
+
+    use chrono_tz::Tz;
+    use classroom::api::{AssignmentSubmission, GradeHistory, Link, StateHistory};
+
+    use super::*;
+    use crate::backend::memory::MemoryBackend;
+
+    #[tokio::test]
+    async fn renders_assignment() {
+        let backend = Arc::new(MemoryBackend::fixture());
+        let Html(html) = assignment(
+            UserClient(backend.clone()),
+            State(AppState::for_tests()),
+            TeacherMode(false),
+            Timezone(Tz::UTC),
+            Csrf::for_tests(),
+            Path(("math".to_string(), "hw2".to_string())),
+        )
+        .await
+        .unwrap();
+        assert!(html.contains("Homework 2"));
+        let assignment = get_assignment(&(backend as Backend), "math", "hw2")
+            .await
+            .unwrap();
+        assert_eq!(assignment.class.name.as_deref(), Some("Math"));
+        assert_eq!(assignment.coursework.title.as_deref(), Some("Homework 2"));
+        assert_eq!(assignment.submission.unwrap().status, "Assigned");
+        assert!(html.contains(r#"href="/assignment/math/hw2/turn-in""#));
+        assert!(html.contains(r#"name="drive_file""#));
+    }
+
+    #[tokio::test]
+    async fn sends_work_css_didnt_make_to_classroom() {
+        let mut backend = MemoryBackend::fixture();
+        let sub = backend.submission_mut("math", "project").unwrap();
+        sub.alternate_link = Some("https://classroom.google.com/c/math/project".to_string());
+        let Html(html) = assignment(
+            UserClient(Arc::new(backend)),
+            State(AppState::for_tests()),
+            TeacherMode(false),
+            Timezone(Tz::UTC),
+            Csrf::for_tests(),
+            Path(("math".to_string(), "project".to_string())),
+        )
+        .await
+        .unwrap();
+        assert!(!html.contains("/turn-in"));
+        assert!(!html.contains(r#"name="drive_file""#));
+        assert!(html.contains("turn it in, unsubmit it or attach files in Classroom"));
+        assert!(html.contains(
+            r#"href="https:&#x2F;&#x2F;classroom.google.com&#x2F;c&#x2F;math&#x2F;project""#
+        ));
+    }
+
+    #[tokio::test]
+    async fn shows_submission_details() {
+        let mut backend = MemoryBackend::fixture();
+        let work = backend.work_mut("history", "reading").unwrap();
+        work.description = Some("Chapters 1-3".to_string());
+        work.max_points = Some(10.0);
+        let sub = backend.submission_mut("history", "reading").unwrap();
+        sub.alternate_link = Some("https://classroom.google.com/c/history/reading".to_string());
+        sub.assignment_submission = Some(AssignmentSubmission {
+            attachments: Some(vec![classroom::api::Attachment {
+                link: Some(Link {
+                    title: Some("My notes".to_string()),
+                    url: Some("https://example.com/notes".to_string()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }]),
+        });
+        sub.submission_history = Some(vec![
+            SubmissionHistory {
+                state_history: Some(StateHistory {
+                    state: Some("TURNED_IN".to_string()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            SubmissionHistory {
+                grade_history: Some(GradeHistory {
+                    grade_change_type: Some("ASSIGNED_GRADE_POINTS_EARNED_CHANGE".to_string()),
+                    points_earned: Some(10.0),
+                    max_points: Some(10.0),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        ]);
+        let Html(html) = assignment(
+            UserClient(Arc::new(backend)),
+            State(AppState::for_tests()),
+            TeacherMode(false),
+            Timezone(Tz::UTC),
+            Csrf::for_tests(),
+            Path(("history".to_string(), "reading".to_string())),
+        )
+        .await
+        .unwrap();
+        assert!(html.contains("Chapters 1-3"));
+        assert!(html.contains("Sep 4, 2023"));
+        assert!(html.contains("Returned"));
+        assert!(html.contains("10/10"));
+        assert!(html.contains("My notes"));
+        assert!(html.contains("Turned in"));
+    }
+
+    #[tokio::test]
+    async fn sends_private_comments_to_classroom() {
+        let mut backend = MemoryBackend::fixture();
+        let sub = backend.submission_mut("history", "reading").unwrap();
+        sub.alternate_link = Some("https://classroom.google.com/c/history/reading".to_string());
+        let Html(html) = assignment(
+            UserClient(Arc::new(backend)),
+            State(AppState::for_tests()),
+            TeacherMode(false),
+            Timezone(Tz::UTC),
+            Csrf::for_tests(),
+            Path(("history".to_string(), "reading".to_string())),
+        )
+        .await
+        .unwrap();
+        assert!(html.contains("Private comments"));
+        assert!(html.contains("message your teacher in Classroom"));
+    }
+
+    #[tokio::test]
+    async fn unknown_assignment() {
+        let backend = Arc::new(MemoryBackend::fixture());
+        let err = assignment(
+            UserClient(backend),
+            State(AppState::for_tests()),
+            TeacherMode(false),
+            Timezone(Tz::UTC),
+            Csrf::for_tests(),
+            Path(("math".to_string(), "hw9".to_string())),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,92 @@
+use classroom::api::Material;
+
+/// A Drive file, `YouTube` video, link or form attached to a post or submission,
+/// flattened for templates.
+#[derive(serde::Serialize)]
+pub struct Attachment {
+    /// One of `drive`, `youtube`, `link` or `form`
+    pub kind: &'static str,
+    pub title: String,
+    pub url: Option<String>,
+}
+
+impl Attachment {
+    fn new(kind: &'static str, title: Option<&String>, url: Option<&String>) -> Self {
+        Self {
+            kind,
+            title: title
+                .or(url)
+                .map_or_else(|| "Untitled".to_string(), Clone::clone),
+            url: url.cloned(),
+        }
+    }
+
+    #[must_use]
+    pub fn from_material(material: &Material) -> Option<Self> {
+        let drive = material
+            .drive_file
+            .as_ref()
+            .and_then(|v| v.drive_file.as_ref());
+        drive
+            .map(|file| Self::new("drive", file.title.as_ref(), file.alternate_link.as_ref()))
+            .or_else(|| {
+                let video = material.youtube_video.as_ref()?;
+                Some(Self::new(
+                    "youtube",
+                    video.title.as_ref(),
+                    video.alternate_link.as_ref(),
+                ))
+            })
+            .or_else(|| {
+                let link = material.link.as_ref()?;
+                Some(Self::new("link", link.title.as_ref(), link.url.as_ref()))
+            })
+            .or_else(|| {
+                let form = material.form.as_ref()?;
+                Some(Self::new(
+                    "form",
+                    form.title.as_ref(),
+                    form.form_url.as_ref(),
+                ))
+            })
+    }
+
+    /// Like [`Self::from_material`], for files attached to a submission.
+    #[must_use]
+    pub fn from_attachment(attachment: &classroom::api::Attachment) -> Option<Self> {
+        attachment
+            .drive_file
+            .as_ref()
+            .map(|file| Self::new("drive", file.title.as_ref(), file.alternate_link.as_ref()))
+            .or_else(|| {
+                let video = attachment.you_tube_video.as_ref()?;
+                Some(Self::new(
+                    "youtube",
+                    video.title.as_ref(),
+                    video.alternate_link.as_ref(),
+                ))
+            })
+            .or_else(|| {
+                let link = attachment.link.as_ref()?;
+                Some(Self::new("link", link.title.as_ref(), link.url.as_ref()))
+            })
+            .or_else(|| {
+                let form = attachment.form.as_ref()?;
+                Some(Self::new(
+                    "form",
+                    form.title.as_ref(),
+                    form.form_url.as_ref(),
+                ))
+            })
+    }
+}
+
+/// Every attachment in `materials` that css knows how to show.
+#[must_use]
+pub fn attachments(materials: Option<&[Material]>) -> Vec<Attachment> {
+    materials
+        .unwrap_or_default()
+        .iter()
+        .filter_map(Attachment::from_material)
+        .collect()
+}
@@ -0,0 +1,1091 @@
+use axum::{
+    extract::{Path, State},
+    response::{Html, Redirect},
+    Form,
+};
+use chrono_tz::{Tz, TZ_VARIANTS};
+use classroom::{
+    api::{
+        CourseWork, CourseWorkMaterial, IndividualStudentsOptions,
+        ModifyCourseWorkAssigneesRequest, ModifyIndividualStudentsOptions, MultipleChoiceQuestion,
+        Student, Topic,
+    },
+    chrono::{DateTime, NaiveDate, NaiveTime, TimeZone, Utc},
+};
+use futures_util::TryStreamExt;
+use tokio::try_join;
+
+use super::{classroom_from_utc, confirm, due_date, require_teacher};
+use crate::{
+    auth::{TeacherMode, UserClient},
+    backend::Backend,
+    csrf::{Csrf, CsrfForm},
+    paginate::paginate,
+    timezone::Timezone,
+    AppState, Error,
+};
+
+pub async fn new_coursework(
+    UserClient(client): UserClient,
+    State(state): State<AppState>,
+    TeacherMode(teacher): TeacherMode,
+    Timezone(timezone): Timezone,
+    csrf: Csrf,
+    Path(course_id): Path<String>,
+) -> Result<Html<String>, Error> {
+    require_teacher(&client, teacher, &course_id).await?;
+    let page = FormPage {
+        kind: "coursework",
+        action: format!("/class/{course_id}/coursework/new"),
+        cancel: format!("/class/{course_id}"),
+        editing: false,
+        form: ClassworkForm {
+            timezone: timezone.name().to_string(),
+            ..Default::default()
+        },
+    };
+    render_form(&client, &state, &csrf, &course_id, page).await
+}
+
+pub async fn create_coursework(
+    UserClient(client): UserClient,
+    TeacherMode(teacher): TeacherMode,
+    csrf: Csrf,
+    Path(course_id): Path<String>,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> Result<Redirect, Error> {
+    let form = ClassworkForm::from_fields(fields);
+    csrf.verify(&form.csrf)?;
+    require_teacher(&client, teacher, &course_id).await?;
+    let classwork = form.parse()?;
+    let (work_type, multiple_choice_question) = form.work_type()?;
+    let course_work = CourseWork {
+        work_type: Some(work_type.to_string()),
+        multiple_choice_question,
+        ..classwork.course_work()
+    };
+    let created = client.create_course_work(&course_id, course_work).await?;
+    let id = created
+        .id
+        .ok_or(Error::MissingField("courses.courseWork.create.id"))?;
+    Ok(Redirect::to(&format!("/assignment/{course_id}/{id}")))
+}
+
+pub async fn edit_coursework(
+    UserClient(client): UserClient,
+    State(state): State<AppState>,
+    TeacherMode(teacher): TeacherMode,
+    Timezone(timezone): Timezone,
+    csrf: Csrf,
+    Path((course_id, id)): Path<(String, String)>,
+) -> Result<Html<String>, Error> {
+    require_teacher(&client, teacher, &course_id).await?;
+    let work = client.get_course_work(&course_id, &id).await?;
+    let page = FormPage {
+        kind: "coursework",
+        action: format!("/assignment/{course_id}/{id}/edit"),
+        cancel: format!("/assignment/{course_id}/{id}"),
+        editing: true,
+        form: ClassworkForm::from_course_work(&work, timezone),
+    };
+    render_form(&client, &state, &csrf, &course_id, page).await
+}
+
+pub async fn update_coursework(
+    UserClient(client): UserClient,
+    TeacherMode(teacher): TeacherMode,
+    csrf: Csrf,
+    Path((course_id, id)): Path<(String, String)>,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> Result<Redirect, Error> {
+    let form = ClassworkForm::from_fields(fields);
+    csrf.verify(&form.csrf)?;
+    require_teacher(&client, teacher, &course_id).await?;
+    let existing = client.get_course_work(&course_id, &id).await?;
+    let classwork = form.parse()?;
+    let update_mask = classwork.update_mask(
+        "title,description,dueDate,dueTime,maxPoints,topicId",
+        existing.state.as_deref(),
+    )?;
+    client
+        .patch_course_work(&course_id, &id, classwork.course_work(), &update_mask)
+        .await?;
+    if let Some(request) = classwork.assignee_changes(&existing) {
+        client
+            .modify_course_work_assignees(&course_id, &id, request)
+            .await?;
+    }
+    Ok(Redirect::to(&format!("/assignment/{course_id}/{id}")))
+}
+
+/// Ask the teacher to confirm deleting coursework.
+pub async fn delete_coursework_page(
+    UserClient(client): UserClient,
+    State(state): State<AppState>,
+    TeacherMode(teacher): TeacherMode,
+    csrf: Csrf,
+    Path((course_id, id)): Path<(String, String)>,
+) -> Result<Html<String>, Error> {
+    require_teacher(&client, teacher, &course_id).await?;
+    let work = client.get_course_work(&course_id, &id).await?;
+    let title = work.title.as_deref().unwrap_or("this assignment");
+    Ok(confirm(
+        &state,
+        &csrf,
+        &format!("Delete {title} and all of its student work?"),
+        &format!("/assignment/{course_id}/{id}/delete"),
+        "Delete",
+        &format!("/assignment/{course_id}/{id}"),
+    )?)
+}
+
+pub async fn delete_coursework(
+    UserClient(client): UserClient,
+    TeacherMode(teacher): TeacherMode,
+    csrf: Csrf,
+    Path((course_id, id)): Path<(String, String)>,
+    Form(form): Form<CsrfForm>,
+) -> Result<Redirect, Error> {
+    csrf.verify(&form.csrf)?;
+    require_teacher(&client, teacher, &course_id).await?;
+    client.delete_course_work(&course_id, &id).await?;
+    Ok(Redirect::to(&format!("/class/{course_id}")))
+}
+
+pub async fn new_material(
+    UserClient(client): UserClient,
+    State(state): State<AppState>,
+    TeacherMode(teacher): TeacherMode,
+    Timezone(timezone): Timezone,
+    csrf: Csrf,
+    Path(course_id): Path<String>,
+) -> Result<Html<String>, Error> {
+    require_teacher(&client, teacher, &course_id).await?;
+    let page = FormPage {
+        kind: "material",
+        action: format!("/class/{course_id}/material/new"),
+        cancel: format!("/class/{course_id}"),
+        editing: false,
+        form: ClassworkForm {
+            timezone: timezone.name().to_string(),
+            ..Default::default()
+        },
+    };
+    render_form(&client, &state, &csrf, &course_id, page).await
+}
+
+pub async fn create_material(
+    UserClient(client): UserClient,
+    TeacherMode(teacher): TeacherMode,
+    csrf: Csrf,
+    Path(course_id): Path<String>,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> Result<Redirect, Error> {
+    let form = ClassworkForm::from_fields(fields);
+    csrf.verify(&form.csrf)?;
+    require_teacher(&client, teacher, &course_id).await?;
+    let material = form.parse()?.material();
+    let created = client
+        .create_course_work_material(&course_id, material)
+        .await?;
+    let id = created
+        .id
+        .ok_or(Error::MissingField("courses.courseWorkMaterials.create.id"))?;
+    Ok(Redirect::to(&format!("/class/{course_id}/material/{id}")))
+}
+
+pub async fn edit_material(
+    UserClient(client): UserClient,
+    State(state): State<AppState>,
+    TeacherMode(teacher): TeacherMode,
+    Timezone(timezone): Timezone,
+    csrf: Csrf,
+    Path((course_id, id)): Path<(String, String)>,
+) -> Result<Html<String>, Error> {
+    require_teacher(&client, teacher, &course_id).await?;
+    let material = client.get_course_work_material(&course_id, &id).await?;
+    let page = FormPage {
+        kind: "material",
+        action: format!("/class/{course_id}/material/{id}/edit"),
+        cancel: format!("/class/{course_id}/material/{id}"),
+        editing: true,
+        form: ClassworkForm::from_material(&material, timezone),
+    };
+    render_form(&client, &state, &csrf, &course_id, page).await
+}
+
+pub async fn update_material(
+    UserClient(client): UserClient,
+    TeacherMode(teacher): TeacherMode,
+    csrf: Csrf,
+    Path((course_id, id)): Path<(String, String)>,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> Result<Redirect, Error> {
+    let form = ClassworkForm::from_fields(fields);
+    csrf.verify(&form.csrf)?;
+    require_teacher(&client, teacher, &course_id).await?;
+    let existing = client.get_course_work_material(&course_id, &id).await?;
+    let classwork = form.parse()?;
+    let update_mask =
+        classwork.update_mask("title,description,topicId", existing.state.as_deref())?;
+    client
+        .patch_course_work_material(&course_id, &id, classwork.material(), &update_mask)
+        .await?;
+    Ok(Redirect::to(&format!("/class/{course_id}/material/{id}")))
+}
+
+/// Ask the teacher to confirm deleting a material.
+pub async fn delete_material_page(
+    UserClient(client): UserClient,
+    State(state): State<AppState>,
+    TeacherMode(teacher): TeacherMode,
+    csrf: Csrf,
+    Path((course_id, id)): Path<(String, String)>,
+) -> Result<Html<String>, Error> {
+    require_teacher(&client, teacher, &course_id).await?;
+    let material = client.get_course_work_material(&course_id, &id).await?;
+    let title = material.title.as_deref().unwrap_or("this material");
+    Ok(confirm(
+        &state,
+        &csrf,
+        &format!("Delete {title}?"),
+        &format!("/class/{course_id}/material/{id}/delete"),
+        "Delete",
+        &format!("/class/{course_id}/material/{id}"),
+    )?)
+}
+
+pub async fn delete_material(
+    UserClient(client): UserClient,
+    TeacherMode(teacher): TeacherMode,
+    csrf: Csrf,
+    Path((course_id, id)): Path<(String, String)>,
+    Form(form): Form<CsrfForm>,
+) -> Result<Redirect, Error> {
+    csrf.verify(&form.csrf)?;
+    require_teacher(&client, teacher, &course_id).await?;
+    client.delete_course_work_material(&course_id, &id).await?;
+    Ok(Redirect::to(&format!("/class/{course_id}")))
+}
+
+/// What [`render_form`] needs to know about the form it's showing.
+struct FormPage {
+    /// Either `coursework` or `material`
+    kind: &'static str,
+    action: String,
+    cancel: String,
+    editing: bool,
+    form: ClassworkForm,
+}
+
+/// A student who can be picked as an assignee.
+#[derive(serde::Serialize)]
+struct StudentChoice {
+    id: String,
+    name: String,
+}
+
+async fn render_form(
+    client: &Backend,
+    state: &AppState,
+    csrf: &Csrf,
+    course_id: &str,
+    page: FormPage,
+) -> Result<Html<String>, Error> {
+    let max_pages = state.max_pages;
+    let (class, topics) = try_join!(
+        client.get_course(course_id),
+        paginate(max_pages, |page| async move {
+            client.list_topics(course_id, page.as_deref()).await
+        })
+        .try_collect::<Vec<Topic>>()
+    )?;
+    // users who signed in before css asked for rosters can't list students, so they
+    // only get to assign to the whole class until they sign in again
+    let students: Vec<Student> = paginate(max_pages, |page| async move {
+        client.list_students(course_id, page.as_deref()).await
+    })
+    .try_collect()
+    .await
+    .unwrap_or_default();
+    let mut students: Vec<StudentChoice> = students
+        .into_iter()
+        .filter_map(|student| {
+            let id = student.user_id?;
+            let name = student
+                .profile
+                .and_then(|profile| profile.name?.full_name)
+                .unwrap_or_else(|| id.clone());
+            Some(StudentChoice { id, name })
+        })
+        .collect();
+    students.sort_by(|a, b| a.name.cmp(&b.name));
+    let timezones: Vec<&str> = TZ_VARIANTS.iter().map(|tz| tz.name()).collect();
+    let mut context = tera::Context::new();
+    context.insert("csrf", csrf.token());
+    context.insert("class", &class);
+    context.insert("kind", page.kind);
+    context.insert("action", &page.action);
+    context.insert("cancel", &page.cancel);
+    context.insert("editing", &page.editing);
+    context.insert("form", &page.form);
+    context.insert("topics", &topics);
+    context.insert("students", &students);
+    context.insert("timezones", &timezones);
+    Ok(Html(state.tera.render("classwork_form.jinja", &context)?))
+}
+
+/// The coursework and material form, as submitted or as prefilled for editing.
+///
+/// Forms can repeat `students`, which [`serde_urlencoded`] can't collect into a struct,
+/// so this is filled in from the raw fields by [`Self::from_fields`].
+#[derive(Default, serde::Serialize)]
+pub struct ClassworkForm {
+    #[serde(skip)]
+    csrf: String,
+    title: String,
+    description: String,
+    /// `YYYY-MM-DD`, in `timezone`
+    due_date: String,
+    /// `HH:MM` in `timezone`; blank means the end of the day
+    due_time: String,
+    /// An IANA time zone name; blank means UTC
+    timezone: String,
+    max_points: String,
+    topic: String,
+    /// Only used when creating coursework, since Classroom can't change it after
+    work_type: String,
+    /// Multiple choice options, one per line
+    choices: String,
+    /// `ALL_STUDENTS` or `INDIVIDUAL_STUDENTS`
+    assignee_mode: String,
+    students: Vec<String>,
+    /// `now`, `draft` or `schedule`
+    publish: String,
+    scheduled_date: String,
+    scheduled_time: String,
+}
+
+/// A [`ClassworkForm`] that made sense, ready to send to Classroom.
+struct Classwork {
+    title: String,
+    description: Option<String>,
+    due: Option<DateTime<Utc>>,
+    max_points: Option<f64>,
+    topic_id: Option<String>,
+    state: &'static str,
+    scheduled_time: Option<DateTime<Utc>>,
+    assignee_mode: &'static str,
+    students: Vec<String>,
+}
+
+impl ClassworkForm {
+    fn from_fields(fields: Vec<(String, String)>) -> Self {
+        let mut form = Self::default();
+        for (name, value) in fields {
+            let field = match name.as_str() {
+                "csrf" => &mut form.csrf,
+                "title" => &mut form.title,
+                "description" => &mut form.description,
+                "due_date" => &mut form.due_date,
+                "due_time" => &mut form.due_time,
+                "timezone" => &mut form.timezone,
+                "max_points" => &mut form.max_points,
+                "topic" => &mut form.topic,
+                "work_type" => &mut form.work_type,
+                "choices" => &mut form.choices,
+                "assignee_mode" => &mut form.assignee_mode,
+                "publish" => &mut form.publish,
+                "scheduled_date" => &mut form.scheduled_date,
+                "scheduled_time" => &mut form.scheduled_time,
+                "students" => {
+                    form.students.push(value);
+                    continue;
+                }
+                _ => continue,
+            };
+            *field = value;
+        }
+        form
+    }
+
+    /// Prefill the form from existing coursework, showing times in `timezone`.
+    fn from_course_work(work: &CourseWork, timezone: Tz) -> Self {
+        let (due_date, due_time) = local_date_time(due_date(work), timezone);
+        let (scheduled_date, scheduled_time) = local_date_time(work.scheduled_time, timezone);
+        Self {
+            title: work.title.clone().unwrap_or_default(),
+            description: work.description.clone().unwrap_or_default(),
+            due_date,
+            due_time,
+            timezone: timezone.name().to_string(),
+            max_points: work
+                .max_points
+                .map(|max| max.to_string())
+                .unwrap_or_default(),
+            topic: work.topic_id.clone().unwrap_or_default(),
+            work_type: work.work_type.clone().unwrap_or_default(),
+            assignee_mode: work.assignee_mode.clone().unwrap_or_default(),
+            students: work
+                .individual_students_options
+                .as_ref()
+                .and_then(|options| options.student_ids.clone())
+                .unwrap_or_default(),
+            publish: publish_choice(work.state.as_deref(), work.scheduled_time).to_string(),
+            scheduled_date,
+            scheduled_time,
+            ..Default::default()
+        }
+    }
+
+    fn from_material(material: &CourseWorkMaterial, timezone: Tz) -> Self {
+        let (scheduled_date, scheduled_time) = local_date_time(material.scheduled_time, timezone);
+        Self {
+            title: material.title.clone().unwrap_or_default(),
+            description: material.description.clone().unwrap_or_default(),
+            timezone: timezone.name().to_string(),
+            topic: material.topic_id.clone().unwrap_or_default(),
+            assignee_mode: material.assignee_mode.clone().unwrap_or_default(),
+            students: material
+                .individual_students_options
+                .as_ref()
+                .and_then(|options| options.student_ids.clone())
+                .unwrap_or_default(),
+            publish: publish_choice(material.state.as_deref(), material.scheduled_time).to_string(),
+            scheduled_date,
+            scheduled_time,
+            ..Default::default()
+        }
+    }
+
+    fn parse(&self) -> Result<Classwork, Error> {
+        let title = self.title.trim();
+        if title.is_empty() {
+            return Err(Error::InvalidClasswork("the title is empty"));
+        }
+        let timezone = match self.timezone.trim() {
+            "" => Tz::UTC,
+            name => name
+                .parse()
+                .map_err(|_| Error::InvalidClasswork("that isn't a time zone"))?,
+        };
+        let due = match (self.due_date.trim(), self.due_time.trim()) {
+            ("", "") => None,
+            ("", _) => return Err(Error::InvalidClasswork("a due time needs a due date")),
+            (date, "") => Some(local_to_utc(timezone, date, "23:59")?),
+            (date, time) => Some(local_to_utc(timezone, date, time)?),
+        };
+        let max_points = match self.max_points.trim() {
+            "" => None,
+            points => match points.parse::<f64>() {
+                Ok(points) if points.is_finite() && points > 0.0 => Some(points),
+                _ => {
+                    return Err(Error::InvalidClasswork(
+                        "points have to be a number over zero",
+                    ))
+                }
+            },
+        };
+        let (state, scheduled_time) = match self.publish.as_str() {
+            "" | "now" => ("PUBLISHED", None),
+            "draft" => ("DRAFT", None),
+            "schedule" => {
+                let time = match self.scheduled_time.trim() {
+                    "" => "00:00",
+                    time => time,
+                };
+                let scheduled = local_to_utc(timezone, self.scheduled_date.trim(), time)?;
+                if scheduled <= Utc::now() {
+                    return Err(Error::InvalidClasswork("schedule it for the future"));
+                }
+                // Classroom publishes scheduled drafts by itself
+                ("DRAFT", Some(scheduled))
+            }
+            _ => return Err(Error::InvalidClasswork("pick when to publish it")),
+        };
+        let assignee_mode = match self.assignee_mode.as_str() {
+            "" | "ALL_STUDENTS" => "ALL_STUDENTS",
+            "INDIVIDUAL_STUDENTS" if self.students.is_empty() => {
+                return Err(Error::InvalidClasswork("pick at least one student"));
+            }
+            "INDIVIDUAL_STUDENTS" => "INDIVIDUAL_STUDENTS",
+            _ => return Err(Error::InvalidClasswork("pick who to assign it to")),
+        };
+        Ok(Classwork {
+            title: title.to_string(),
+            description: Some(self.description.trim())
+                .filter(|description| !description.is_empty())
+                .map(str::to_string),
+            due,
+            max_points,
+            topic_id: Some(self.topic.as_str())
+                .filter(|topic| !topic.is_empty())
+                .map(str::to_string),
+            state,
+            scheduled_time,
+            assignee_mode,
+            students: self.students.clone(),
+        })
+    }
+
+    /// The work type to create, and the choices if it's a multiple choice question.
+    fn work_type(&self) -> Result<(&'static str, Option<MultipleChoiceQuestion>), Error> {
+        match self.work_type.as_str() {
+            "" | "ASSIGNMENT" => Ok(("ASSIGNMENT", None)),
+            "SHORT_ANSWER_QUESTION" => Ok(("SHORT_ANSWER_QUESTION", None)),
+            "MULTIPLE_CHOICE_QUESTION" => {
+                let choices: Vec<String> = self
+                    .choices
+                    .lines()
+                    .map(str::trim)
+                    .filter(|choice| !choice.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                if choices.is_empty() {
+                    return Err(Error::InvalidClasswork("add some choices, one per line"));
+                }
+                let question = MultipleChoiceQuestion {
+                    choices: Some(choices),
+                };
+                Ok(("MULTIPLE_CHOICE_QUESTION", Some(question)))
+            }
+            _ => Err(Error::InvalidClasswork("that isn't a kind of coursework")),
+        }
+    }
+}
+
+impl Classwork {
+    fn course_work(&self) -> CourseWork {
+        let (due_date, due_time) = self.due.map(classroom_from_utc).unzip();
+        CourseWork {
+            title: Some(self.title.clone()),
+            description: self.description.clone(),
+            due_date,
+            due_time,
+            max_points: self.max_points,
+            topic_id: self.topic_id.clone(),
+            state: Some(self.state.to_string()),
+            scheduled_time: self.scheduled_time,
+            assignee_mode: Some(self.assignee_mode.to_string()),
+            individual_students_options: self.individual_students_options(),
+            ..Default::default()
+        }
+    }
+
+    fn material(&self) -> CourseWorkMaterial {
+        CourseWorkMaterial {
+            title: Some(self.title.clone()),
+            description: self.description.clone(),
+            topic_id: self.topic_id.clone(),
+            state: Some(self.state.to_string()),
+            scheduled_time: self.scheduled_time,
+            assignee_mode: Some(self.assignee_mode.to_string()),
+            individual_students_options: self.individual_students_options(),
+            ..Default::default()
+        }
+    }
+
+    fn individual_students_options(&self) -> Option<IndividualStudentsOptions> {
+        (self.assignee_mode == "INDIVIDUAL_STUDENTS").then(|| IndividualStudentsOptions {
+            student_ids: Some(self.students.clone()),
+        })
+    }
+
+    /// `fields`, plus the publishing fields if the classwork is still a draft.
+    /// Classroom won't turn published classwork back into a draft.
+    fn update_mask(&self, fields: &str, current_state: Option<&str>) -> Result<String, Error> {
+        if current_state == Some("DRAFT") {
+            Ok(format!("{fields},state,scheduledTime"))
+        } else if self.state == "PUBLISHED" {
+            Ok(fields.to_string())
+        } else {
+            Err(Error::InvalidClasswork("it's already published"))
+        }
+    }
+
+    /// The request to assign `existing` coursework to the form's students, if they changed.
+    fn assignee_changes(&self, existing: &CourseWork) -> Option<ModifyCourseWorkAssigneesRequest> {
+        let current_mode = existing.assignee_mode.as_deref().unwrap_or("ALL_STUDENTS");
+        let current: &[String] = existing
+            .individual_students_options
+            .as_ref()
+            .and_then(|options| options.student_ids.as_deref())
+            .unwrap_or_default();
+        let add: Vec<String> = self
+            .students
+            .iter()
+            .filter(|student| !current.contains(student))
+            .cloned()
+            .collect();
+        let remove: Vec<String> = current
+            .iter()
+            .filter(|student| !self.students.contains(student))
+            .cloned()
+            .collect();
+        let options = match self.assignee_mode {
+            "ALL_STUDENTS" if current_mode == "ALL_STUDENTS" => return None,
+            "ALL_STUDENTS" => None,
+            _ if current_mode == self.assignee_mode && add.is_empty() && remove.is_empty() => {
+                return None;
+            }
+            _ => Some(ModifyIndividualStudentsOptions {
+                add_student_ids: (!add.is_empty()).then_some(add),
+                remove_student_ids: (!remove.is_empty()).then_some(remove),
+            }),
+        };
+        Some(ModifyCourseWorkAssigneesRequest {
+            assignee_mode: Some(self.assignee_mode.to_string()),
+            modify_individual_students_options: options,
+        })
+    }
+}
+
+/// Which of the form's publishing options describes classwork in `state`.
+fn publish_choice(state: Option<&str>, scheduled_time: Option<DateTime<Utc>>) -> &'static str {
+    match (state, scheduled_time) {
+        (Some("DRAFT"), Some(_)) => "schedule",
+        (Some("DRAFT"), None) => "draft",
+        _ => "now",
+    }
+}
+
+/// Turn a form's `YYYY-MM-DD` date and `HH:MM` time in `timezone` into UTC.
+fn local_to_utc(timezone: Tz, date: &str, time: &str) -> Result<DateTime<Utc>, Error> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| Error::InvalidClasswork("dates have to look like 2023-09-01"))?;
+    let time = NaiveTime::parse_from_str(time, "%H:%M")
+        .map_err(|_| Error::InvalidClasswork("times have to look like 23:59"))?;
+    // when clocks go back, the earlier of the two times is the one people usually mean
+    let local = timezone
+        .from_local_datetime(&date.and_time(time))
+        .earliest()
+        .ok_or(Error::InvalidClasswork(
+            "that time gets skipped when the clocks change",
+        ))?;
+    Ok(local.with_timezone(&Utc))
+}
+
+/// The form's date and time fields for `time`, in `timezone`.
+fn local_date_time(time: Option<DateTime<Utc>>, timezone: Tz) -> (String, String) {
+    time.map(|time| {
+        let local = time.with_timezone(&timezone);
+        (
+            local.format("%Y-%m-%d").to_string(),
+            local.format("%H:%M").to_string(),
+        )
+    })
+    .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::http::StatusCode;
+
+    use super::*;
+    use crate::backend::memory::MemoryBackend;
+
+    /// `art`, taught by the current user, with a "Mural" assignment, a "Palettes" material
+    /// and two students. css made the mural and palettes, so it can change them.
+    fn art_class() -> MemoryBackend {
+        let mut backend = MemoryBackend::fixture();
+        backend.add_course("art", "Art");
+        backend.teaching.push("art".to_string());
+        backend.add_work("art", "mural", "Mural", Some((2023, 9, 1, 12, 0)));
+        backend.made_by_css("art", "mural");
+        backend.add_material("art", "palettes", "Palettes", None);
+        backend.material_made_by_css("art", "palettes");
+        backend.add_topic("art", "color", "Color");
+        for (id, name) in [("wanda", "Wanda Li"), ("arnold", "Arnold Perlstein")] {
+            backend.add_user(id, name);
+            backend.add_student("art", id);
+        }
+        backend
+    }
+
+    fn form(fields: &[(&str, &str)]) -> Form<Vec<(String, String)>> {
+        let csrf = ("csrf".to_string(), Csrf::for_tests().token().to_string());
+        let fields = fields
+            .iter()
+            .map(|(name, value)| ((*name).to_string(), (*value).to_string()));
+        Form(std::iter::once(csrf).chain(fields).collect())
+    }
+
+    fn path(course_id: &str, id: &str) -> Path<(String, String)> {
+        Path((course_id.to_string(), id.to_string()))
+    }
+
+    async fn create(client: &Backend, fields: &[(&str, &str)]) -> Result<CourseWork, Error> {
+        let _ = create_coursework(
+            UserClient(client.clone()),
+            TeacherMode(true),
+            Csrf::for_tests(),
+            Path("art".to_string()),
+            form(fields),
+        )
+        .await?;
+        let title = fields.iter().find(|(name, _)| *name == "title").unwrap().1;
+        let work = client.list_course_work("art", None, None).await.unwrap();
+        let listed = work
+            .course_work
+            .unwrap()
+            .into_iter()
+            .find(|work| work.title.as_deref() == Some(title.trim()))
+            .unwrap();
+        Ok(client
+            .get_course_work("art", listed.id.as_deref().unwrap())
+            .await
+            .unwrap())
+    }
+
+    async fn update(client: &Backend, id: &str, fields: &[(&str, &str)]) -> Result<(), Error> {
+        let _ = update_coursework(
+            UserClient(client.clone()),
+            TeacherMode(true),
+            Csrf::for_tests(),
+            path("art", id),
+            form(fields),
+        )
+        .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn creates_coursework_due_in_local_time() {
+        let client: Backend = Arc::new(art_class());
+        let work = create(
+            &client,
+            &[
+                ("title", " Still life "),
+                ("description", "Draw a bowl of fruit"),
+                ("due_date", "2023-09-15"),
+                ("due_time", "17:30"),
+                ("timezone", "America/New_York"),
+                ("max_points", "50"),
+                ("topic", "color"),
+            ],
+        )
+        .await
+        .unwrap();
+        assert_eq!(work.title.as_deref(), Some("Still life"));
+        assert_eq!(work.work_type.as_deref(), Some("ASSIGNMENT"));
+        assert_eq!(work.topic_id.as_deref(), Some("color"));
+        assert_eq!(work.max_points, Some(50.0));
+        // 17:30 EDT
+        let due = due_date(&work).unwrap();
+        assert_eq!(due.to_rfc3339(), "2023-09-15T21:30:00+00:00");
+        // a due date without a time is due at the end of the day
+        let work = create(
+            &client,
+            &[
+                ("title", "Sketch"),
+                ("due_date", "2023-12-01"),
+                ("timezone", "Europe/Berlin"),
+                ("work_type", "MULTIPLE_CHOICE_QUESTION"),
+                ("choices", "Red\r\n\r\nBlue\r\n"),
+            ],
+        )
+        .await
+        .unwrap();
+        let due = due_date(&work).unwrap();
+        assert_eq!(due.to_rfc3339(), "2023-12-01T22:59:00+00:00");
+        let choices = work.multiple_choice_question.unwrap().choices.unwrap();
+        assert_eq!(choices, ["Red", "Blue"]);
+    }
+
+    #[tokio::test]
+    async fn edits_coursework_and_assignees() {
+        let client: Backend = Arc::new(art_class());
+        let Html(html) = edit_coursework(
+            UserClient(client.clone()),
+            State(AppState::for_tests()),
+            TeacherMode(true),
+            Timezone(chrono_tz::America::New_York),
+            Csrf::for_tests(),
+            path("art", "mural"),
+        )
+        .await
+        .unwrap();
+        // due at noon UTC, shown in the teacher's time zone
+        assert!(html.contains(r#"value="2023-09-01""#));
+        assert!(html.contains(r#"value="08:00""#));
+        assert!(html.contains("Arnold Perlstein"));
+        update(
+            &client,
+            "mural",
+            &[
+                ("title", "Big mural"),
+                ("max_points", "100"),
+                ("assignee_mode", "INDIVIDUAL_STUDENTS"),
+                ("students", "wanda"),
+                ("students", "arnold"),
+            ],
+        )
+        .await
+        .unwrap();
+        let work = client.get_course_work("art", "mural").await.unwrap();
+        assert_eq!(work.title.as_deref(), Some("Big mural"));
+        assert_eq!(work.max_points, Some(100.0));
+        assert!(work.due_date.is_none(), "blank due date wasn't cleared");
+        assert_eq!(work.assignee_mode.as_deref(), Some("INDIVIDUAL_STUDENTS"));
+        let students = work.individual_students_options.unwrap().student_ids;
+        assert_eq!(students.unwrap(), ["wanda", "arnold"]);
+        update(
+            &client,
+            "mural",
+            &[
+                ("title", "Big mural"),
+                ("assignee_mode", "INDIVIDUAL_STUDENTS"),
+                ("students", "arnold"),
+            ],
+        )
+        .await
+        .unwrap();
+        let work = client.get_course_work("art", "mural").await.unwrap();
+        let students = work.individual_students_options.unwrap().student_ids;
+        assert_eq!(students.unwrap(), ["arnold"]);
+    }
+
+    #[tokio::test]
+    async fn schedules_drafts() {
+        let client: Backend = Arc::new(art_class());
+        let _ = create_coursework(
+            UserClient(client.clone()),
+            TeacherMode(true),
+            Csrf::for_tests(),
+            Path("art".to_string()),
+            form(&[
+                ("title", "Portrait"),
+                ("timezone", "Asia/Tokyo"),
+                ("publish", "schedule"),
+                ("scheduled_date", "2099-01-01"),
+                ("scheduled_time", "08:00"),
+            ]),
+        )
+        .await
+        .unwrap();
+        // the memory backend numbers created classwork from a million
+        let work = client.get_course_work("art", "1000000").await.unwrap();
+        assert_eq!(work.state.as_deref(), Some("DRAFT"));
+        let scheduled = work.scheduled_time.unwrap();
+        assert_eq!(scheduled.to_rfc3339(), "2098-12-31T23:00:00+00:00");
+        let listed = client.list_course_work("art", None, None).await.unwrap();
+        assert_eq!(listed.course_work.unwrap().len(), 1, "drafts are listed");
+        update(
+            &client,
+            "1000000",
+            &[("title", "Portrait"), ("publish", "now")],
+        )
+        .await
+        .unwrap();
+        let work = client.get_course_work("art", "1000000").await.unwrap();
+        assert_eq!(work.state.as_deref(), Some("PUBLISHED"));
+        assert!(work.scheduled_time.is_none());
+        let listed = client.list_course_work("art", None, None).await.unwrap();
+        assert_eq!(listed.course_work.unwrap().len(), 2);
+        let err = update(
+            &client,
+            "1000000",
+            &[("title", "Portrait"), ("publish", "draft")],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn manages_materials() {
+        let client: Backend = Arc::new(art_class());
+        let _ = create_material(
+            UserClient(client.clone()),
+            TeacherMode(true),
+            Csrf::for_tests(),
+            Path("art".to_string()),
+            form(&[("title", "Color wheel"), ("topic", "color")]),
+        )
+        .await
+        .unwrap();
+        let material = client
+            .get_course_work_material("art", "1000000")
+            .await
+            .unwrap();
+        assert_eq!(material.title.as_deref(), Some("Color wheel"));
+        assert_eq!(material.topic_id.as_deref(), Some("color"));
+        let _ = update_material(
+            UserClient(client.clone()),
+            TeacherMode(true),
+            Csrf::for_tests(),
+            path("art", "palettes"),
+            form(&[("title", "Palettes"), ("description", "Bring one")]),
+        )
+        .await
+        .unwrap();
+        let material = client
+            .get_course_work_material("art", "palettes")
+            .await
+            .unwrap();
+        assert_eq!(material.description.as_deref(), Some("Bring one"));
+        let _ = delete_material(
+            UserClient(client.clone()),
+            TeacherMode(true),
+            Csrf::for_tests(),
+            path("art", "palettes"),
+            Form(CsrfForm {
+                csrf: Csrf::for_tests().token().to_string(),
+            }),
+        )
+        .await
+        .unwrap();
+        let materials = client
+            .list_course_work_materials("art", None)
+            .await
+            .unwrap();
+        assert_eq!(materials.course_work_material.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn deletes_coursework() {
+        let client: Backend = Arc::new(art_class());
+        let Html(html) = delete_coursework_page(
+            UserClient(client.clone()),
+            State(AppState::for_tests()),
+            TeacherMode(true),
+            Csrf::for_tests(),
+            path("art", "mural"),
+        )
+        .await
+        .unwrap();
+        assert!(html.contains("Delete Mural"));
+        let _ = delete_coursework(
+            UserClient(client.clone()),
+            TeacherMode(true),
+            Csrf::for_tests(),
+            path("art", "mural"),
+            Form(CsrfForm {
+                csrf: Csrf::for_tests().token().to_string(),
+            }),
+        )
+        .await
+        .unwrap();
+        let err = client.get_course_work("art", "mural").await.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn only_changes_classwork_css_made() {
+        let mut backend = art_class();
+        let work = backend.work_mut("art", "mural").unwrap();
+        work.associated_with_developer = None;
+        work.alternate_link = Some("https://classroom.google.com/c/art/a/mural".to_string());
+        backend.css_materials.get_mut().unwrap().clear();
+        let client: Backend = Arc::new(backend);
+        let Html(html) = crate::routes::assignment(
+            UserClient(client.clone()),
+            State(AppState::for_tests()),
+            TeacherMode(true),
+            Timezone(chrono_tz::UTC),
+            Csrf::for_tests(),
+            path("art", "mural"),
+        )
+        .await
+        .unwrap();
+        assert!(!html.contains("/assignment/art/mural/edit"));
+        assert!(!html.contains("/assignment/art/mural/delete"));
+        assert!(html.contains("edit it in Classroom"));
+        let err = update(&client, "mural", &[("title", "Big mural")])
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+        let err = delete_coursework(
+            UserClient(client.clone()),
+            TeacherMode(true),
+            Csrf::for_tests(),
+            path("art", "mural"),
+            Form(CsrfForm {
+                csrf: Csrf::for_tests().token().to_string(),
+            }),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+        let work = client.get_course_work("art", "mural").await.unwrap();
+        assert_eq!(work.title.as_deref(), Some("Mural"));
+        let err = update_material(
+            UserClient(client.clone()),
+            TeacherMode(true),
+            Csrf::for_tests(),
+            path("art", "palettes"),
+            form(&[("title", "Paints")]),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+        let err = delete_material(
+            UserClient(client.clone()),
+            TeacherMode(true),
+            Csrf::for_tests(),
+            path("art", "palettes"),
+            Form(CsrfForm {
+                csrf: Csrf::for_tests().token().to_string(),
+            }),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+        let material = client
+            .get_course_work_material("art", "palettes")
+            .await
+            .unwrap();
+        assert_eq!(material.title.as_deref(), Some("Palettes"));
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_forms() {
+        let client: Backend = Arc::new(art_class());
+        let forms: [&[(&str, &str)]; 8] = [
+            &[("title", " ")],
+            &[("title", "Sketch"), ("timezone", "Mars/Olympus_Mons")],
+            &[("title", "Sketch"), ("due_time", "09:00")],
+            &[("title", "Sketch"), ("due_date", "09/01/2023")],
+            // clocks skip from 02:00 to 03:00
+            &[
+                ("title", "Sketch"),
+                ("due_date", "2023-03-12"),
+                ("due_time", "02:30"),
+                ("timezone", "America/New_York"),
+            ],
+            &[("title", "Sketch"), ("max_points", "-5")],
+            &[
+                ("title", "Sketch"),
+                ("assignee_mode", "INDIVIDUAL_STUDENTS"),
+            ],
+            &[
+                ("title", "Sketch"),
+                ("publish", "schedule"),
+                ("scheduled_date", "2020-01-01"),
+            ],
+        ];
+        for fields in forms {
+            let err = create(&client, fields).await.unwrap_err();
+            assert_eq!(err.status_code(), StatusCode::BAD_REQUEST, "{fields:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn only_teachers_change_classwork() {
+        let client: Backend = Arc::new(art_class());
+        for (course_id, teacher) in [("art", false), ("math", true)] {
+            let err = create_coursework(
+                UserClient(client.clone()),
+                TeacherMode(teacher),
+                Csrf::for_tests(),
+                Path(course_id.to_string()),
+                form(&[("title", "Sketch")]),
+            )
+            .await
+            .unwrap_err();
+            assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+        }
+    }
+}
@@ -1,43 +1,263 @@
 use axum::{
-    extract::{Path, Query, State},
+    extract::{Path, State},
     response::Html,
 };
+use classroom::{
+    api::{Course, CourseWork, CourseWorkMaterial, Topic},
+    chrono::{DateTime, Utc},
+};
+use futures_util::TryStreamExt;
 use tokio::try_join;
 
-use crate::{auth::UserClient, AppState, Error};
+use crate::{
+    auth::{TeacherMode, UserClient},
+    backend::Backend,
+    paginate::paginate,
+    timezone::Timezone,
+    AppState, Error,
+};
 
 pub async fn classes(
     UserClient(client): UserClient,
     State(state): State<AppState>,
 ) -> Result<Html<String>, Error> {
     let mut context = tera::Context::new();
-    let classes = client.courses().list().doit().await?;
-    context.insert("classes", &classes.1.courses);
+    let classes = list_classes(&client, state.max_pages).await?;
+    context.insert("classes", &classes);
     Ok(Html(state.tera.render("classes.jinja", &context)?))
 }
 
 pub async fn class(
     UserClient(client): UserClient,
     State(state): State<AppState>,
+    TeacherMode(teacher): TeacherMode,
+    Timezone(timezone): Timezone,
     Path(id): Path<String>,
-    Query(pages): Query<super::PaginationQuery>,
 ) -> Result<Html<String>, Error> {
     let mut context = tera::Context::new();
-    let req_general = client.courses().get(&id).param("fields", "id,name");
-    let mut req_work = client
-        .courses()
-        .course_work_list(&id)
-        .page_size(10)
-        .param("fields", "nextPageToken,courseWork(id,title)");
-    if let Some(page) = pages.page {
-        req_work = req_work.page_token(&page);
-        context.insert("is_first_page", &false);
-    } else {
-        context.insert("is_first_page", &true);
-    }
-    let (general, work) = try_join!(req_general.doit(), req_work.doit())?;
-    context.insert("class", &general.1);
-    context.insert("coursework", &work.1);
-    context.insert("pagination_token", &work.1.next_page_token);
+    context.insert("teacher", &teacher);
+    context.insert("timezone", timezone.name());
+    let class = get_class(&client, &id, state.max_pages).await?;
+    context.insert("class", &class.class);
+    context.insert("topics", &class.topics);
+    context.insert("latest_posts", &class.latest_posts);
     Ok(Html(state.tera.render("class.jinja", &context)?))
 }
+
+/// A class's classwork, grouped by topic like Classroom's Classwork tab, and its latest
+/// few announcements.
+#[derive(serde::Serialize)]
+pub struct ClassPage {
+    class: Course,
+    topics: Vec<TopicSection>,
+    latest_posts: Vec<super::Post>,
+}
+
+/// The classwork under one topic, newest first. Classwork without a topic comes first,
+/// in a section with no `topic`.
+#[derive(serde::Serialize)]
+pub struct TopicSection {
+    topic: Option<Topic>,
+    items: Vec<ClassworkItem>,
+}
+
+/// A piece of coursework or a course work material.
+#[derive(serde::Serialize)]
+pub struct ClassworkItem {
+    /// Either `coursework` or `material`
+    kind: &'static str,
+    id: Option<String>,
+    title: Option<String>,
+    description: Option<String>,
+    created: Option<DateTime<Utc>>,
+    /// When coursework is due; materials are never due
+    due: Option<DateTime<Utc>>,
+    #[serde(skip)]
+    topic_id: Option<String>,
+}
+
+impl ClassworkItem {
+    fn coursework(work: CourseWork) -> Self {
+        Self {
+            kind: "coursework",
+            due: super::due_date(&work),
+            id: work.id,
+            title: work.title,
+            description: work.description,
+            created: work.creation_time,
+            topic_id: work.topic_id,
+        }
+    }
+
+    fn material(material: CourseWorkMaterial) -> Self {
+        Self {
+            kind: "material",
+            id: material.id,
+            title: material.title,
+            description: material.description,
+            created: material.creation_time,
+            due: None,
+            topic_id: material.topic_id,
+        }
+    }
+}
+
+pub async fn list_classes(client: &Backend, max_pages: usize) -> Result<Vec<Course>, Error> {
+    paginate(max_pages, |page| async move {
+        client.list_courses(page.as_deref()).await
+    })
+    .try_collect()
+    .await
+}
+
+pub async fn get_class(client: &Backend, id: &str, max_pages: usize) -> Result<ClassPage, Error> {
+    let (class, work, materials, topics, latest_posts) = try_join!(
+        client.get_course(id),
+        paginate(max_pages, |page| async move {
+            client.list_course_work(id, page.as_deref(), None).await
+        })
+        .try_collect::<Vec<_>>(),
+        paginate(max_pages, |page| async move {
+            client.list_course_work_materials(id, page.as_deref()).await
+        })
+        .try_collect::<Vec<_>>(),
+        paginate(max_pages, |page| async move {
+            client.list_topics(id, page.as_deref()).await
+        })
+        .try_collect::<Vec<_>>(),
+        super::latest_posts(client, id, 3)
+    )?;
+    let mut items: Vec<ClassworkItem> = work
+        .into_iter()
+        .map(ClassworkItem::coursework)
+        .chain(materials.into_iter().map(ClassworkItem::material))
+        .collect();
+    items.sort_by_key(|item| std::cmp::Reverse(item.created));
+    let mut topics: Vec<TopicSection> = std::iter::once(None)
+        .chain(topics.into_iter().map(Some))
+        .map(|topic| TopicSection {
+            topic,
+            items: Vec::new(),
+        })
+        .collect();
+    for item in items {
+        // anything under a topic we didn't fetch goes with the classwork that has no topic
+        let section = item
+            .topic_id
+            .as_deref()
+            .and_then(|topic_id| {
+                topics.iter().position(|section| {
+                    section
+                        .topic
+                        .as_ref()
+                        .is_some_and(|topic| topic.topic_id.as_deref() == Some(topic_id))
+                })
+            })
+            .unwrap_or(0);
+        topics[section].items.push(item);
+    }
+    topics.retain(|section| section.topic.is_some() || !section.items.is_empty());
+    Ok(ClassPage {
+        class,
+        topics,
+        latest_posts,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::http::StatusCode;
+    use chrono_tz::Tz;
+
+    use super::*;
+    use crate::backend::memory::MemoryBackend;
+
+    fn titles(section: &TopicSection) -> Vec<&str> {
+        section
+            .items
+            .iter()
+            .map(|item| item.title.as_deref().unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn lists_classes() {
+        let backend = Arc::new(MemoryBackend::fixture());
+        let Html(html) = classes(UserClient(backend), State(AppState::for_tests()))
+            .await
+            .unwrap();
+        assert!(html.contains(r#"href="/todo/math""#));
+        assert!(html.contains("History"));
+    }
+
+    #[tokio::test]
+    async fn renders_class() {
+        let backend = Arc::new(MemoryBackend::fixture());
+        let Html(html) = class(
+            UserClient(backend),
+            State(AppState::for_tests()),
+            TeacherMode(false),
+            Timezone(Tz::UTC),
+            Path("math".to_string()),
+        )
+        .await
+        .unwrap();
+        assert!(html.contains("Math"));
+        assert!(!html.contains("New assignment"));
+        assert!(html.contains("Unit 1"));
+        assert!(html.contains(r#"href="/assignment/math/hw1""#));
+        assert!(html.contains(r#"href="/class/math/material/notes""#));
+        assert!(html.contains("Quiz on Friday"));
+        assert!(html.contains(r#"href="/class/math/announcements""#));
+    }
+
+    #[tokio::test]
+    async fn groups_classwork_by_topic() {
+        let backend: Backend = Arc::new(MemoryBackend::fixture());
+        let page = get_class(&backend, "math", 20).await.unwrap();
+        assert_eq!(page.topics.len(), 3);
+        assert!(page.topics[0].topic.is_none());
+        assert_eq!(titles(&page.topics[0]), ["Syllabus", "Project"]);
+        assert_eq!(
+            page.topics[1].topic.as_ref().unwrap().name.as_deref(),
+            Some("Unit 1")
+        );
+        assert_eq!(
+            titles(&page.topics[1]),
+            ["Lecture notes", "Homework 2", "Homework 1"]
+        );
+        assert!(page.topics[2].items.is_empty());
+        assert_eq!(page.latest_posts.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn follows_every_page_of_classwork() {
+        let mut backend = MemoryBackend::fixture();
+        for i in 0..12 {
+            backend.add_work("math", &format!("extra{i}"), &format!("Extra {i}"), None);
+        }
+        backend.page_size = 5;
+        let backend: Backend = Arc::new(backend);
+        let page = get_class(&backend, "math", 20).await.unwrap();
+        let items: usize = page.topics.iter().map(|section| section.items.len()).sum();
+        assert_eq!(items, 17);
+        assert_eq!(titles(&page.topics[0])[0], "Extra 11");
+    }
+
+    #[tokio::test]
+    async fn unknown_class() {
+        let backend = Arc::new(MemoryBackend::fixture());
+        let err = class(
+            UserClient(backend),
+            State(AppState::for_tests()),
+            TeacherMode(false),
+            Timezone(Tz::UTC),
+            Path("gym".to_string()),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Redirect, Response},
+    Form,
+};
+use classroom::chrono::Utc;
+use oauth2::{
+    basic::BasicErrorResponseType, reqwest::async_http_client, CsrfToken, RefreshToken,
+    RequestTokenError, TokenResponse,
+};
+use sha2::{Digest, Sha256};
+use tower_cookies::{cookie::CookieJar, Cookie, Cookies, Key};
+
+use crate::{auth::UserId, csrf::Csrf, AppState, Error};
+
+/// A secret token that lets calendar apps fetch a user's to-do feed without cookies.
+/// Feeds are stored under [`feed_id`] rather than the token itself, so reading the store
+/// isn't enough to fetch them.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct FeedToken {
+    user: String,
+    /// The user's refresh token, encrypted with [`AppState::key`]
+    refresh: String,
+    /// Unix timestamp of when the token was created
+    created: i64,
+}
+
+/// A feed as listed on the settings page. The token itself is only shown once, when the
+/// feed is created.
+#[derive(serde::Serialize)]
+pub struct FeedListing {
+    id: String,
+    created: i64,
+}
+
+/// What a feed with `token` is stored under: the hex SHA-256 of the token.
+#[must_use]
+pub fn feed_id(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Store feeds saved before they were stored under [`feed_id`] under it instead. Returns
+/// whether there were any.
+pub fn hash_feed_tokens(feeds: &mut HashMap<String, FeedToken>) -> bool {
+    let is_id = |key: &str| key.len() == 64 && key.bytes().all(|b| b.is_ascii_hexdigit());
+    let legacy: Vec<String> = feeds.keys().filter(|key| !is_id(key)).cloned().collect();
+    for token in &legacy {
+        if let Some(feed) = feeds.remove(token) {
+            feeds.insert(feed_id(token), feed);
+        }
+    }
+    !legacy.is_empty()
+}
+
+pub async fn feed(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<Response, Error> {
+    let id = feed_id(token.strip_suffix(".ics").unwrap_or(&token));
+    let (user, sealed) = state
+        .feeds
+        .read()
+        .await
+        .get(&id)
+        .map(|feed| (feed.user.clone(), feed.refresh.clone()))
+        .ok_or(Error::UnknownFeed)?;
+    let refresh = unseal(&state.key, &sealed).ok_or(Error::UnknownFeed)?;
+    let access = match state
+        .oauth
+        .exchange_refresh_token(&RefreshToken::new(refresh))
+        .request_async(async_http_client)
+        .await
+    {
+        Ok(v) => v,
+        Err(RequestTokenError::ServerResponse(resp))
+            if *resp.error() == BasicErrorResponseType::InvalidGrant =>
+        {
+            // The user revoked css's access, so this feed can never work again
+            state.feeds.update(|feeds| feeds.remove(&id)).await?;
+            return Err(Error::UnknownFeed);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if let Some(refresh) = access.refresh_token() {
+        let sealed = seal(&state.key, refresh.secret());
+        state
+            .feeds
+            .update(|feeds| {
+                if let Some(feed) = feeds.get_mut(&id) {
+                    feed.refresh = sealed;
+                }
+            })
+            .await?;
+    }
+    let client = state.cached_backend(access.access_token().secret().clone(), &user, false);
+    let policy = state.completion_policy(&user).await;
+    let todos = super::all_todos(&client, policy, state.max_pages).await?;
+    let list = super::mark_todos(&state, &user, todos, None, Utc::now()).await?;
+    Ok(super::todo_calendar(&list.todos, "css to-do", &state.root_url).into_response())
+}
+
+pub async fn create_feed(
+    State(state): State<AppState>,
+    UserId(user): UserId,
+    csrf: Csrf,
+    cookies: Cookies,
+    Form(form): Form<crate::csrf::CsrfForm>,
+) -> Result<Redirect, Error> {
+    csrf.verify(&form.csrf)?;
+    let refresh = cookies
+        .private(&state.key)
+        .get("refresh")
+        .ok_or(Error::NoRefreshToken)?;
+    let feed = FeedToken {
+        user,
+        refresh: seal(&state.key, refresh.value()),
+        created: Utc::now().timestamp(),
+    };
+    let token = CsrfToken::new_random_len(32).secret().clone();
+    state
+        .feeds
+        .update(|feeds| feeds.insert(feed_id(&token), feed))
+        .await?;
+    // css can't show the token again once this request is over, so the settings page
+    // shows it from this cookie once and then removes it
+    let mut cookie = Cookie::new("new_feed", token);
+    cookie.set_path("/settings");
+    cookies.private(&state.key).add(cookie);
+    Ok(Redirect::to("/settings"))
+}
+
+pub async fn revoke_feed(
+    State(state): State<AppState>,
+    UserId(user): UserId,
+    csrf: Csrf,
+    Form(form): Form<RevokeFeedForm>,
+) -> Result<Redirect, Error> {
+    csrf.verify(&form.csrf)?;
+    state
+        .feeds
+        .update(|feeds| {
+            if feeds.get(&form.id).is_some_and(|feed| feed.user == user) {
+                feeds.remove(&form.id);
+            }
+        })
+        .await?;
+    Ok(Redirect::to("/settings"))
+}
+
+/// Every feed belonging to `user`, oldest first.
+pub async fn list_feeds(state: &AppState, user: &str) -> Vec<FeedListing> {
+    let mut feeds: Vec<FeedListing> = state
+        .feeds
+        .read()
+        .await
+        .iter()
+        .filter(|(_, feed)| feed.user == user)
+        .map(|(id, feed)| FeedListing {
+            id: id.clone(),
+            created: feed.created,
+        })
+        .collect();
+    feeds.sort_by_key(|feed| feed.created);
+    feeds
+}
+
+#[derive(serde::Deserialize)]
+pub struct RevokeFeedForm {
+    csrf: String,
+    /// The feed's [`feed_id`]
+    id: String,
+}
+
+/// The token of the feed the user just created, if they haven't seen it yet. It's only
+/// returned once.
+pub fn take_new_feed(state: &AppState, cookies: &Cookies) -> Option<String> {
+    let cookies = cookies.private(&state.key);
+    let cookie = cookies.get("new_feed")?;
+    let mut removal = Cookie::named("new_feed");
+    removal.set_path("/settings");
+    cookies.remove(removal);
+    Some(cookie.value().to_string())
+}
+
+/// Encrypt a refresh token the same way private cookies are encrypted.
+fn seal(key: &Key, refresh: &str) -> String {
+    let mut jar = CookieJar::new();
+    jar.private_mut(key)
+        .add(Cookie::new("refresh", refresh.to_string()));
+    jar.get("refresh")
+        .map(|cookie| cookie.value().to_string())
+        .unwrap_or_default()
+}
+
+fn unseal(key: &Key, sealed: &str) -> Option<String> {
+    CookieJar::new()
+        .private(key)
+        .decrypt(Cookie::new("refresh", sealed.to_string()))
+        .map(|cookie| cookie.value().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sealed_refresh_tokens_round_trip() {
+        let key = Key::from(&[7; 64]);
+        let sealed = seal(&key, "1//refresh-token");
+        assert!(
+            !sealed.contains("refresh-token"),
+            "the token isn't encrypted"
+        );
+        assert_eq!(unseal(&key, &sealed).as_deref(), Some("1//refresh-token"));
+    }
+
+    #[test]
+    fn unsealing_needs_the_same_key_and_an_intact_token() {
+        let key = Key::from(&[7; 64]);
+        let sealed = seal(&key, "1//refresh-token");
+        assert_eq!(unseal(&Key::from(&[8; 64]), &sealed), None);
+        let mut tampered = sealed.into_bytes();
+        let last = tampered.len() - 2;
+        tampered[last] = if tampered[last] == b'A' { b'B' } else { b'A' };
+        assert_eq!(unseal(&key, &String::from_utf8(tampered).unwrap()), None);
+    }
+
+    #[test]
+    fn hashes_tokens_saved_before_feeds_were_stored_by_id() {
+        let feed = || FeedToken {
+            user: "student".to_string(),
+            refresh: String::new(),
+            created: 0,
+        };
+        let token = "Gp5Yh3Rk0d5oGmZ1Jq0tqfYq3yDqKQk8Xl6bJg1VZ2E";
+        let mut feeds = HashMap::from([(token.to_string(), feed())]);
+        assert!(hash_feed_tokens(&mut feeds));
+        assert!(feeds.contains_key(&feed_id(token)));
+        assert!(!feeds.contains_key(token));
+        // feeds already stored by id are left alone
+        assert!(!hash_feed_tokens(&mut feeds));
+        assert_eq!(feeds.len(), 1);
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, State},
+    response::Html,
+};
+use classroom::api::{Course, CourseWork, StudentSubmission};
+use futures_util::{future::try_join_all, TryStreamExt};
+use tokio::try_join;
+
+use crate::{auth::UserClient, backend::Backend, paginate::paginate, AppState, Error};
+
+pub async fn grades(
+    UserClient(client): UserClient,
+    State(state): State<AppState>,
+) -> Result<Html<String>, Error> {
+    let mut context = tera::Context::new();
+    let classes = all_grades(&client, state.max_pages).await?;
+    context.insert("classes", &classes);
+    Ok(Html(state.tera.render("grades.jinja", &context)?))
+}
+
+pub async fn grades_for_class(
+    UserClient(client): UserClient,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Html<String>, Error> {
+    let mut context = tera::Context::new();
+    let grades = class_grades(&client, &id, state.max_pages).await?;
+    context.insert("classes", &[grades]);
+    Ok(Html(state.tera.render("grades.jinja", &context)?))
+}
+
+/// The user's returned grades in one class.
+#[derive(serde::Serialize)]
+pub struct ClassGrades {
+    class: Course,
+    /// In the order Classroom lists the coursework, newest first
+    grades: Vec<Grade>,
+    /// Points earned on every grade that's out of some number of points
+    points: f64,
+    max_points: f64,
+    /// `points` as a percentage of `max_points`
+    percent: Option<f64>,
+    /// The mean of every grade's percentage
+    average: Option<f64>,
+    categories: Vec<CategoryGrades>,
+    /// The overall grade the way the class's gradebook calculates it, if the teacher
+    /// shows it to students
+    overall: Option<f64>,
+}
+
+/// A returned, graded submission.
+#[derive(serde::Serialize)]
+pub struct Grade {
+    work_id: Option<String>,
+    title: Option<String>,
+    category: Option<String>,
+    points: f64,
+    /// `None` for ungraded-by-points coursework
+    max_points: Option<f64>,
+    percent: Option<f64>,
+}
+
+/// The grades in one of a class's grade categories.
+#[derive(serde::Serialize)]
+pub struct CategoryGrades {
+    name: String,
+    /// How much of the overall grade this category is worth, as a percentage, if the class
+    /// uses weighted categories
+    weight: Option<f64>,
+    points: f64,
+    max_points: f64,
+    percent: Option<f64>,
+}
+
+/// Grades in every one of the user's classes, in the order Classroom lists the classes.
+pub async fn all_grades(client: &Backend, max_pages: usize) -> Result<Vec<ClassGrades>, Error> {
+    let courses = super::list_classes(client, max_pages).await?;
+    try_join_all(
+        courses
+            .into_iter()
+            .map(|course| get_grades(client, course, max_pages)),
+    )
+    .await
+}
+
+pub async fn class_grades(
+    client: &Backend,
+    course_id: &str,
+    max_pages: usize,
+) -> Result<ClassGrades, Error> {
+    let course = client.get_course(course_id).await?;
+    get_grades(client, course, max_pages).await
+}
+
+async fn get_grades(
+    client: &Backend,
+    class: Course,
+    max_pages: usize,
+) -> Result<ClassGrades, Error> {
+    let course_id = class
+        .id
+        .as_deref()
+        .ok_or(Error::MissingField("courses.list.courses[].id"))?;
+    let (work, submissions) = try_join!(
+        paginate(max_pages, |page| async move {
+            client
+                .list_course_work(course_id, page.as_deref(), None)
+                .await
+        })
+        .try_collect::<Vec<CourseWork>>(),
+        paginate(max_pages, |page| async move {
+            client
+                .list_submissions(course_id, "-", page.as_deref())
+                .await
+        })
+        .try_collect::<Vec<StudentSubmission>>()
+    )?;
+    let mut returned: HashMap<String, f64> = submissions
+        .into_iter()
+        .filter(|sub| sub.state.as_deref() == Some("RETURNED"))
+        .filter_map(|sub| Some((sub.course_work_id?, sub.assigned_grade?)))
+        .collect();
+    let mut grades = Vec::new();
+    let mut categories: Vec<CategoryGrades> = Vec::new();
+    for work in work {
+        let Some(points) = work.id.as_ref().and_then(|id| returned.remove(id)) else {
+            continue;
+        };
+        let max_points = work.max_points.filter(|max| *max > 0.0);
+        if let (Some(category), Some(max_points)) = (&work.grade_category, max_points) {
+            let name = category.name.clone().unwrap_or_default();
+            let index = categories
+                .iter()
+                .position(|existing| existing.name == name)
+                .unwrap_or_else(|| {
+                    categories.push(CategoryGrades {
+                        name,
+                        weight: category.weight.map(|weight| f64::from(weight) / 10_000.0),
+                        points: 0.0,
+                        max_points: 0.0,
+                        percent: None,
+                    });
+                    categories.len() - 1
+                });
+            categories[index].points += points;
+            categories[index].max_points += max_points;
+        }
+        grades.push(Grade {
+            work_id: work.id,
+            title: work.title,
+            category: work.grade_category.and_then(|category| category.name),
+            points,
+            max_points,
+            percent: max_points.map(|max| points / max * 100.0),
+        });
+    }
+    for category in &mut categories {
+        category.percent = percent(category.points, category.max_points);
+    }
+    let out_of_points = grades.iter().filter(|grade| grade.max_points.is_some());
+    let points = out_of_points.clone().map(|grade| grade.points).sum();
+    let max_points = out_of_points.filter_map(|grade| grade.max_points).sum();
+    let percentages: Vec<f64> = grades.iter().filter_map(|grade| grade.percent).collect();
+    #[allow(clippy::cast_precision_loss)] // there aren't 2^52 grades
+    let average = (!percentages.is_empty())
+        .then(|| percentages.iter().sum::<f64>() / percentages.len() as f64);
+    let percent = percent(points, max_points);
+    let overall = overall_grade(&class, percent, &categories);
+    Ok(ClassGrades {
+        class,
+        grades,
+        points,
+        max_points,
+        percent,
+        average,
+        categories,
+        overall,
+    })
+}
+
+fn percent(points: f64, max_points: f64) -> Option<f64> {
+    (max_points > 0.0).then(|| points / max_points * 100.0)
+}
+
+/// Classroom's overall grade for `class`: either `percent`, or the weighted average of
+/// the categories that have grades in them.
+fn overall_grade(
+    class: &Course,
+    percent: Option<f64>,
+    categories: &[CategoryGrades],
+) -> Option<f64> {
+    let settings = class.gradebook_settings.as_ref()?;
+    if settings.display_setting.as_deref() != Some("SHOW_OVERALL_GRADE") {
+        return None;
+    }
+    if settings.calculation_type.as_deref() != Some("WEIGHTED_CATEGORIES") {
+        return percent;
+    }
+    let (weighted, weights) = categories
+        .iter()
+        .filter_map(|category| Some((category.percent?, category.weight?)))
+        .fold((0.0, 0.0), |(weighted, weights), (percent, weight)| {
+            (percent.mul_add(weight, weighted), weights + weight)
+        });
+    (weights > 0.0).then(|| weighted / weights)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use classroom::api::{GradeCategory, GradebookSettings};
+
+    use super::*;
+    use crate::backend::memory::MemoryBackend;
+
+    fn category(name: &str, weight: i32) -> GradeCategory {
+        GradeCategory {
+            id: Some(name.to_lowercase()),
+            name: Some(name.to_string()),
+            weight: Some(weight),
+            ..Default::default()
+        }
+    }
+
+    /// History, with its two pieces of coursework graded 8/10 and 45/50.
+    fn graded_history() -> MemoryBackend {
+        let mut backend = MemoryBackend::fixture();
+        backend.work_mut("history", "reading").unwrap().max_points = Some(10.0);
+        let essay = backend.work_mut("history", "essay").unwrap();
+        essay.max_points = Some(50.0);
+        backend
+            .submission_mut("history", "reading")
+            .unwrap()
+            .assigned_grade = Some(8.0);
+        backend
+            .submission_mut("history", "essay")
+            .unwrap()
+            .assigned_grade = Some(45.0);
+        backend
+    }
+
+    #[tokio::test]
+    async fn lists_returned_grades() {
+        let backend = Arc::new(graded_history());
+        let Html(html) = grades(UserClient(backend.clone()), State(AppState::for_tests()))
+            .await
+            .unwrap();
+        assert!(html.contains("Essay"));
+        assert!(html.contains("45/50"));
+        assert!(!html.contains("Homework 1"), "ungraded work is shown");
+        let grades = class_grades(&(backend as Backend), "history", 20)
+            .await
+            .unwrap();
+        assert_eq!(grades.grades.len(), 2);
+        assert!((grades.points - 53.0).abs() < f64::EPSILON);
+        assert!((grades.max_points - 60.0).abs() < f64::EPSILON);
+        assert!((grades.average.unwrap() - 85.0).abs() < 1e-9);
+        assert!(
+            grades.overall.is_none(),
+            "overall grade shown without settings"
+        );
+    }
+
+    #[tokio::test]
+    async fn weights_categories() {
+        let mut backend = graded_history();
+        backend
+            .work_mut("history", "reading")
+            .unwrap()
+            .grade_category = Some(category("Homework", 250_000));
+        backend.work_mut("history", "essay").unwrap().grade_category =
+            Some(category("Essays", 750_000));
+        let history = backend
+            .courses
+            .iter_mut()
+            .find(|course| course.id.as_deref() == Some("history"))
+            .unwrap();
+        history.gradebook_settings = Some(GradebookSettings {
+            calculation_type: Some("WEIGHTED_CATEGORIES".to_string()),
+            display_setting: Some("SHOW_OVERALL_GRADE".to_string()),
+            grade_categories: None,
+        });
+        let backend: Backend = Arc::new(backend);
+        let grades = class_grades(&backend, "history", 20).await.unwrap();
+        assert_eq!(grades.categories.len(), 2);
+        assert_eq!(grades.categories[0].name, "Essays");
+        assert!((grades.categories[1].weight.unwrap() - 25.0).abs() < f64::EPSILON);
+        // 90% * 0.75 + 80% * 0.25
+        assert!((grades.overall.unwrap() - 87.5).abs() < 1e-9);
+        let Html(html) = grades_for_class(
+            UserClient(backend),
+            State(AppState::for_tests()),
+            Path("history".to_string()),
+        )
+        .await
+        .unwrap();
+        assert!(html.contains("Homework"));
+        assert!(html.contains("87.5%"));
+    }
+}
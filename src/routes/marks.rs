@@ -0,0 +1,309 @@
+use std::collections::{HashMap, HashSet};
+
+use axum::{extract::State, response::Redirect, Form};
+use classroom::chrono::{DateTime, NaiveDate, TimeZone, Utc};
+
+use super::Todo;
+use crate::{auth::UserId, csrf::Csrf, timezone::Timezone, AppState, Error};
+
+/// One user's marks, keyed by [`Todo::mark_key`].
+pub type TodoMarks = HashMap<String, Mark>;
+
+/// Something a student has said about a to-do item that Classroom doesn't know about.
+/// Marks only live in css, and are dropped once the work is gone from the to-do list.
+#[derive(Clone, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "mark", rename_all = "kebab-case")]
+pub enum Mark {
+    /// The student counts it as done, even if Classroom never will
+    Done,
+    /// The student doesn't want to see it
+    Hidden,
+    /// Hidden until `until`, then back on the list
+    Snoozed { until: DateTime<Utc> },
+}
+
+impl Mark {
+    fn hides(&self, now: DateTime<Utc>) -> bool {
+        match self {
+            Self::Done | Self::Hidden => true,
+            Self::Snoozed { until } => *until > now,
+        }
+    }
+}
+
+/// A to-do item taken off the list by a [`Mark`].
+#[derive(serde::Serialize)]
+pub struct MarkedTodo {
+    todo: Todo,
+    #[serde(flatten)]
+    mark: Mark,
+}
+
+/// A to-do list with the user's marks applied, as the JSON API returns it.
+#[derive(serde::Serialize)]
+pub struct TodoList {
+    /// What's still on the list
+    pub(super) todos: Vec<Todo>,
+    /// What the user's marks took off it
+    pub(super) marked: Vec<MarkedTodo>,
+}
+
+/// Prune `user`'s marks against `todos`, then apply them. `todos` and `class_id` are as
+/// for [`prune_marks`].
+pub async fn mark_todos(
+    state: &AppState,
+    user: &str,
+    todos: Vec<Todo>,
+    class_id: Option<&str>,
+    now: DateTime<Utc>,
+) -> Result<TodoList, Error> {
+    prune_marks(state, user, &todos, class_id, now).await?;
+    let marks = state.todo_marks.read().await.get(user).cloned();
+    let (todos, marked) = apply_marks(todos, &marks.unwrap_or_default(), now);
+    Ok(TodoList { todos, marked })
+}
+
+/// Split `todos` into the ones still on the list and the ones `marks` take off it.
+#[must_use]
+pub fn apply_marks(
+    todos: Vec<Todo>,
+    marks: &TodoMarks,
+    now: DateTime<Utc>,
+) -> (Vec<Todo>, Vec<MarkedTodo>) {
+    let mut shown = Vec::new();
+    let mut marked = Vec::new();
+    for todo in todos {
+        match marks.get(&todo.mark_key()) {
+            Some(mark) if mark.hides(now) => marked.push(MarkedTodo {
+                todo,
+                mark: mark.clone(),
+            }),
+            _ => shown.push(todo),
+        }
+    }
+    (shown, marked)
+}
+
+/// Drop the marks in `user`'s store that no longer do anything: work that's gone from
+/// `todos`, and snoozes that are over. Work that's turned in but still listed, like late
+/// work, keeps its mark. `todos` is the whole list for
+/// the class `class_id`, or for every class if it's `None`, so marks for other classes
+/// are left alone.
+pub async fn prune_marks(
+    state: &AppState,
+    user: &str,
+    todos: &[Todo],
+    class_id: Option<&str>,
+    now: DateTime<Utc>,
+) -> Result<(), Error> {
+    let listed: HashSet<String> = todos.iter().map(Todo::mark_key).collect();
+    let keep = |key: &str, mark: &Mark| {
+        let in_scope = class_id.is_none_or(|id| key.split('/').next() == Some(id));
+        !in_scope
+            || (listed.contains(key) && !matches!(mark, Mark::Snoozed { until } if *until <= now))
+    };
+    // most page loads have nothing to prune, so only write the store when something changes
+    let has_stale = state
+        .todo_marks
+        .read()
+        .await
+        .get(user)
+        .is_some_and(|marks| marks.iter().any(|(key, mark)| !keep(key, mark)));
+    if has_stale {
+        state
+            .todo_marks
+            .update(|saved| {
+                if let Some(marks) = saved.get_mut(user) {
+                    marks.retain(|key, mark| keep(key, mark));
+                    if marks.is_empty() {
+                        saved.remove(user);
+                    }
+                }
+            })
+            .await?;
+    }
+    Ok(())
+}
+
+#[derive(serde::Deserialize)]
+pub struct MarkForm {
+    csrf: String,
+    class_id: String,
+    id: String,
+    /// `done`, `hidden`, `snoozed` or `none` to take a mark off
+    mark: String,
+    /// The day to snooze until, as `YYYY-MM-DD` in the user's time zone
+    #[serde(default)]
+    until: String,
+    /// Set when the form is on a class's to-do page, so it can go back there
+    #[serde(default)]
+    from_class: bool,
+}
+
+/// Mark a to-do item done, hidden or snoozed, or take its mark off.
+pub async fn mark_todo(
+    State(state): State<AppState>,
+    UserId(user): UserId,
+    Timezone(timezone): Timezone,
+    csrf: Csrf,
+    Form(form): Form<MarkForm>,
+) -> Result<Redirect, Error> {
+    csrf.verify(&form.csrf)?;
+    let mark = match form.mark.as_str() {
+        "done" => Some(Mark::Done),
+        "hidden" => Some(Mark::Hidden),
+        "snoozed" => {
+            let day = NaiveDate::parse_from_str(&form.until, "%Y-%m-%d")
+                .map_err(|_| Error::InvalidMark("pick a day to snooze until"))?;
+            let until = timezone
+                .from_local_datetime(&day.and_time(classroom::chrono::NaiveTime::MIN))
+                .earliest()
+                .ok_or(Error::InvalidMark("that day doesn't start at midnight"))?
+                .with_timezone(&Utc);
+            if until <= Utc::now() {
+                return Err(Error::InvalidMark("pick a day after today"));
+            }
+            Some(Mark::Snoozed { until })
+        }
+        "none" => None,
+        _ => return Err(Error::InvalidMark("unknown mark")),
+    };
+    let key = Todo::key_for(&form.class_id, &form.id);
+    state
+        .todo_marks
+        .update(|saved| {
+            let marks = saved.entry(user.clone()).or_default();
+            if let Some(mark) = mark {
+                marks.insert(key, mark);
+            } else {
+                marks.remove(&key);
+                if marks.is_empty() {
+                    saved.remove(&user);
+                }
+            }
+        })
+        .await?;
+    if form.from_class {
+        Ok(Redirect::to(&format!("/todo/{}", form.class_id)))
+    } else {
+        Ok(Redirect::to("/todo"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        backend::{memory::MemoryBackend, Backend},
+        completion::CompletionPolicy,
+        routes::all_todos,
+    };
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    /// The fixture's to-do list, plus Homework 3, which was turned in late and so is
+    /// still on the list.
+    async fn todos() -> Vec<Todo> {
+        let mut backend = MemoryBackend::fixture();
+        backend.add_work("math", "hw3", "Homework 3", Some((2023, 9, 2, 23, 59)));
+        backend.add_submission("math", "hw3", "TURNED_IN", true, None);
+        let backend: Backend = Arc::new(backend);
+        all_todos(&backend, CompletionPolicy::default(), 20)
+            .await
+            .unwrap()
+    }
+
+    fn key(todos: &[Todo], index: usize) -> String {
+        todos[index].mark_key()
+    }
+
+    #[tokio::test]
+    async fn marks_take_work_off_the_list() {
+        let todos = todos().await;
+        let now = at("2023-09-06T12:00:00Z");
+        let marks = TodoMarks::from([
+            (key(&todos, 0), Mark::Done),
+            (
+                key(&todos, 1),
+                Mark::Snoozed {
+                    until: at("2023-09-07T00:00:00Z"),
+                },
+            ),
+            (
+                key(&todos, 2),
+                Mark::Snoozed {
+                    until: at("2023-09-06T00:00:00Z"),
+                },
+            ),
+        ]);
+        let (shown, marked) = apply_marks(todos.clone(), &marks, now);
+        assert_eq!(shown.len(), todos.len() - 2);
+        assert_eq!(marked.len(), 2);
+        assert_eq!(marked[0].mark, Mark::Done);
+        // the snooze is over once its day comes
+        let (shown, _) = apply_marks(todos.clone(), &marks, at("2023-09-07T00:00:00Z"));
+        assert_eq!(shown.len(), todos.len() - 1);
+    }
+
+    #[tokio::test]
+    async fn prunes_marks_that_do_nothing() {
+        let todos = todos().await;
+        let math = |todo: &Todo| todo.mark_key().starts_with("math/");
+        let turned_in = todos
+            .iter()
+            .position(|todo| todo.mark_key() == "math/hw3-submission")
+            .expect("late turned in work isn't listed");
+        let mut open = (0..todos.len()).filter(|i| *i != turned_in && math(&todos[*i]));
+        let (hidden, snoozed) = (open.next().unwrap(), open.next().unwrap());
+        let state = AppState::for_tests();
+        let now = at("2023-09-06T12:00:00Z");
+        let marks = TodoMarks::from([
+            (key(&todos, hidden), Mark::Hidden),
+            (key(&todos, turned_in), Mark::Done),
+            (
+                key(&todos, snoozed),
+                Mark::Snoozed {
+                    until: at("2023-09-06T00:00:00Z"),
+                },
+            ),
+            ("math/gone".to_string(), Mark::Hidden),
+            ("art/elsewhere".to_string(), Mark::Hidden),
+        ]);
+        state
+            .todo_marks
+            .update(|saved| saved.insert("student".to_string(), marks))
+            .await
+            .unwrap();
+        let math_todos: Vec<Todo> = todos.iter().filter(|todo| math(todo)).cloned().collect();
+        prune_marks(&state, "student", &math_todos, Some("math"), now)
+            .await
+            .unwrap();
+        let saved = state.todo_marks.read().await["student"].clone();
+        // turned in work keeps its mark while it's listed
+        assert_eq!(
+            saved,
+            TodoMarks::from([
+                (key(&todos, hidden), Mark::Hidden),
+                (key(&todos, turned_in), Mark::Done),
+                ("art/elsewhere".to_string(), Mark::Hidden),
+            ])
+        );
+        prune_marks(&state, "student", &todos, None, now)
+            .await
+            .unwrap();
+        let saved = state.todo_marks.read().await["student"].clone();
+        assert_eq!(
+            saved,
+            TodoMarks::from([
+                (key(&todos, hidden), Mark::Hidden),
+                (key(&todos, turned_in), Mark::Done),
+            ])
+        );
+    }
+}
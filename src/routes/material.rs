@@ -0,0 +1,91 @@
+use axum::{
+    extract::{Path, State},
+    response::Html,
+};
+use classroom::api::{Course, CourseWorkMaterial};
+use tokio::try_join;
+
+use super::{attachments, Attachment};
+use crate::{
+    auth::{TeacherMode, UserClient},
+    backend::Backend,
+    AppState, Error,
+};
+
+pub async fn material(
+    UserClient(client): UserClient,
+    State(state): State<AppState>,
+    TeacherMode(teacher): TeacherMode,
+    Path((course_id, id)): Path<(String, String)>,
+) -> Result<Html<String>, Error> {
+    let mut context = tera::Context::new();
+    context.insert("teacher", &teacher);
+    let page = get_material(&client, &course_id, &id).await?;
+    context.insert("class", &page.class);
+    context.insert("material", &page.material);
+    context.insert("attachments", &page.attachments);
+    Ok(Html(state.tera.render("material.jinja", &context)?))
+}
+
+#[derive(serde::Serialize)]
+pub struct MaterialPage {
+    class: Course,
+    material: CourseWorkMaterial,
+    attachments: Vec<Attachment>,
+}
+
+pub async fn get_material(
+    client: &Backend,
+    course_id: &str,
+    id: &str,
+) -> Result<MaterialPage, Error> {
+    let (class, material) = try_join!(
+        client.get_course(course_id),
+        client.get_course_work_material(course_id, id)
+    )?;
+    Ok(MaterialPage {
+        attachments: attachments(material.materials.as_deref()),
+        class,
+        material,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::http::StatusCode;
+
+    use super::*;
+    use crate::backend::memory::MemoryBackend;
+
+    #[tokio::test]
+    async fn renders_material() {
+        let backend = Arc::new(MemoryBackend::fixture());
+        let Html(html) = material(
+            UserClient(backend),
+            State(AppState::for_tests()),
+            TeacherMode(false),
+            Path(("math".to_string(), "notes".to_string())),
+        )
+        .await
+        .unwrap();
+        assert!(html.contains("Lecture notes"));
+        assert!(html.contains("Slides"));
+        assert!(html.contains(r#"href="/class/math""#));
+    }
+
+    #[tokio::test]
+    async fn unknown_material() {
+        let backend = Arc::new(MemoryBackend::fixture());
+        let err = material(
+            UserClient(backend),
+            State(AppState::for_tests()),
+            TeacherMode(false),
+            Path(("math".to_string(), "hw1".to_string())),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+    }
+}
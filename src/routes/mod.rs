@@ -1,13 +1,35 @@
+mod announcements;
 mod assignment;
+mod attachment;
+mod authoring;
 mod class;
+mod feed;
+mod grades;
 mod info;
+mod marks;
+mod material;
+mod people;
+mod settings;
+mod submission;
+mod teacher;
 mod todo;
+pub use announcements::*;
 pub use assignment::*;
+pub use attachment::*;
+pub use authoring::*;
 pub use class::*;
+pub use feed::*;
+pub use grades::*;
 pub use info::*;
+pub use marks::*;
+pub use material::*;
+pub use people::*;
+pub use settings::*;
+pub use submission::*;
+pub use teacher::*;
 pub use todo::*;
 
 #[derive(serde::Deserialize)]
 pub struct PaginationQuery {
-    page: Option<String>,
+    pub page: Option<String>,
 }
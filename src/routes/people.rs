@@ -0,0 +1,208 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::Html,
+};
+use classroom::api::{Course, Teacher, UserProfile};
+use futures_util::TryStreamExt;
+use tokio::try_join;
+
+use crate::{auth::UserClient, backend::Backend, paginate::paginate, AppState, Error};
+
+/// How many pixels across profile photos are fetched at, twice the size they're shown at
+/// so they stay sharp on high-DPI screens.
+const PHOTO_SIZE: u32 = 96;
+
+pub async fn people(
+    UserClient(client): UserClient,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(pages): Query<super::PaginationQuery>,
+) -> Result<Html<String>, Error> {
+    let mut context = tera::Context::new();
+    context.insert("is_first_page", &pages.page.is_none());
+    let page = get_people(&client, &id, pages.page, state.max_pages).await?;
+    context.insert("class", &page.class);
+    context.insert("teachers", &page.teachers);
+    context.insert("students", &page.students);
+    context.insert("pagination_token", &page.next_page);
+    Ok(Html(state.tera.render("people.jinja", &context)?))
+}
+
+/// One page of a class's roster. Every teacher is on the first page, and none on the rest.
+#[derive(serde::Serialize)]
+pub struct PeoplePage {
+    class: Course,
+    teachers: Vec<Person>,
+    students: Vec<Person>,
+    /// The page token for more students
+    next_page: Option<String>,
+}
+
+/// A teacher or student, as much as Classroom shows of them.
+#[derive(serde::Serialize)]
+pub struct Person {
+    id: Option<String>,
+    /// Their full name, or their user id if Classroom won't say
+    name: String,
+    /// Only shown if the user's domain lets them see it
+    email: Option<String>,
+    photo: Option<String>,
+}
+
+impl Person {
+    fn new(user_id: Option<String>, profile: Option<UserProfile>) -> Self {
+        let profile = profile.unwrap_or_default();
+        let id = user_id.or(profile.id);
+        Self {
+            name: profile
+                .name
+                .and_then(|name| name.full_name)
+                .or_else(|| id.clone())
+                .unwrap_or_default(),
+            id,
+            email: profile.email_address,
+            photo: profile
+                .photo_url
+                .and_then(|url| sized_photo(&url, PHOTO_SIZE)),
+        }
+    }
+}
+
+pub async fn get_people(
+    client: &Backend,
+    id: &str,
+    page: Option<String>,
+    max_pages: usize,
+) -> Result<PeoplePage, Error> {
+    let first_page = page.is_none();
+    let (class, teachers, students) = try_join!(
+        client.get_course(id),
+        async {
+            if !first_page {
+                return Ok(Vec::new());
+            }
+            paginate(max_pages, |page| async move {
+                client.list_teachers(id, page.as_deref()).await
+            })
+            .try_collect::<Vec<Teacher>>()
+            .await
+        },
+        client.list_students(id, page.as_deref())
+    )?;
+    Ok(PeoplePage {
+        class,
+        teachers: teachers
+            .into_iter()
+            .map(|teacher| Person::new(teacher.user_id, teacher.profile))
+            .collect(),
+        students: students
+            .students
+            .unwrap_or_default()
+            .into_iter()
+            .map(|student| Person::new(student.user_id, student.profile))
+            .collect(),
+        next_page: students.next_page_token,
+    })
+}
+
+/// A profile photo URL that fetches the photo `size` pixels square.
+///
+/// Classroom leaves the scheme off photo URLs, and Google serves the full-size upload
+/// unless the URL asks for a size with a `=s<size>` suffix. Anything that isn't HTTPS
+/// once the scheme is filled in is dropped.
+fn sized_photo(url: &str, size: u32) -> Option<String> {
+    let url = url
+        .strip_prefix("//")
+        .map_or_else(|| url.to_string(), |rest| format!("https://{rest}"));
+    let mut url = oauth2::url::Url::parse(&url).ok()?;
+    if url.scheme() != "https" {
+        return None;
+    }
+    let googleusercontent = url
+        .host_str()
+        .is_some_and(|host| host.ends_with(".googleusercontent.com"));
+    if googleusercontent {
+        // replace any size options already on the end, like `=s100-c`
+        let path = url.path();
+        let path = path.split_once('=').map_or(path, |(path, _)| path);
+        let path = format!("{path}=s{size}-c");
+        url.set_path(&path);
+    }
+    Some(url.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::http::StatusCode;
+
+    use super::*;
+    use crate::backend::memory::MemoryBackend;
+
+    #[tokio::test]
+    async fn lists_teachers_and_students() {
+        let mut backend = MemoryBackend::fixture();
+        let frizzle = backend
+            .users
+            .iter_mut()
+            .find(|user| user.id.as_deref() == Some("teacher"))
+            .unwrap();
+        frizzle.email_address = Some("frizzle@school.example".to_string());
+        frizzle.photo_url = Some("//lh3.googleusercontent.com/a/frizzle".to_string());
+        let Html(html) = people(
+            UserClient(Arc::new(backend)),
+            State(AppState::for_tests()),
+            Path("math".to_string()),
+            Query(super::super::PaginationQuery { page: None }),
+        )
+        .await
+        .unwrap();
+        let teacher = html.find("Ms. Frizzle").unwrap();
+        let student = html.find("student").unwrap();
+        assert!(teacher < student);
+        assert!(html.contains("frizzle@school.example"));
+        assert!(html.contains("frizzle=s96-c"));
+    }
+
+    #[tokio::test]
+    async fn pages_through_students() {
+        let mut backend = MemoryBackend::fixture();
+        for i in 0..4 {
+            backend.add_student("math", &format!("classmate{i}"));
+        }
+        backend.page_size = 3;
+        let backend: Backend = Arc::new(backend);
+        let first = get_people(&backend, "math", None, 20).await.unwrap();
+        assert_eq!(first.teachers.len(), 1);
+        assert_eq!(first.students.len(), 3);
+        let second = get_people(&backend, "math", first.next_page, 20)
+            .await
+            .unwrap();
+        assert!(second.teachers.is_empty());
+        assert_eq!(second.students.len(), 2);
+        assert_eq!(second.students[1].name, "classmate3");
+        assert!(second.next_page.is_none());
+    }
+
+    #[tokio::test]
+    async fn unknown_class() {
+        let backend: Backend = Arc::new(MemoryBackend::fixture());
+        let err = get_people(&backend, "gym", None, 20).await.err().unwrap();
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn sizes_photos() {
+        assert_eq!(
+            sized_photo("//lh3.googleusercontent.com/a-/abc=s100-c", 96).as_deref(),
+            Some("https://lh3.googleusercontent.com/a-/abc=s96-c")
+        );
+        assert_eq!(
+            sized_photo("https://example.com/me.png", 96).as_deref(),
+            Some("https://example.com/me.png")
+        );
+        assert!(sized_photo("javascript:alert(1)", 96).is_none());
+        assert!(sized_photo("http://example.com/me.png", 96).is_none());
+    }
+}
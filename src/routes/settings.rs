@@ -0,0 +1,139 @@
+use axum::{
+    extract::State,
+    response::{Html, Redirect},
+    Form,
+};
+use chrono_tz::{Tz, TZ_VARIANTS};
+use tower_cookies::{
+    cookie::{time::Duration, SameSite},
+    Cookie, Cookies,
+};
+
+use crate::{
+    auth::{TeacherMode, UserId},
+    completion::{CompletionOverrides, Returned},
+    csrf::{Csrf, CsrfForm},
+    timezone::{unknown_timezone, Timezone},
+    AppState, Error,
+};
+
+pub async fn settings(
+    State(state): State<AppState>,
+    UserId(user): UserId,
+    TeacherMode(teacher): TeacherMode,
+    Timezone(timezone): Timezone,
+    cookies: Cookies,
+    csrf: Csrf,
+) -> Result<Html<String>, Error> {
+    let mut context = tera::Context::new();
+    let timezones: Vec<&str> = TZ_VARIANTS.iter().map(|tz| tz.name()).collect();
+    context.insert("timezone", timezone.name());
+    context.insert("unknown_timezone", &unknown_timezone(&cookies));
+    context.insert("timezones", &timezones);
+    context.insert("csrf", csrf.token());
+    context.insert("root_url", &state.root_url);
+    context.insert("feeds", &super::list_feeds(&state, &user).await);
+    context.insert("new_feed", &super::take_new_feed(&state, &cookies));
+    context.insert("teacher", &teacher);
+    let overrides = state.completion_overrides.read().await.get(&user).cloned();
+    context.insert("completion", &overrides.unwrap_or_default());
+    context.insert("server_completion", &state.completion);
+    Ok(Html(state.tera.render("settings.jinja", &context)?))
+}
+
+/// Turn teacher mode off. The teacher scopes stay granted until the user revokes them
+/// in their Google account, so turning it back on doesn't need their consent again.
+pub async fn teacher_mode_off(
+    State(state): State<AppState>,
+    csrf: Csrf,
+    cookies: Cookies,
+    Form(form): Form<CsrfForm>,
+) -> Result<Redirect, Error> {
+    csrf.verify(&form.csrf)?;
+    let mut cookie = Cookie::named("teacher");
+    cookie.set_path("/");
+    cookies.private(&state.key).remove(cookie);
+    Ok(Redirect::to("/settings"))
+}
+
+#[derive(serde::Deserialize)]
+pub struct TimezoneForm {
+    csrf: String,
+    /// An IANA time zone name, or blank to go back to the browser's zone
+    timezone: String,
+}
+
+/// Set the time zone that times are shown in. The `tz` cookie isn't private, since the
+/// browser fills it in with its own zone whenever it's missing.
+pub async fn set_timezone(
+    csrf: Csrf,
+    cookies: Cookies,
+    Form(form): Form<TimezoneForm>,
+) -> Result<Redirect, Error> {
+    csrf.verify(&form.csrf)?;
+    let mut cookie = Cookie::named("tz");
+    cookie.set_path("/");
+    match form.timezone.trim() {
+        "" => cookies.remove(cookie),
+        name => {
+            let timezone: Tz = name.parse().map_err(|_| Error::UnknownTimezone)?;
+            cookie.set_value(timezone.name());
+            cookie.set_max_age(Duration::days(365));
+            cookie.set_same_site(SameSite::Lax);
+            cookies.add(cookie);
+        }
+    }
+    Ok(Redirect::to("/settings"))
+}
+
+/// The to-do list settings form. A blank field follows the server's setting.
+#[derive(serde::Deserialize)]
+pub struct CompletionForm {
+    csrf: String,
+    late: String,
+    returned: String,
+    no_due_date: String,
+}
+
+/// Change which work the user's to-do list counts as done.
+pub async fn set_completion(
+    State(state): State<AppState>,
+    UserId(user): UserId,
+    csrf: Csrf,
+    Form(form): Form<CompletionForm>,
+) -> Result<Redirect, Error> {
+    csrf.verify(&form.csrf)?;
+    let overrides = CompletionOverrides {
+        late: choice("late", &form.late)?,
+        returned: match form.returned.as_str() {
+            "" => None,
+            "all" => Some(Returned::All),
+            "ungraded" => Some(Returned::Ungraded),
+            "none" => Some(Returned::None),
+            _ => return Err(Error::InvalidSetting("returned")),
+        },
+        no_due_date: choice("no_due_date", &form.no_due_date)?,
+    };
+    state
+        .completion_overrides
+        .update(|saved| {
+            if overrides.is_empty() {
+                saved.remove(&user);
+            } else {
+                saved.insert(user, overrides);
+            }
+        })
+        .await?;
+    Ok(Redirect::to("/settings"))
+}
+
+/// A true or false setting named `field`, or `None` if it's blank.
+fn choice(field: &'static str, value: &str) -> Result<Option<bool>, Error> {
+    match value {
+        "" => Ok(None),
+        value => value
+            .parse()
+            .map(Some)
+            .map_err(|_| Error::InvalidSetting(field)),
+    }
+}
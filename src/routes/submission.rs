@@ -0,0 +1,620 @@
+use axum::{
+    extract::{Path, State},
+    response::{Html, Redirect},
+    Form,
+};
+use classroom::api::{
+    Attachment, DriveFile, Link, MultipleChoiceSubmission, ShortAnswerSubmission, StudentSubmission,
+};
+
+use crate::{
+    auth::UserClient,
+    backend::Backend,
+    csrf::{Csrf, CsrfForm},
+    AppState, Error,
+};
+
+/// Ask the user to confirm turning in an assignment.
+pub async fn turn_in_page(
+    UserClient(client): UserClient,
+    State(state): State<AppState>,
+    csrf: Csrf,
+    Path((course_id, id)): Path<(String, String)>,
+) -> Result<Html<String>, Error> {
+    let work = client.get_course_work(&course_id, &id).await?;
+    let title = work.title.as_deref().unwrap_or("this assignment");
+    Ok(confirm(
+        &state,
+        &csrf,
+        &format!("Turn in {title}?"),
+        &format!("/assignment/{course_id}/{id}/turn-in"),
+        "Turn in",
+        &format!("/assignment/{course_id}/{id}"),
+    )?)
+}
+
+pub async fn turn_in(
+    UserClient(client): UserClient,
+    csrf: Csrf,
+    Path((course_id, id)): Path<(String, String)>,
+    Form(form): Form<CsrfForm>,
+) -> Result<Redirect, Error> {
+    csrf.verify(&form.csrf)?;
+    let (submission_id, _) = own_submission(&client, &course_id, &id).await?;
+    client.turn_in(&course_id, &id, &submission_id).await?;
+    Ok(Redirect::to(&format!("/assignment/{course_id}/{id}")))
+}
+
+/// Ask the user to confirm unsubmitting an assignment.
+pub async fn unsubmit_page(
+    UserClient(client): UserClient,
+    State(state): State<AppState>,
+    csrf: Csrf,
+    Path((course_id, id)): Path<(String, String)>,
+) -> Result<Html<String>, Error> {
+    let work = client.get_course_work(&course_id, &id).await?;
+    let title = work.title.as_deref().unwrap_or("this assignment");
+    Ok(confirm(
+        &state,
+        &csrf,
+        &format!("Unsubmit {title}?"),
+        &format!("/assignment/{course_id}/{id}/unsubmit"),
+        "Unsubmit",
+        &format!("/assignment/{course_id}/{id}"),
+    )?)
+}
+
+pub async fn unsubmit(
+    UserClient(client): UserClient,
+    csrf: Csrf,
+    Path((course_id, id)): Path<(String, String)>,
+    Form(form): Form<CsrfForm>,
+) -> Result<Redirect, Error> {
+    csrf.verify(&form.csrf)?;
+    let (submission_id, _) = own_submission(&client, &course_id, &id).await?;
+    client.reclaim(&course_id, &id, &submission_id).await?;
+    Ok(Redirect::to(&format!("/assignment/{course_id}/{id}")))
+}
+
+#[derive(serde::Deserialize)]
+pub struct AnswerForm {
+    csrf: String,
+    #[serde(default)]
+    answer: String,
+}
+
+/// Save the user's answer to a short answer or multiple choice question.
+pub async fn answer(
+    UserClient(client): UserClient,
+    csrf: Csrf,
+    Path((course_id, id)): Path<(String, String)>,
+    Form(form): Form<AnswerForm>,
+) -> Result<Redirect, Error> {
+    csrf.verify(&form.csrf)?;
+    let (work, (submission_id, _)) = tokio::try_join!(
+        client.get_course_work(&course_id, &id),
+        own_submission(&client, &course_id, &id)
+    )?;
+    let answer = form.answer.trim().to_string();
+    if answer.is_empty() {
+        return Err(Error::InvalidAnswer("the answer is empty"));
+    }
+    let (submission, update_mask) = match work.work_type.as_deref() {
+        Some("SHORT_ANSWER_QUESTION") => (
+            StudentSubmission {
+                short_answer_submission: Some(ShortAnswerSubmission {
+                    answer: Some(answer),
+                }),
+                ..Default::default()
+            },
+            "shortAnswerSubmission.answer",
+        ),
+        Some("MULTIPLE_CHOICE_QUESTION") => {
+            let choices = work
+                .multiple_choice_question
+                .and_then(|question| question.choices)
+                .unwrap_or_default();
+            if !choices.contains(&answer) {
+                return Err(Error::InvalidAnswer("that isn't one of the choices"));
+            }
+            (
+                StudentSubmission {
+                    multiple_choice_submission: Some(MultipleChoiceSubmission {
+                        answer: Some(answer),
+                    }),
+                    ..Default::default()
+                },
+                "multipleChoiceSubmission.answer",
+            )
+        }
+        _ => return Err(Error::InvalidAnswer("this isn't a question")),
+    };
+    client
+        .patch_submission(&course_id, &id, &submission_id, submission, update_mask)
+        .await?;
+    Ok(Redirect::to(&format!("/assignment/{course_id}/{id}")))
+}
+
+/// The most attachments Classroom allows on one submission.
+const MAX_ATTACHMENTS: usize = 20;
+
+#[derive(serde::Deserialize)]
+pub struct AttachForm {
+    csrf: String,
+    #[serde(default)]
+    link: String,
+    /// A Drive file id, or a link to the file
+    #[serde(default)]
+    drive_file: String,
+}
+
+pub async fn add_attachment(
+    UserClient(client): UserClient,
+    csrf: Csrf,
+    Path((course_id, id)): Path<(String, String)>,
+    Form(form): Form<AttachForm>,
+) -> Result<Redirect, Error> {
+    csrf.verify(&form.csrf)?;
+    let mut attachments = Vec::new();
+    if !form.link.trim().is_empty() {
+        attachments.push(link_attachment(form.link.trim())?);
+    }
+    if !form.drive_file.trim().is_empty() {
+        attachments.push(drive_attachment(form.drive_file.trim())?);
+    }
+    if attachments.is_empty() {
+        return Err(Error::InvalidAttachment("enter a link or a Drive file"));
+    }
+    let (work, (submission_id, submission)) = tokio::try_join!(
+        client.get_course_work(&course_id, &id),
+        own_submission(&client, &course_id, &id)
+    )?;
+    if work.work_type.as_deref() != Some("ASSIGNMENT") {
+        return Err(Error::InvalidAttachment(
+            "only assignments can have attachments, not questions",
+        ));
+    }
+    let existing = submission
+        .assignment_submission
+        .and_then(|work| work.attachments)
+        .map_or(0, |attachments| attachments.len());
+    if existing + attachments.len() > MAX_ATTACHMENTS {
+        return Err(Error::InvalidAttachment(
+            "a submission can't have more than 20 attachments",
+        ));
+    }
+    client
+        .add_attachments(&course_id, &id, &submission_id, attachments)
+        .await?;
+    Ok(Redirect::to(&format!("/assignment/{course_id}/{id}")))
+}
+
+fn link_attachment(link: &str) -> Result<Attachment, Error> {
+    let url = oauth2::url::Url::parse(link)
+        .map_err(|_| Error::InvalidAttachment("that link isn't a valid URL"))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(Error::InvalidAttachment(
+            "links have to start with http:// or https://",
+        ));
+    }
+    Ok(Attachment {
+        link: Some(Link {
+            url: Some(url.into()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+/// Accepts a bare file id, or a Drive link like `https://drive.google.com/file/d/{id}/view`
+/// or `https://drive.google.com/open?id={id}`.
+fn drive_attachment(file: &str) -> Result<Attachment, Error> {
+    let id = match oauth2::url::Url::parse(file) {
+        Ok(url) => url
+            .query_pairs()
+            .find(|(key, _)| key == "id")
+            .map(|(_, id)| id.into_owned())
+            .or_else(|| {
+                let mut segments = url.path_segments()?;
+                segments.find(|segment| *segment == "d")?;
+                segments.next().map(str::to_string)
+            })
+            .ok_or(Error::InvalidAttachment(
+                "that link doesn't point at a Drive file",
+            ))?,
+        Err(_) => file.to_string(),
+    };
+    let valid = !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(Error::InvalidAttachment("that isn't a Drive file id"));
+    }
+    Ok(Attachment {
+        drive_file: Some(DriveFile {
+            id: Some(id),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+/// The user's own submission for a piece of coursework, and its id. Teachers and anyone else
+/// the work isn't assigned to have none.
+pub async fn own_submission(
+    client: &Backend,
+    course_id: &str,
+    course_work_id: &str,
+) -> Result<(String, StudentSubmission), Error> {
+    let sub = client
+        .list_submissions(course_id, course_work_id, None)
+        .await?
+        .student_submissions
+        .and_then(|subs| subs.into_iter().next())
+        .ok_or(Error::InvalidClasswork(
+            "you have no submission for this work",
+        ))?;
+    let id = sub.id.clone().ok_or(Error::MissingField(
+        "courses.courseWork.studentSubmissions[].id",
+    ))?;
+    Ok((id, sub))
+}
+
+/// Render a page with a single button that POSTs to `action`.
+pub(super) fn confirm(
+    state: &AppState,
+    csrf: &Csrf,
+    question: &str,
+    action: &str,
+    button: &str,
+    cancel: &str,
+) -> Result<Html<String>, tera::Error> {
+    let mut context = tera::Context::new();
+    context.insert("csrf", csrf.token());
+    context.insert("question", question);
+    context.insert("action", action);
+    context.insert("button", button);
+    context.insert("cancel", cancel);
+    Ok(Html(state.tera.render("confirm.jinja", &context)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::http::StatusCode;
+
+    use super::*;
+    use crate::backend::memory::MemoryBackend;
+
+    async fn state_of(client: &Backend, course_id: &str, id: &str) -> String {
+        let subs = client.list_submissions(course_id, id, None).await.unwrap();
+        subs.student_submissions.unwrap()[0].state.clone().unwrap()
+    }
+
+    fn form(csrf: &str) -> Form<CsrfForm> {
+        Form(CsrfForm {
+            csrf: csrf.to_string(),
+        })
+    }
+
+    fn path(course_id: &str, id: &str) -> Path<(String, String)> {
+        Path((course_id.to_string(), id.to_string()))
+    }
+
+    #[tokio::test]
+    async fn asks_before_turning_in() {
+        let backend = Arc::new(MemoryBackend::fixture());
+        let Html(html) = turn_in_page(
+            UserClient(backend),
+            State(AppState::for_tests()),
+            Csrf::for_tests(),
+            path("math", "hw2"),
+        )
+        .await
+        .unwrap();
+        assert!(html.contains("Turn in Homework 2?"));
+        assert!(html.contains(r#"method="post""#));
+        assert!(html.contains(Csrf::for_tests().token()));
+    }
+
+    #[tokio::test]
+    async fn turns_in_and_unsubmits() {
+        let client: Backend = Arc::new(MemoryBackend::fixture());
+        let token = Csrf::for_tests().token().to_string();
+        let redirect = turn_in(
+            UserClient(client.clone()),
+            Csrf::for_tests(),
+            path("math", "hw2"),
+            form(&token),
+        )
+        .await
+        .unwrap();
+        let response = axum::response::IntoResponse::into_response(redirect);
+        assert_eq!(response.headers()["location"], "/assignment/math/hw2");
+        assert_eq!(state_of(&client, "math", "hw2").await, "TURNED_IN");
+        let _ = unsubmit(
+            UserClient(client.clone()),
+            Csrf::for_tests(),
+            path("math", "hw2"),
+            form(&token),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            state_of(&client, "math", "hw2").await,
+            "RECLAIMED_BY_STUDENT"
+        );
+    }
+
+    #[tokio::test]
+    async fn only_turns_in_work_css_made() {
+        let client: Backend = Arc::new(MemoryBackend::fixture());
+        let err = turn_in(
+            UserClient(client.clone()),
+            Csrf::for_tests(),
+            path("math", "project"),
+            form(Csrf::for_tests().token()),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+        assert_eq!(state_of(&client, "math", "project").await, "CREATED");
+    }
+
+    #[tokio::test]
+    async fn refuses_to_turn_in_without_a_submission() {
+        let mut backend = MemoryBackend::fixture();
+        backend.add_work("math", "hw3", "Homework 3", None);
+        backend.made_by_css("math", "hw3");
+        let err = turn_in(
+            UserClient(Arc::new(backend)),
+            Csrf::for_tests(),
+            path("math", "hw3"),
+            form(Csrf::for_tests().token()),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn checks_csrf() {
+        let client: Backend = Arc::new(MemoryBackend::fixture());
+        let err = turn_in(
+            UserClient(client.clone()),
+            Csrf::for_tests(),
+            path("math", "hw2"),
+            form("forged"),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+        assert_eq!(state_of(&client, "math", "hw2").await, "CREATED");
+    }
+
+    fn attach_form(link: &str, drive_file: &str) -> Form<AttachForm> {
+        Form(AttachForm {
+            csrf: Csrf::for_tests().token().to_string(),
+            link: link.to_string(),
+            drive_file: drive_file.to_string(),
+        })
+    }
+
+    async fn attachment_titles(client: &Backend, course_id: &str, id: &str) -> Vec<String> {
+        let subs = client.list_submissions(course_id, id, None).await.unwrap();
+        let sub = subs.student_submissions.unwrap().remove(0);
+        sub.assignment_submission
+            .and_then(|work| work.attachments)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(crate::routes::Attachment::from_attachment)
+            .map(|attachment| attachment.title)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn attaches_links_and_drive_files() {
+        let client: Backend = Arc::new(MemoryBackend::fixture());
+        let _ = add_attachment(
+            UserClient(client.clone()),
+            Csrf::for_tests(),
+            path("math", "hw2"),
+            attach_form(
+                "https://example.com/essay",
+                "https://drive.google.com/file/d/1aB-c_D/view?usp=sharing",
+            ),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            attachment_titles(&client, "math", "hw2").await,
+            ["https://example.com/essay", "1aB-c_D"]
+        );
+    }
+
+    #[tokio::test]
+    async fn only_attaches_to_work_css_made() {
+        let mut backend = MemoryBackend::fixture();
+        backend.add_work("math", "hw3", "Homework 3", None);
+        backend.add_submission("math", "hw3", "CREATED", false, None);
+        let client: Backend = Arc::new(backend);
+        let err = add_attachment(
+            UserClient(client.clone()),
+            Csrf::for_tests(),
+            path("math", "hw3"),
+            attach_form("https://example.com/essay", ""),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+        assert!(attachment_titles(&client, "math", "hw3").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_attachments() {
+        let client: Backend = Arc::new(MemoryBackend::fixture());
+        for (link, drive_file) in [
+            ("", ""),
+            ("javascript:alert(1)", ""),
+            ("not a url", ""),
+            ("", "https://example.com/no-id"),
+            ("", "id with spaces"),
+        ] {
+            let err = add_attachment(
+                UserClient(client.clone()),
+                Csrf::for_tests(),
+                path("math", "hw2"),
+                attach_form(link, drive_file),
+            )
+            .await
+            .unwrap_err();
+            assert!(matches!(err, Error::InvalidAttachment(_)), "{err:?}");
+            assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+        }
+        assert!(attachment_titles(&client, "math", "hw2").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn caps_attachments() {
+        let client: Backend = Arc::new(MemoryBackend::fixture());
+        for _ in 0..MAX_ATTACHMENTS {
+            let _ = add_attachment(
+                UserClient(client.clone()),
+                Csrf::for_tests(),
+                path("math", "hw2"),
+                attach_form("https://example.com", ""),
+            )
+            .await
+            .unwrap();
+        }
+        let err = add_attachment(
+            UserClient(client.clone()),
+            Csrf::for_tests(),
+            path("math", "hw2"),
+            attach_form("https://example.com", ""),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, Error::InvalidAttachment(_)));
+    }
+
+    fn answer_form(answer: &str) -> Form<AnswerForm> {
+        Form(AnswerForm {
+            csrf: Csrf::for_tests().token().to_string(),
+            answer: answer.to_string(),
+        })
+    }
+
+    async fn saved_answer(client: &Backend, course_id: &str, id: &str) -> Option<String> {
+        let subs = client.list_submissions(course_id, id, None).await.unwrap();
+        let sub = subs.student_submissions.unwrap().remove(0);
+        sub.short_answer_submission
+            .and_then(|answer| answer.answer)
+            .or_else(|| sub.multiple_choice_submission?.answer)
+    }
+
+    #[tokio::test]
+    async fn answers_questions() {
+        let mut backend = MemoryBackend::fixture();
+        backend.add_question("math", "q1", "What is 6 x 7?", &[]);
+        backend.add_submission("math", "q1", "CREATED", false, None);
+        backend.made_by_css("math", "q1");
+        backend.add_question("math", "q2", "Is 7 prime?", &["Yes", "No"]);
+        backend.add_submission("math", "q2", "CREATED", false, None);
+        backend.made_by_css("math", "q2");
+        let client: Backend = Arc::new(backend);
+        for (id, answer) in [("q1", " 42 "), ("q2", "Yes")] {
+            let _ = super::answer(
+                UserClient(client.clone()),
+                Csrf::for_tests(),
+                path("math", id),
+                answer_form(answer),
+            )
+            .await
+            .unwrap();
+        }
+        assert_eq!(saved_answer(&client, "math", "q1").await.unwrap(), "42");
+        assert_eq!(saved_answer(&client, "math", "q2").await.unwrap(), "Yes");
+        let Html(html) = crate::routes::assignment(
+            UserClient(client),
+            State(AppState::for_tests()),
+            crate::auth::TeacherMode(false),
+            crate::timezone::Timezone(chrono_tz::Tz::UTC),
+            Csrf::for_tests(),
+            path("math", "q2"),
+        )
+        .await
+        .unwrap();
+        assert!(html.contains(r#"value="Yes" checked"#));
+        assert!(html.contains("Saved answer: Yes"));
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_answers() {
+        let mut backend = MemoryBackend::fixture();
+        backend.add_question("math", "q2", "Is 7 prime?", &["Yes", "No"]);
+        backend.add_submission("math", "q2", "CREATED", false, None);
+        backend.made_by_css("math", "q2");
+        let client: Backend = Arc::new(backend);
+        for (id, answer) in [("q2", "Maybe"), ("q2", " "), ("hw2", "An essay")] {
+            let err = super::answer(
+                UserClient(client.clone()),
+                Csrf::for_tests(),
+                path("math", id),
+                answer_form(answer),
+            )
+            .await
+            .unwrap_err();
+            assert!(matches!(err, Error::InvalidAnswer(_)), "{err:?}");
+        }
+        assert!(saved_answer(&client, "math", "q2").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn only_answers_questions_css_made() {
+        let mut backend = MemoryBackend::fixture();
+        backend.add_question("math", "q1", "What is 6 x 7?", &[]);
+        backend.add_submission("math", "q1", "CREATED", false, None);
+        backend.submission_mut("math", "q1").unwrap().alternate_link =
+            Some("https://classroom.google.com/c/math/q1".to_string());
+        let client: Backend = Arc::new(backend);
+        let err = super::answer(
+            UserClient(client.clone()),
+            Csrf::for_tests(),
+            path("math", "q1"),
+            answer_form("42"),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+        assert!(saved_answer(&client, "math", "q1").await.is_none());
+        let Html(html) = crate::routes::assignment(
+            UserClient(client),
+            State(AppState::for_tests()),
+            crate::auth::TeacherMode(false),
+            crate::timezone::Timezone(chrono_tz::Tz::UTC),
+            Csrf::for_tests(),
+            path("math", "q1"),
+        )
+        .await
+        .unwrap();
+        assert!(!html.contains("Save answer"));
+        assert!(html.contains("answer it in Classroom"));
+    }
+
+    #[tokio::test]
+    async fn cant_unsubmit_unsubmitted_work() {
+        let client: Backend = Arc::new(MemoryBackend::fixture());
+        let err = unsubmit(
+            UserClient(client),
+            Csrf::for_tests(),
+            path("math", "hw2"),
+            form(Csrf::for_tests().token()),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,397 @@
+use axum::{
+    extract::{Path, State},
+    response::{Html, Redirect},
+    Form,
+};
+use classroom::api::{Course, CourseWork, StudentSubmission};
+use futures_util::TryStreamExt;
+use tokio::try_join;
+
+use super::{assignment::status, user_names, Attachment};
+use crate::{
+    auth::{TeacherMode, UserClient},
+    backend::Backend,
+    csrf::{Csrf, CsrfForm},
+    paginate::paginate,
+    AppState, Error,
+};
+
+/// Every student's submission for a piece of coursework, for its teachers.
+pub async fn student_work(
+    UserClient(client): UserClient,
+    State(state): State<AppState>,
+    TeacherMode(teacher): TeacherMode,
+    csrf: Csrf,
+    Path((course_id, id)): Path<(String, String)>,
+) -> Result<Html<String>, Error> {
+    require_teacher(&client, teacher, &course_id).await?;
+    let mut context = tera::Context::new();
+    context.insert("csrf", csrf.token());
+    let work = get_student_work(&client, &course_id, &id, state.max_pages).await?;
+    context.insert("class", &work.class);
+    context.insert("coursework", &work.coursework);
+    context.insert("can_grade", &work.can_grade);
+    context.insert("submissions", &work.submissions);
+    Ok(Html(state.tera.render("student_work.jinja", &context)?))
+}
+
+#[derive(serde::Deserialize)]
+pub struct GradeForm {
+    csrf: String,
+    #[serde(default)]
+    draft_grade: String,
+    #[serde(default)]
+    assigned_grade: String,
+}
+
+/// Set a submission's draft and/or assigned grade. Blank grades are left as they were.
+pub async fn grade(
+    UserClient(client): UserClient,
+    TeacherMode(teacher): TeacherMode,
+    csrf: Csrf,
+    Path((course_id, id, submission_id)): Path<(String, String, String)>,
+    Form(form): Form<GradeForm>,
+) -> Result<Redirect, Error> {
+    csrf.verify(&form.csrf)?;
+    require_teacher(&client, teacher, &course_id).await?;
+    let draft_grade = parse_grade(&form.draft_grade)?;
+    let assigned_grade = parse_grade(&form.assigned_grade)?;
+    let update_mask = match (draft_grade, assigned_grade) {
+        (Some(_), Some(_)) => "draftGrade,assignedGrade",
+        (Some(_), None) => "draftGrade",
+        (None, Some(_)) => "assignedGrade",
+        (None, None) => return Err(Error::InvalidGrade("enter a draft or assigned grade")),
+    };
+    let submission = StudentSubmission {
+        draft_grade,
+        assigned_grade,
+        ..Default::default()
+    };
+    client
+        .patch_submission(&course_id, &id, &submission_id, submission, update_mask)
+        .await?;
+    Ok(Redirect::to(&format!(
+        "/assignment/{course_id}/{id}/submissions"
+    )))
+}
+
+pub async fn return_work(
+    UserClient(client): UserClient,
+    TeacherMode(teacher): TeacherMode,
+    csrf: Csrf,
+    Path((course_id, id, submission_id)): Path<(String, String, String)>,
+    Form(form): Form<CsrfForm>,
+) -> Result<Redirect, Error> {
+    csrf.verify(&form.csrf)?;
+    require_teacher(&client, teacher, &course_id).await?;
+    client
+        .return_submission(&course_id, &id, &submission_id)
+        .await?;
+    Ok(Redirect::to(&format!(
+        "/assignment/{course_id}/{id}/submissions"
+    )))
+}
+
+/// Fail unless the user is in teacher mode and teaches `course_id`.
+pub(super) async fn require_teacher(
+    client: &Backend,
+    teacher: bool,
+    course_id: &str,
+) -> Result<(), Error> {
+    if !teacher {
+        return Err(Error::TeacherModeOff);
+    }
+    if !client.is_teacher(course_id).await? {
+        return Err(Error::NotTeacher);
+    }
+    Ok(())
+}
+
+fn parse_grade(grade: &str) -> Result<Option<f64>, Error> {
+    let grade = grade.trim();
+    if grade.is_empty() {
+        return Ok(None);
+    }
+    match grade.parse::<f64>() {
+        // Classroom allows extra credit, so grades can be over the maximum
+        Ok(grade) if grade.is_finite() && grade >= 0.0 => Ok(Some(grade)),
+        _ => Err(Error::InvalidGrade("grades have to be a number of points")),
+    }
+}
+
+/// A piece of coursework and every student's submission for it.
+#[derive(serde::Serialize)]
+pub struct StudentWork {
+    class: Course,
+    coursework: CourseWork,
+    /// Whether css can grade and return the work, which Classroom only allows on
+    /// coursework created through css's Google Cloud project
+    can_grade: bool,
+    /// Sorted by student name
+    submissions: Vec<RosterEntry>,
+}
+
+/// One student's submission, as their teacher sees it.
+#[derive(serde::Serialize)]
+pub struct RosterEntry {
+    id: Option<String>,
+    /// The student's full name, or their user id if Classroom won't say
+    student: String,
+    status: &'static str,
+    late: bool,
+    draft_grade: Option<f64>,
+    assigned_grade: Option<f64>,
+    /// The student's answer, if the coursework is a question
+    answer: Option<String>,
+    attachments: Vec<Attachment>,
+    /// Whether returning would change anything: it isn't returned yet, or has a new grade
+    can_return: bool,
+    link: Option<String>,
+}
+
+pub async fn get_student_work(
+    client: &Backend,
+    course_id: &str,
+    id: &str,
+    max_pages: usize,
+) -> Result<StudentWork, Error> {
+    let (class, coursework, submissions) = try_join!(
+        client.get_course(course_id),
+        client.get_course_work(course_id, id),
+        paginate(max_pages, |page| async move {
+            client
+                .list_student_submissions(course_id, id, page.as_deref())
+                .await
+        })
+        .try_collect::<Vec<StudentSubmission>>()
+    )?;
+    let names = user_names(
+        client,
+        submissions.iter().filter_map(|sub| sub.user_id.as_deref()),
+    )
+    .await;
+    let mut submissions: Vec<RosterEntry> = submissions
+        .into_iter()
+        .map(|sub| {
+            let student = sub
+                .user_id
+                .as_ref()
+                .map(|id| names.get(id).unwrap_or(id).clone())
+                .unwrap_or_default();
+            RosterEntry {
+                id: sub.id,
+                student,
+                status: status(sub.state.as_deref()),
+                late: sub.late.unwrap_or(false),
+                can_return: sub.state.as_deref() != Some("RETURNED")
+                    || sub.draft_grade != sub.assigned_grade,
+                draft_grade: sub.draft_grade,
+                assigned_grade: sub.assigned_grade,
+                answer: sub
+                    .short_answer_submission
+                    .and_then(|answer| answer.answer)
+                    .or_else(|| sub.multiple_choice_submission?.answer),
+                attachments: sub
+                    .assignment_submission
+                    .and_then(|work| work.attachments)
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(Attachment::from_attachment)
+                    .collect(),
+                link: sub.alternate_link,
+            }
+        })
+        .collect();
+    submissions.sort_by(|a, b| a.student.cmp(&b.student));
+    Ok(StudentWork {
+        class,
+        can_grade: coursework.associated_with_developer.unwrap_or(false),
+        coursework,
+        submissions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::http::StatusCode;
+
+    use super::*;
+    use crate::backend::memory::MemoryBackend;
+
+    /// `art`, taught by the current user, with one assignment turned in by Arnold and
+    /// not yet by Wanda.
+    fn art_class() -> MemoryBackend {
+        let mut backend = MemoryBackend::fixture();
+        backend.add_course("art", "Art");
+        backend.teaching.push("art".to_string());
+        backend.add_work("art", "mural", "Mural", None);
+        backend.made_by_css("art", "mural");
+        backend.work_mut("art", "mural").unwrap().max_points = Some(100.0);
+        backend.add_user("wanda", "Wanda Li");
+        backend.add_user("arnold", "Arnold Perlstein");
+        backend.add_student_submission("art", "mural", "wanda", "CREATED");
+        backend.add_student_submission("art", "mural", "arnold", "TURNED_IN");
+        backend
+    }
+
+    fn path(course_id: &str, id: &str, submission: &str) -> Path<(String, String, String)> {
+        Path((
+            course_id.to_string(),
+            id.to_string(),
+            submission.to_string(),
+        ))
+    }
+
+    fn grade_form(draft_grade: &str, assigned_grade: &str) -> Form<GradeForm> {
+        Form(GradeForm {
+            csrf: Csrf::for_tests().token().to_string(),
+            draft_grade: draft_grade.to_string(),
+            assigned_grade: assigned_grade.to_string(),
+        })
+    }
+
+    async fn submission(client: &Backend, user_id: &str) -> StudentSubmission {
+        let subs = client
+            .list_student_submissions("art", "mural", None)
+            .await
+            .unwrap();
+        subs.student_submissions
+            .unwrap()
+            .into_iter()
+            .find(|sub| sub.user_id.as_deref() == Some(user_id))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn lists_student_work() {
+        let backend = Arc::new(art_class());
+        let Html(html) = student_work(
+            UserClient(backend),
+            State(AppState::for_tests()),
+            TeacherMode(true),
+            Csrf::for_tests(),
+            Path(("art".to_string(), "mural".to_string())),
+        )
+        .await
+        .unwrap();
+        let arnold = html.find("Arnold Perlstein").unwrap();
+        let wanda = html.find("Wanda Li").unwrap();
+        assert!(arnold < wanda);
+        assert!(html.contains("Turned in"));
+        assert!(html.contains(r#"name="draft_grade""#));
+    }
+
+    #[tokio::test]
+    async fn grades_and_returns_work() {
+        let client: Backend = Arc::new(art_class());
+        let _ = grade(
+            UserClient(client.clone()),
+            TeacherMode(true),
+            Csrf::for_tests(),
+            path("art", "mural", "mural-arnold"),
+            grade_form("95", ""),
+        )
+        .await
+        .unwrap();
+        let arnold = submission(&client, "arnold").await;
+        assert_eq!(arnold.draft_grade, Some(95.0));
+        assert_eq!(arnold.assigned_grade, None);
+        let _ = return_work(
+            UserClient(client.clone()),
+            TeacherMode(true),
+            Csrf::for_tests(),
+            path("art", "mural", "mural-arnold"),
+            Form(CsrfForm {
+                csrf: Csrf::for_tests().token().to_string(),
+            }),
+        )
+        .await
+        .unwrap();
+        let arnold = submission(&client, "arnold").await;
+        assert_eq!(arnold.state.as_deref(), Some("RETURNED"));
+        assert_eq!(arnold.assigned_grade, Some(95.0));
+    }
+
+    #[tokio::test]
+    async fn only_grades_work_css_made() {
+        let mut backend = art_class();
+        let work = backend.work_mut("art", "mural").unwrap();
+        work.associated_with_developer = None;
+        work.alternate_link = Some("https://classroom.google.com/c/art/a/mural".to_string());
+        let client: Backend = Arc::new(backend);
+        let Html(html) = student_work(
+            UserClient(client.clone()),
+            State(AppState::for_tests()),
+            TeacherMode(true),
+            Csrf::for_tests(),
+            Path(("art".to_string(), "mural".to_string())),
+        )
+        .await
+        .unwrap();
+        assert!(html.contains("Arnold Perlstein"));
+        assert!(!html.contains(r#"name="draft_grade""#));
+        assert!(!html.contains("/return"));
+        assert!(html.contains("grade and return it in Classroom"));
+        let err = grade(
+            UserClient(client.clone()),
+            TeacherMode(true),
+            Csrf::for_tests(),
+            path("art", "mural", "mural-arnold"),
+            grade_form("95", ""),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+        let err = return_work(
+            UserClient(client.clone()),
+            TeacherMode(true),
+            Csrf::for_tests(),
+            path("art", "mural", "mural-arnold"),
+            Form(CsrfForm {
+                csrf: Csrf::for_tests().token().to_string(),
+            }),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+        let arnold = submission(&client, "arnold").await;
+        assert_eq!(arnold.draft_grade, None);
+        assert_eq!(arnold.state.as_deref(), Some("TURNED_IN"));
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_grades() {
+        let client: Backend = Arc::new(art_class());
+        for (draft_grade, assigned_grade) in [("", ""), ("A+", ""), ("", "-1"), ("NaN", "")] {
+            let err = grade(
+                UserClient(client.clone()),
+                TeacherMode(true),
+                Csrf::for_tests(),
+                path("art", "mural", "mural-arnold"),
+                grade_form(draft_grade, assigned_grade),
+            )
+            .await
+            .unwrap_err();
+            assert_eq!(err.status_code(), StatusCode::BAD_REQUEST, "{err:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn only_teachers_see_student_work() {
+        let backend = Arc::new(art_class());
+        for (course_id, teacher) in [("art", false), ("math", true)] {
+            let err = student_work(
+                UserClient(backend.clone()),
+                State(AppState::for_tests()),
+                TeacherMode(teacher),
+                Csrf::for_tests(),
+                Path((course_id.to_string(), "mural".to_string())),
+            )
+            .await
+            .unwrap_err();
+            assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+        }
+    }
+}
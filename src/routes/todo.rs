@@ -1,75 +1,423 @@
 use std::collections::HashMap;
 
 use axum::{
-    extract::{Path, State},
-    response::Html,
+    extract::{Path, Query, State},
+    http::header,
+    response::{Html, IntoResponse, Redirect, Response},
+    Form,
 };
+use chrono_tz::Tz;
 use classroom::{
-    api::{Course, CourseWork, StudentSubmission, TimeOfDay},
-    chrono::{DateTime, NaiveDate, NaiveTime, Utc},
-    Classroom,
+    api::{Course, CourseWork, Date, StudentSubmission, TimeOfDay},
+    chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, Timelike, Utc},
 };
+use futures_util::TryStreamExt;
 use tokio::{task::JoinSet, try_join};
 
-use crate::{auth::UserClient, state::ClassroomHyperClient, AppState, Error};
+use crate::{
+    auth::{UserClient, UserId},
+    backend::Backend,
+    completion::CompletionPolicy,
+    csrf::Csrf,
+    ical::Calendar,
+    paginate::paginate,
+    timezone::Timezone,
+    AppState, Error,
+};
 
 pub async fn todos_all(
     UserClient(client): UserClient,
     State(state): State<AppState>,
+    UserId(user): UserId,
+    Timezone(timezone): Timezone,
+    csrf: Csrf,
+    Query(query): Query<TodoQuery>,
 ) -> Result<Html<String>, Error> {
+    let saved = state.todo_options.read().await.get(&user).cloned();
+    let options = query.over(saved.unwrap_or_default());
+    let policy = state.completion_policy(&user).await;
+    let assignment_list = all_todos(&client, policy, state.max_pages).await?;
+    let now = Utc::now();
     let mut context = tera::Context::new();
-    let courses = client
-        .courses()
-        .list()
-        .param("fields", "courses(id,name)")
-        .doit()
+    context.insert("csrf", csrf.token());
+    context.insert("timezone", timezone.name());
+    context.insert("classes", &classes_in(&assignment_list));
+    let list = super::mark_todos(&state, &user, assignment_list, None, now).await?;
+    context.insert("groups", &arrange(list.todos, &options, now, timezone));
+    context.insert("marked", &list.marked);
+    context.insert("options", &options);
+    Ok(Html(state.tera.render("todo.jinja", &context)?))
+}
+
+/// Save the to-do list options as the user's defaults, for whenever the query string
+/// doesn't say otherwise.
+pub async fn save_todo_options(
+    State(state): State<AppState>,
+    UserId(user): UserId,
+    csrf: Csrf,
+    Form(form): Form<TodoOptionsForm>,
+) -> Result<Redirect, Error> {
+    csrf.verify(&form.csrf)?;
+    let options = form.options.over(TodoOptions::default());
+    state
+        .todo_options
+        .update(|saved| saved.insert(user, options))
         .await?;
+    Ok(Redirect::to("/todo"))
+}
+
+pub async fn todos_for_class(
+    UserClient(client): UserClient,
+    State(state): State<AppState>,
+    UserId(user): UserId,
+    Timezone(timezone): Timezone,
+    csrf: Csrf,
+    Query(query): Query<TodoQuery>,
+    Path(course_id): Path<String>,
+) -> Result<Response, Error> {
+    // `/todo/:class.ics` can't be its own route, so the calendar feed is served from here
+    if let Some(course_id) = course_id.strip_suffix(".ics") {
+        let policy = state.completion_policy(&user).await;
+        let assignment_list = class_todos(&client, course_id, policy, state.max_pages).await?;
+        let name = assignment_list
+            .first()
+            .map_or_else(|| "css to-do".to_string(), |todo| todo.class_name.clone());
+        let list =
+            super::mark_todos(&state, &user, assignment_list, Some(course_id), Utc::now()).await?;
+        return Ok(todo_calendar(&list.todos, &name, &state.root_url).into_response());
+    }
+    let saved = state.todo_options.read().await.get(&user).cloned();
+    let options = TodoOptions {
+        // this page is already one class's work
+        class: None,
+        ..query.over(saved.unwrap_or_default())
+    };
+    let policy = state.completion_policy(&user).await;
+    let assignment_list = class_todos(&client, &course_id, policy, state.max_pages).await?;
+    let now = Utc::now();
+    let list = super::mark_todos(&state, &user, assignment_list, Some(&course_id), now).await?;
+    let mut context = tera::Context::new();
+    context.insert("csrf", csrf.token());
+    context.insert("timezone", timezone.name());
+    context.insert("class_page", &true);
+    context.insert("groups", &arrange(list.todos, &options, now, timezone));
+    context.insert("marked", &list.marked);
+    context.insert("options", &options);
+    Ok(Html(state.tera.render("todo.jinja", &context)?).into_response())
+}
+
+/// How to filter, group and sort the to-do list.
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct TodoOptions {
+    /// Only show one class's work, by course id
+    class: Option<String>,
+    show: Show,
+    window: Window,
+    group: Grouping,
+    sort: Sort,
+}
+
+/// Which incomplete work to show.
+#[derive(Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Show {
+    #[default]
+    All,
+    /// Work Classroom has marked late, turned in or not
+    Late,
+    /// Late work that hasn't been turned in
+    Missing,
+    NoDueDate,
+}
+
+/// When work has to be due to be shown. Work without a due date is never in a window.
+#[derive(Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Window {
+    #[default]
+    Any,
+    /// Due Monday through Sunday of the current week, including earlier this week
+    ThisWeek,
+    /// Due between now and a week from now
+    #[serde(rename = "next-7-days")]
+    Next7Days,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Grouping {
+    #[default]
+    None,
+    /// By class name
+    Class,
+    /// Into Overdue, Today, This week, Later and No due date
+    Due,
+}
+
+/// Work without a due date always sorts last.
+#[derive(Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Sort {
+    /// Latest due date first
+    #[default]
+    Latest,
+    /// Soonest due date first
+    Soonest,
+    /// By class name, then soonest due date first
+    Class,
+}
+
+/// [`TodoOptions`] from the query string. Anything left out comes from the saved defaults,
+/// and a blank `class` means every class.
+#[derive(serde::Deserialize)]
+pub struct TodoQuery {
+    class: Option<String>,
+    show: Option<Show>,
+    window: Option<Window>,
+    group: Option<Grouping>,
+    sort: Option<Sort>,
+}
+
+impl TodoQuery {
+    fn over(self, saved: TodoOptions) -> TodoOptions {
+        TodoOptions {
+            class: match self.class {
+                Some(class) if class.is_empty() => None,
+                Some(class) => Some(class),
+                None => saved.class,
+            },
+            show: self.show.unwrap_or(saved.show),
+            window: self.window.unwrap_or(saved.window),
+            group: self.group.unwrap_or(saved.group),
+            sort: self.sort.unwrap_or(saved.sort),
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct TodoOptionsForm {
+    csrf: String,
+    #[serde(flatten)]
+    options: TodoQuery,
+}
+
+/// A heading's worth of the to-do list. `name` is `None` when the list isn't grouped.
+#[derive(serde::Serialize)]
+pub struct TodoGroup {
+    name: Option<String>,
+    todos: Vec<Todo>,
+}
+
+/// The classes with something to do, as `(id, name)` sorted by name.
+fn classes_in(todos: &[Todo]) -> Vec<(&str, &str)> {
+    let mut classes: Vec<(&str, &str)> = todos
+        .iter()
+        .map(|todo| (todo.class_id.as_str(), todo.class_name.as_str()))
+        .collect();
+    classes.sort_unstable_by(|a, b| a.1.cmp(b.1).then(a.0.cmp(b.0)));
+    classes.dedup();
+    classes
+}
+
+/// Filter, sort and group `todos` by `options`. Days and weeks are counted by the
+/// calendar in `timezone`, with weeks starting on Monday.
+#[must_use]
+pub fn arrange(
+    todos: Vec<Todo>,
+    options: &TodoOptions,
+    now: DateTime<Utc>,
+    timezone: Tz,
+) -> Vec<TodoGroup> {
+    let today = now.with_timezone(&timezone).date_naive();
+    let week_start = today - Duration::days(today.weekday().num_days_from_monday().into());
+    let week_end = week_start + Duration::days(6);
+    let day = |due: &DueDateTime| due.with_timezone(&timezone).date_naive();
+    let mut todos: Vec<Todo> = todos
+        .into_iter()
+        .filter(|todo| options.class.as_ref().is_none_or(|id| *id == todo.class_id))
+        .filter(|todo| match options.show {
+            Show::All => true,
+            Show::Late => todo.late,
+            Show::Missing => todo.is_missing(),
+            Show::NoDueDate => todo.due.is_none(),
+        })
+        .filter(|todo| match (options.window, &todo.due) {
+            (Window::Any, _) => true,
+            (_, None) => false,
+            (Window::ThisWeek, Some(due)) => (week_start..=week_end).contains(&day(due)),
+            (Window::Next7Days, Some(due)) => **due >= now && **due < now + Duration::days(7),
+        })
+        .collect();
+    // `None` is less than any due date, so it's put last by hand
+    let soonest = |a: &Todo, b: &Todo| match (&a.due, &b.due) {
+        (Some(a), Some(b)) => a.cmp(b),
+        (a, b) => b.is_some().cmp(&a.is_some()),
+    };
+    match options.sort {
+        Sort::Latest => todos.sort_by(|a, b| a.due.cmp(&b.due).reverse()),
+        Sort::Soonest => todos.sort_by(soonest),
+        Sort::Class => todos.sort_by(|a, b| a.class_name.cmp(&b.class_name).then(soonest(a, b))),
+    }
+    let key = |todo: &Todo| -> String {
+        match options.group {
+            Grouping::None => String::new(),
+            Grouping::Class => todo.class_name.clone(),
+            Grouping::Due => match &todo.due {
+                None => "No due date",
+                Some(due) if **due < now => "Overdue",
+                Some(due) if day(due) == today => "Today",
+                Some(due) if day(due) <= week_end => "This week",
+                Some(_) => "Later",
+            }
+            .to_string(),
+        }
+    };
+    let mut groups: Vec<TodoGroup> = Vec::new();
+    for todo in todos {
+        let name = key(&todo);
+        match groups
+            .iter_mut()
+            .find(|group| group.name.as_ref() == Some(&name))
+        {
+            Some(group) => group.todos.push(todo),
+            None => groups.push(TodoGroup {
+                name: Some(name),
+                todos: vec![todo],
+            }),
+        }
+    }
+    match options.group {
+        Grouping::None => {
+            for group in &mut groups {
+                group.name = None;
+            }
+        }
+        Grouping::Class => groups.sort_by(|a, b| a.name.cmp(&b.name)),
+        Grouping::Due => {
+            const ORDER: [&str; 5] = ["Overdue", "Today", "This week", "Later", "No due date"];
+            groups.sort_by_key(|group| {
+                ORDER
+                    .iter()
+                    .position(|name| group.name.as_deref() == Some(*name))
+            });
+        }
+    }
+    groups
+}
+
+pub async fn todos_all_ics(
+    UserClient(client): UserClient,
+    UserId(user): UserId,
+    State(state): State<AppState>,
+) -> Result<Response, Error> {
+    let policy = state.completion_policy(&user).await;
+    let assignment_list = all_todos(&client, policy, state.max_pages).await?;
+    let list = super::mark_todos(&state, &user, assignment_list, None, Utc::now()).await?;
+    Ok(todo_calendar(&list.todos, "css to-do", &state.root_url).into_response())
+}
+
+/// Build an iCalendar feed of the to-do list. Assignments with a due date become
+/// events at that time, so they show up in calendar apps; the rest become VTODOs.
+pub fn todo_calendar(todos: &[Todo], name: &str, root_url: &str) -> impl IntoResponse {
+    let mut calendar = Calendar::new(name);
+    let now = Utc::now();
+    for todo in todos {
+        let component = if todo.due.is_some() {
+            "VEVENT"
+        } else {
+            "VTODO"
+        };
+        calendar.begin(component);
+        calendar.text("UID", &format!("{}-{}@css", todo.class_id, todo.id));
+        calendar.date_time("DTSTAMP", &now);
+        let name = todo.name.as_deref().unwrap_or("Untitled assignment");
+        calendar.text("SUMMARY", &format!("{}: {name}", todo.class_name));
+        if let Some(description) = &todo.description {
+            calendar.text("DESCRIPTION", description);
+        }
+        calendar.property(
+            "URL",
+            &format!("{root_url}/assignment/{}/{}", todo.class_id, todo.work_id),
+        );
+        // an event with no DTEND lasts no time at all, which is right for a deadline;
+        // DTEND can't be the same as DTSTART (RFC 5545 3.8.2.2)
+        if let Some(due) = &todo.due {
+            calendar.date_time("DTSTART", due);
+        } else {
+            calendar.property("STATUS", "NEEDS-ACTION");
+        }
+        calendar.end(component);
+    }
+    (
+        [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+        calendar.finish(),
+    )
+}
+
+/// Every incomplete assignment across all of the user's courses, latest due date first.
+pub async fn all_todos(
+    client: &Backend,
+    policy: CompletionPolicy,
+    max_pages: usize,
+) -> Result<Vec<Todo>, Error> {
+    let courses = super::list_classes(client, max_pages).await?;
     let mut assignment_list: Vec<Todo> = Vec::new();
     let mut lister_joins = JoinSet::new();
-    for course in courses
-        .1
-        .courses
-        .ok_or(Error::MissingField("courses.list.courses"))?
-    {
-        lister_joins.spawn(get_course(client.clone(), course));
+    for course in courses {
+        lister_joins.spawn(get_course(client.clone(), course, policy, max_pages));
     }
     while let Some(res) = lister_joins.join_next().await {
         assignment_list.append(&mut res??);
     }
     assignment_list.sort_by(|a, b| a.due.cmp(&b.due).reverse());
-    context.insert("todos", &assignment_list);
-    Ok(Html(state.tera.render("todo.jinja", &context)?))
+    Ok(assignment_list)
 }
 
-pub async fn todos_for_class(
-    UserClient(client): UserClient,
-    State(state): State<AppState>,
-    Path(course_id): Path<String>,
-) -> Result<Html<String>, Error> {
-    let mut context = tera::Context::new();
-    let course = client
-        .courses()
-        .get(&course_id)
-        .param("fields", "id,name")
-        .doit()
-        .await?
-        .1;
-    let assignment_list: Vec<Todo> = get_course(client, course).await?;
-    context.insert("todos", &assignment_list);
-    Ok(Html(state.tera.render("todo.jinja", &context)?))
+/// Every incomplete assignment in one course, latest due date first.
+pub async fn class_todos(
+    client: &Backend,
+    course_id: &str,
+    policy: CompletionPolicy,
+    max_pages: usize,
+) -> Result<Vec<Todo>, Error> {
+    let course = client.get_course(course_id).await?;
+    get_course(client.clone(), course, policy, max_pages).await
 }
 
-#[derive(serde::Serialize)]
-struct Todo {
+#[derive(Clone, serde::Serialize)]
+pub struct Todo {
     class_name: String,
     class_id: String,
     id: String,
+    work_id: String,
     description: Option<String>,
     name: Option<String>,
     late: bool,
+    /// Late work can still be turned in, it's just not done until it's graded
+    turned_in: bool,
     due: Option<DueDateTime>,
 }
 
+impl Todo {
+    /// Whether Classroom would show this as missing: late and not turned in.
+    #[must_use]
+    pub const fn is_missing(&self) -> bool {
+        self.late && !self.turned_in
+    }
+
+    /// What this is called in the user's [`super::TodoMarks`].
+    #[must_use]
+    pub fn mark_key(&self) -> String {
+        Self::key_for(&self.class_id, &self.id)
+    }
+
+    /// [`Self::mark_key`] for submission `id` in class `class_id`.
+    #[must_use]
+    pub fn key_for(class_id: &str, id: &str) -> String {
+        format!("{class_id}/{id}")
+    }
+}
+
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone)]
 struct DueDateTime(DateTime<Utc>);
 
@@ -90,8 +438,10 @@ impl serde::Serialize for DueDateTime {
 }
 
 async fn get_course(
-    client: Classroom<ClassroomHyperClient>,
+    client: Backend,
     course: Course,
+    policy: CompletionPolicy,
+    max_pages: usize,
 ) -> Result<Vec<Todo>, Error> {
     let course_id = course
         .id
@@ -99,29 +449,27 @@ async fn get_course(
     let class_name = course
         .name
         .ok_or(Error::MissingField("courses.list.courses[].name"))?;
-    let courses = client.courses();
-    let submissions_req = courses
-        .course_work_student_submissions_list(&course_id, "-")
-        .param(
-            "fields",
-            "studentSubmissions(courseWorkId,state,late,id,courseWorkId,assignedGrade)",
-        )
-        .doit();
-    let course_work_req = courses
-        .course_work_list(&course_id)
-        .param("fields", "courseWork(id,title,description,dueDate,dueTime)")
-        .doit();
-    let (course_work_resp, submissions_resp) = try_join!(course_work_req, submissions_req)?;
-    let submissions = submissions_resp
-        .1
-        .student_submissions
-        .ok_or(Error::MissingField(
-            "courses.courseWork.studentSubmissions[]",
-        ))?;
-    let course_works = course_work_resp
-        .1
-        .course_work
-        .ok_or(Error::MissingField("courses.courseWork.studentSubmissions"))?;
+    let submissions_req = paginate(max_pages, |page| {
+        let client = &client;
+        let course_id = &course_id;
+        async move {
+            client
+                .list_submissions(course_id, "-", page.as_deref())
+                .await
+        }
+    })
+    .try_collect::<Vec<StudentSubmission>>();
+    let course_work_req = paginate(max_pages, |page| {
+        let client = &client;
+        let course_id = &course_id;
+        async move {
+            client
+                .list_course_work(course_id, page.as_deref(), None)
+                .await
+        }
+    })
+    .try_collect::<Vec<CourseWork>>();
+    let (course_works, submissions) = try_join!(course_work_req, submissions_req)?;
     let mut course_works_by_id: HashMap<String, CourseWork> = HashMap::new();
     for course in course_works {
         if let Some(id) = course.id.clone() {
@@ -129,35 +477,37 @@ async fn get_course(
         }
     }
     let mut todos = Vec::new();
-    let submissions: Vec<StudentSubmission> =
-        submissions.into_iter().filter(is_incomplete).collect();
+    let submissions: Vec<StudentSubmission> = submissions
+        .into_iter()
+        .filter(|submission| policy.is_incomplete(submission))
+        .collect();
     for submission in submissions {
-        let late = is_late(&submission);
+        let late = submission.late.unwrap_or(false);
+        let turned_in = submission.state.as_deref() == Some("TURNED_IN");
         let work_id = submission.course_work_id.ok_or(Error::MissingField(
             "courses.courseWork.studentSubmissions[].courseWorkId",
         ))?;
         let id = submission.id.ok_or(Error::MissingField(
             "courses.courseWork.studentSubmissions[].id",
         ))?;
-        let course = course_works_by_id.get(&work_id).ok_or(Error::MissingField(
-            "courses.courseWork.studentSubmissions{courses.courseWork[].id}",
-        ))?;
-        let due = course.due_date.as_ref().and_then(|due_date| {
-            let due_time = course.due_time.clone().unwrap_or(TimeOfDay {
-                hours: Some(0),
-                minutes: Some(0),
-                seconds: Some(0),
-                nanos: Some(0),
-            });
-            classroom_to_naivedate(due_date, &due_time)
-        });
+        // when max_pages cuts the coursework list short, some submissions are for work
+        // that wasn't fetched; leave those off rather than failing the whole list
+        let Some(course) = course_works_by_id.get(&work_id) else {
+            continue;
+        };
+        let due = due_date(course).map(DueDateTime);
+        if due.is_none() && !policy.no_due_date {
+            continue;
+        }
         let todo = Todo {
             class_name: class_name.clone(),
             class_id: course_id.clone(),
             id,
+            work_id,
             description: course.description.clone(),
             name: course.title.clone(),
             late,
+            turned_in,
             due,
         };
         todos.push(todo);
@@ -166,28 +516,23 @@ async fn get_course(
     Ok(todos)
 }
 
-fn is_incomplete(sub: &StudentSubmission) -> bool {
-    if is_late(sub) {
-        return true;
-    }
-    let Some(state) = &sub.state else {
-        return true;
-    };
-    match state.as_str() {
-        "TURNED_IN" => false,
-        "RETURNED" => sub.assigned_grade.is_none(),
-        _ => true,
-    }
-}
-
-fn is_late(sub: &StudentSubmission) -> bool {
-    sub.late.map_or(false, |lateness| lateness)
+/// When `work` is due, if it has a due date. A due date without a time is due at midnight UTC.
+#[must_use]
+pub fn due_date(work: &CourseWork) -> Option<DateTime<Utc>> {
+    let due_date = work.due_date.as_ref()?;
+    let due_time = work.due_time.clone().unwrap_or(TimeOfDay {
+        hours: Some(0),
+        minutes: Some(0),
+        seconds: Some(0),
+        nanos: Some(0),
+    });
+    classroom_to_naivedate(due_date, &due_time)
 }
 
 fn classroom_to_naivedate(
     classroom_date: &classroom::api::Date,
     classroom_time: &classroom::api::TimeOfDay,
-) -> Option<DueDateTime> {
+) -> Option<DateTime<Utc>> {
     let date = NaiveDate::from_ymd_opt(
         classroom_date.year?,
         classroom_date.month?.try_into().ok()?,
@@ -199,8 +544,332 @@ fn classroom_to_naivedate(
         classroom_time.seconds.unwrap_or(0).try_into().ok()?,
         classroom_time.nanos.unwrap_or(0).try_into().ok()?,
     )?;
-    Some(DueDateTime(classroom::chrono::DateTime::<Utc>::from_utc(
-        classroom::chrono::NaiveDateTime::new(date, time),
-        Utc,
-    )))
+    Some(classroom::chrono::NaiveDateTime::new(date, time).and_utc())
+}
+
+/// The reverse of [`classroom_to_naivedate`]: the Classroom date and time of day for `time`.
+#[must_use]
+pub fn classroom_from_utc(time: DateTime<Utc>) -> (Date, TimeOfDay) {
+    let date = Date {
+        year: Some(time.year()),
+        month: time.month().try_into().ok(),
+        day: time.day().try_into().ok(),
+    };
+    let time = TimeOfDay {
+        hours: time.hour().try_into().ok(),
+        minutes: time.minute().try_into().ok(),
+        seconds: None,
+        nanos: None,
+    };
+    (date, time)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono_tz::Tz;
+    use classroom::hyper::body::to_bytes;
+
+    use super::*;
+    use crate::{backend::memory::MemoryBackend, completion::Returned};
+
+    fn no_query() -> Query<TodoQuery> {
+        Query(TodoQuery {
+            class: None,
+            show: None,
+            window: None,
+            group: None,
+            sort: None,
+        })
+    }
+
+    /// Wednesday, Sep 6, 2023 at noon UTC, between the fixture's due dates.
+    fn wednesday() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2023-09-06T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    async fn fixture_todos() -> Vec<Todo> {
+        let backend: Backend = Arc::new(MemoryBackend::fixture());
+        all_todos(&backend, CompletionPolicy::default(), 20)
+            .await
+            .unwrap()
+    }
+
+    /// The names of the groups, each followed by the names of its todos.
+    fn names(groups: &[TodoGroup]) -> Vec<String> {
+        groups
+            .iter()
+            .flat_map(|group| {
+                group.name.iter().cloned().chain(
+                    group
+                        .todos
+                        .iter()
+                        .map(|todo| todo.name.clone().unwrap_or_default()),
+                )
+            })
+            .collect()
+    }
+
+    fn position(html: &str, needle: &str) -> usize {
+        html.find(needle)
+            .unwrap_or_else(|| panic!("{needle:?} not found in {html}"))
+    }
+
+    #[tokio::test]
+    async fn lists_incomplete_work_latest_first() {
+        let backend = Arc::new(MemoryBackend::fixture());
+        let Html(html) = todos_all(
+            UserClient(backend),
+            State(AppState::for_tests()),
+            UserId("student".to_string()),
+            Timezone(Tz::UTC),
+            Csrf::for_tests(),
+            no_query(),
+        )
+        .await
+        .unwrap();
+        let hw2 = position(&html, "Homework 2");
+        let essay = position(&html, "Essay");
+        let project = position(&html, "Project");
+        assert!(hw2 < essay && essay < project);
+        assert!(!html.contains("Homework 1"), "turned in work is shown");
+        assert!(!html.contains("Reading"), "graded work is shown");
+        assert!(html.contains("/assignment/math/hw2"));
+        assert!(html.contains("days overdue"), "due dates aren't described");
+    }
+
+    #[tokio::test]
+    async fn follows_every_page() {
+        let backend = Arc::new(MemoryBackend {
+            page_size: 1,
+            ..MemoryBackend::fixture()
+        });
+        let todos = all_todos(&(backend as Backend), CompletionPolicy::default(), 20)
+            .await
+            .unwrap();
+        assert_eq!(todos.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn follows_completion_policy() {
+        let backend: Backend = Arc::new(MemoryBackend::fixture());
+        let policy = CompletionPolicy {
+            returned: Returned::All,
+            no_due_date: false,
+            ..CompletionPolicy::default()
+        };
+        let todos = all_todos(&backend, policy, 20).await.unwrap();
+        let names: Vec<_> = todos
+            .iter()
+            .filter_map(|todo| todo.name.as_deref())
+            .collect();
+        assert_eq!(names, ["Homework 2", "Essay", "Reading"]);
+    }
+
+    #[tokio::test]
+    async fn skips_submissions_for_unfetched_work() {
+        let mut backend = MemoryBackend::fixture();
+        // as if the coursework list had stopped before this one
+        backend.add_submission("math", "unlisted", "CREATED", false, None);
+        let backend: Backend = Arc::new(backend);
+        let todos = all_todos(&backend, CompletionPolicy::default(), 20)
+            .await
+            .unwrap();
+        assert_eq!(todos.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn stops_at_max_pages() {
+        let mut backend = MemoryBackend {
+            page_size: 1,
+            ..MemoryBackend::default()
+        };
+        backend.add_course("art", "Art");
+        for id in ["sketch", "painting"] {
+            backend.add_work("art", id, id, None);
+            backend.add_submission("art", id, "CREATED", false, None);
+        }
+        let backend: Backend = Arc::new(backend);
+        assert_eq!(
+            all_todos(&backend, CompletionPolicy::default(), 20)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            all_todos(&backend, CompletionPolicy::default(), 1)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn lists_one_class() {
+        let backend = Arc::new(MemoryBackend::fixture());
+        let response = todos_for_class(
+            UserClient(backend),
+            State(AppState::for_tests()),
+            UserId("student".to_string()),
+            Timezone(Tz::UTC),
+            Csrf::for_tests(),
+            no_query(),
+            Path("history".to_string()),
+        )
+        .await
+        .unwrap();
+        let body = to_bytes(response.into_body()).await.unwrap();
+        let html = String::from_utf8(body.to_vec()).unwrap();
+        assert!(html.contains("Essay"));
+        assert!(!html.contains("Homework 2"));
+    }
+
+    #[tokio::test]
+    async fn filters_by_class_and_status() {
+        let todos = fixture_todos().await;
+        let arranged =
+            |options: TodoOptions| names(&arrange(todos.clone(), &options, wednesday(), Tz::UTC));
+        let history = TodoOptions {
+            class: Some("history".to_string()),
+            ..TodoOptions::default()
+        };
+        assert_eq!(arranged(history), ["Essay"]);
+        let missing = TodoOptions {
+            show: Show::Missing,
+            ..TodoOptions::default()
+        };
+        assert_eq!(arranged(missing), ["Project"]);
+        let undated = TodoOptions {
+            show: Show::NoDueDate,
+            ..TodoOptions::default()
+        };
+        assert_eq!(arranged(undated), ["Project"]);
+    }
+
+    #[tokio::test]
+    async fn filters_by_due_window() {
+        let todos = fixture_todos().await;
+        let arranged = |window| {
+            let options = TodoOptions {
+                window,
+                ..TodoOptions::default()
+            };
+            names(&arrange(todos.clone(), &options, wednesday(), Tz::UTC))
+        };
+        assert_eq!(arranged(Window::ThisWeek), ["Homework 2", "Essay"]);
+        assert_eq!(arranged(Window::Next7Days), ["Homework 2"]);
+    }
+
+    #[tokio::test]
+    async fn groups_by_due_day() {
+        let options = TodoOptions {
+            group: Grouping::Due,
+            ..TodoOptions::default()
+        };
+        let groups = arrange(fixture_todos().await, &options, wednesday(), Tz::UTC);
+        assert_eq!(
+            names(&groups),
+            [
+                "Overdue",
+                "Essay",
+                "This week",
+                "Homework 2",
+                "No due date",
+                "Project"
+            ]
+        );
+        // Homework 2 is due Friday night in UTC, but already Saturday morning in Tokyo
+        let friday = wednesday() + Duration::days(2);
+        let utc = arrange(fixture_todos().await, &options, friday, Tz::UTC);
+        assert_eq!(names(&utc)[2], "Today");
+        let tokyo = arrange(
+            fixture_todos().await,
+            &options,
+            friday,
+            chrono_tz::Asia::Tokyo,
+        );
+        assert_eq!(names(&tokyo)[2], "This week");
+    }
+
+    #[tokio::test]
+    async fn groups_and_sorts_by_class() {
+        let options = TodoOptions {
+            group: Grouping::Class,
+            sort: Sort::Soonest,
+            ..TodoOptions::default()
+        };
+        let groups = arrange(fixture_todos().await, &options, wednesday(), Tz::UTC);
+        assert_eq!(
+            names(&groups),
+            ["History", "Essay", "Math", "Homework 2", "Project"]
+        );
+        let options = TodoOptions {
+            sort: Sort::Class,
+            ..TodoOptions::default()
+        };
+        let groups = arrange(fixture_todos().await, &options, wednesday(), Tz::UTC);
+        assert!(groups[0].name.is_none());
+        assert_eq!(names(&groups), ["Essay", "Homework 2", "Project"]);
+    }
+
+    #[tokio::test]
+    async fn query_overrides_saved_options() {
+        let state = AppState::for_tests();
+        state
+            .todo_options
+            .update(|saved| {
+                saved.insert(
+                    "student".to_string(),
+                    TodoOptions {
+                        class: Some("history".to_string()),
+                        group: Grouping::Class,
+                        ..TodoOptions::default()
+                    },
+                )
+            })
+            .await
+            .unwrap();
+        let todos = |query| {
+            todos_all(
+                UserClient(Arc::new(MemoryBackend::fixture())),
+                State(state.clone()),
+                UserId("student".to_string()),
+                Timezone(Tz::UTC),
+                Csrf::for_tests(),
+                Query(query),
+            )
+        };
+        let Html(html) = todos(no_query().0).await.unwrap();
+        assert!(html.contains("<h2>History</h2>") && !html.contains("Homework 2"));
+        let Html(html) = todos(TodoQuery {
+            class: Some(String::new()),
+            ..no_query().0
+        })
+        .await
+        .unwrap();
+        assert!(html.contains("<h2>Math</h2>") && html.contains("Homework 2"));
+    }
+
+    #[tokio::test]
+    async fn calendar_feed() {
+        let backend: Backend = Arc::new(MemoryBackend::fixture());
+        let todos = class_todos(&backend, "math", CompletionPolicy::default(), 20)
+            .await
+            .unwrap();
+        let response = todo_calendar(&todos, "Math", "https://css.example").into_response();
+        let body = to_bytes(response.into_body()).await.unwrap();
+        let ics = String::from_utf8(body.to_vec()).unwrap();
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert!(ics.contains("BEGIN:VEVENT\r\nUID:math-hw2-submission@css\r\n"));
+        assert!(ics.contains("DTSTART:20230908T235900Z\r\n"));
+        assert!(!ics.contains("DTEND"));
+        assert!(ics.contains("BEGIN:VTODO\r\nUID:math-project-submission@css\r\n"));
+        assert!(ics.contains("URL:https://css.example/assignment/math/hw2\r\n"));
+    }
 }
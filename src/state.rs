@@ -1,4 +1,4 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use tokio::sync::RwLock;
 
@@ -7,7 +7,13 @@ use oauth2::{
 };
 use tower_cookies::Key;
 
-use crate::Config;
+use crate::{
+    backend::{Backend, Cache, CachedBackend, GoogleBackend},
+    completion::{CompletionOverrides, CompletionPolicy},
+    routes::{FeedToken, TodoMarks, TodoOptions},
+    store::Store,
+    Config,
+};
 
 #[allow(clippy::module_name_repetitions)]
 #[derive(Clone)]
@@ -17,56 +23,176 @@ pub struct AppState {
     pub key: Arc<Key>,
     pub tera: Arc<tera::Tera>,
     pub client: ClassroomHttpClient,
+    pub max_pages: usize,
+    pub root_url: String,
+    pub feeds: Arc<Store<FeedToken>>,
+    /// Each user's default to-do list options, keyed by user id
+    pub todo_options: Arc<Store<TodoOptions>>,
+    /// What counts as done unless a user says otherwise
+    pub completion: CompletionPolicy,
+    /// Each user's changes to [`Self::completion`], keyed by user id
+    pub completion_overrides: Arc<Store<CompletionOverrides>>,
+    /// Each user's done, hidden and snoozed to-do items, keyed by user id
+    pub todo_marks: Arc<Store<TodoMarks>>,
+    pub classroom_url: String,
+    pub cache: Arc<Cache>,
 }
 
 pub type ClassroomHttpClient =
     classroom::hyper::client::Client<ClassroomHyperClient, classroom::hyper::Body>;
 pub type ClassroomHyperClient =
     classroom::hyper_rustls::HttpsConnector<classroom::hyper::client::HttpConnector>;
-pub type Tokens = Arc<RwLock<HashMap<String, PkceCodeVerifier>>>;
+pub type Tokens = Arc<RwLock<HashMap<String, PendingSignIn>>>;
+
+/// A sign in waiting for Google to redirect back, keyed by its OAuth state.
+pub struct PendingSignIn {
+    pub pkce_verifier: PkceCodeVerifier,
+    /// Whether the user is turning on teacher mode
+    pub teacher: bool,
+}
 
 impl AppState {
     /// Create a new [`AppState`].
     /// # Panics
-    /// This function panics on invalid templates.
+    /// This function panics on invalid templates, or if the data directory can't be used.
     pub fn new(config: Config) -> Self {
         let mut tera = tera::Tera::new("templates/*").expect("Failed to create templates");
         tera.autoescape_on(vec!["xml", "htm", "html", "jinja", "jinja2"]);
+        crate::timezone::register_filters(&mut tera);
         let tera = Arc::new(tera);
         crate::error::ERROR_TERA.try_insert(tera.clone()).ok();
+        let root_url = config.root_url.trim_end_matches('/').to_string();
         let oauth = oauth2::basic::BasicClient::new(
             ClientId::new(config.client_id),
             Some(ClientSecret::new(config.client_secret)),
-            AuthUrl::new("https://accounts.google.com/o/oauth2/v2/auth".to_string()).unwrap(),
-            Some(TokenUrl::new("https://oauth2.googleapis.com/token".to_string()).unwrap()),
+            AuthUrl::new(
+                config
+                    .auth_url
+                    .unwrap_or_else(|| "https://accounts.google.com/o/oauth2/v2/auth".to_string()),
+            )
+            .expect("Invalid auth_url"),
+            Some(
+                TokenUrl::new(
+                    config
+                        .token_url
+                        .unwrap_or_else(|| "https://oauth2.googleapis.com/token".to_string()),
+                )
+                .expect("Invalid token_url"),
+            ),
         )
         .set_revocation_uri(
-            RevocationUrl::new("https://oauth2.googleapis.com/revoke".to_string()).unwrap(),
+            RevocationUrl::new(
+                config
+                    .revocation_url
+                    .unwrap_or_else(|| "https://oauth2.googleapis.com/revoke".to_string()),
+            )
+            .expect("Invalid revocation_url"),
         )
         // Set the URL the user will be redirected to after the authorization process.
-        .set_redirect_uri(
-            RedirectUrl::new(format!(
-                "{}/oauth/callback",
-                config.root_url.trim_end_matches('/')
-            ))
-            .unwrap(),
+        .set_redirect_uri(RedirectUrl::new(format!("{root_url}/oauth/callback")).unwrap());
+        let data_dir = std::path::PathBuf::from(config.data_dir.as_deref().unwrap_or("data"));
+        std::fs::create_dir_all(&data_dir).expect("Failed to create data directory");
+        let feeds = Arc::new(
+            Store::load(data_dir.join("feeds.json")).migrate(crate::routes::hash_feed_tokens),
         );
+        let todo_options = Arc::new(Store::load(data_dir.join("todo_options.json")));
+        let completion_overrides = Arc::new(Store::load(data_dir.join("completion.json")));
+        let todo_marks = Arc::new(Store::load(data_dir.join("todo_marks.json")));
         let key_bytes = hex::decode(config.key).expect("Invalid hex");
         let key = Arc::new(Key::from(&key_bytes));
         let tokens: Tokens = Arc::new(RwLock::new(HashMap::new()));
-        let client = classroom::hyper::Client::builder().build(
-            classroom::hyper_rustls::HttpsConnectorBuilder::new()
-                .with_native_roots()
-                .https_only()
-                .enable_http1()
-                .build(),
+        let classroom_url = config.classroom_url.map_or_else(
+            || "https://classroom.googleapis.com/".to_string(),
+            |url| format!("{}/", url.trim_end_matches('/')),
         );
+        let connector = classroom::hyper_rustls::HttpsConnectorBuilder::new().with_native_roots();
+        // plain HTTP is only allowed when the Classroom API has been pointed somewhere local
+        let connector = if classroom_url.starts_with("http://") {
+            connector.https_or_http()
+        } else {
+            connector.https_only()
+        };
+        let client = classroom::hyper::Client::builder().build(connector.enable_http1().build());
+        let cache = Arc::new(Cache::new(
+            Duration::from_secs(config.cache_ttl.unwrap_or(60)),
+            Duration::from_secs(config.cache_stale.unwrap_or(600)),
+            config.cache_entries.unwrap_or(10_000),
+        ));
         Self {
             tokens,
             oauth,
             key,
             tera,
             client,
+            max_pages: config
+                .max_pages
+                .unwrap_or(crate::paginate::DEFAULT_MAX_PAGES),
+            root_url,
+            feeds,
+            todo_options,
+            completion: config.completion,
+            completion_overrides,
+            todo_marks,
+            classroom_url,
+            cache,
         }
     }
+
+    /// The Classroom API, acting as the user who owns `access_token`.
+    #[must_use]
+    pub fn backend(&self, access_token: String) -> Backend {
+        Arc::new(GoogleBackend::new(
+            self.client.clone(),
+            &self.classroom_url,
+            access_token,
+        ))
+    }
+
+    /// The completion policy for `user`: the server's, with their overrides applied.
+    pub async fn completion_policy(&self, user: &str) -> CompletionPolicy {
+        self.completion_overrides
+            .read()
+            .await
+            .get(user)
+            .map_or(self.completion, |overrides| overrides.over(self.completion))
+    }
+
+    /// [`Self::backend`], with list calls cached for `user`. `refresh` skips any cached data.
+    #[must_use]
+    pub fn cached_backend(&self, access_token: String, user: &str, refresh: bool) -> Backend {
+        Arc::new(CachedBackend::new(
+            self.backend(access_token),
+            self.cache.clone(),
+            user,
+            refresh,
+        ))
+    }
+}
+
+#[cfg(test)]
+impl AppState {
+    /// An [`AppState`] with a random key and its own empty data directory.
+    #[must_use]
+    pub fn for_tests() -> Self {
+        let data_dir = std::env::temp_dir().join(format!(
+            "css-test-{}",
+            oauth2::CsrfToken::new_random().secret()
+        ));
+        Self::new(Config {
+            key: hex::encode([7; 64]),
+            root_url: "http://localhost:8080/".to_string(),
+            client_id: "client".to_string(),
+            client_secret: "secret".to_string(),
+            max_pages: None,
+            data_dir: Some(data_dir.to_string_lossy().into_owned()),
+            classroom_url: None,
+            auth_url: None,
+            token_url: None,
+            revocation_url: None,
+            cache_ttl: None,
+            cache_stale: None,
+            cache_entries: None,
+            completion: CompletionPolicy::default(),
+        })
+    }
 }
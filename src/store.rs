@@ -0,0 +1,73 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::{RwLock, RwLockReadGuard};
+
+use crate::Error;
+
+/// A small persistent key-value store, kept in memory and written out to a JSON file
+/// after every change.
+pub struct Store<T> {
+    path: PathBuf,
+    data: RwLock<HashMap<String, T>>,
+}
+
+impl<T: Serialize + DeserializeOwned + Send + Sync> Store<T> {
+    /// Load the store from `path`, starting empty if the file doesn't exist yet.
+    /// # Panics
+    /// This function panics if the file exists but can't be read or parsed.
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let data = match std::fs::read_to_string(&path) {
+            Ok(v) => serde_json::from_str(&v)
+                .unwrap_or_else(|e| panic!("Invalid store file {}: {e}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => panic!("Failed to read store file {}: {e}", path.display()),
+        };
+        Self {
+            path,
+            data: RwLock::new(data),
+        }
+    }
+
+    /// Change the store with `f` as it's loaded, before anything else can see it, and save
+    /// it if `f` returns true to say it changed something.
+    /// # Panics
+    /// This function panics if the changed store can't be saved.
+    #[must_use]
+    pub fn migrate(mut self, f: impl FnOnce(&mut HashMap<String, T>) -> bool) -> Self {
+        if f(self.data.get_mut()) {
+            let json = serde_json::to_vec(self.data.get_mut())
+                .unwrap_or_else(|e| panic!("Failed to save store {}: {e}", self.path.display()));
+            let tmp = self.path.with_extension("tmp");
+            std::fs::write(&tmp, json)
+                .and_then(|()| std::fs::rename(&tmp, &self.path))
+                .unwrap_or_else(|e| panic!("Failed to save store {}: {e}", self.path.display()));
+        }
+        self
+    }
+
+    pub async fn read(&self) -> RwLockReadGuard<'_, HashMap<String, T>> {
+        self.data.read().await
+    }
+
+    /// Change the store with `f`, then save it to disk.
+    // The lock is held until the file is written, so saves can't land out of order
+    #[allow(clippy::significant_drop_tightening)]
+    pub async fn update<R: Send>(
+        &self,
+        f: impl FnOnce(&mut HashMap<String, T>) -> R + Send,
+    ) -> Result<R, Error> {
+        let mut data = self.data.write().await;
+        let out = f(&mut data);
+        let json = serde_json::to_vec(&*data)?;
+        // write to a temporary file first so a crash can't leave a half-written store behind
+        let tmp = self.path.with_extension("tmp");
+        tokio::fs::write(&tmp, json).await?;
+        tokio::fs::rename(&tmp, &self.path).await?;
+        Ok(out)
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+
+use axum::{extract::FromRequestParts, http::request::Parts};
+use chrono_tz::Tz;
+use classroom::chrono::{DateTime, Datelike, TimeZone, Utc};
+use tera::Value;
+use tower_cookies::Cookies;
+
+use crate::{AppState, Error};
+
+/// The user's time zone, from the `tz` cookie. The browser sets it to its own zone the
+/// first time a page loads, and the settings page can change it. Falls back to UTC, and
+/// the settings page says so when that's because of a zone css doesn't know.
+pub struct Timezone(pub Tz);
+
+#[axum::async_trait]
+impl FromRequestParts<AppState> for Timezone {
+    type Rejection = Error;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let cookies = match Cookies::from_request_parts(parts, state).await {
+            Ok(v) => v,
+            Err(e) => return Err(Error::Extractor(e.1)),
+        };
+        let timezone = cookies
+            .get("tz")
+            .and_then(|cookie| cookie.value().parse().ok())
+            .unwrap_or(Tz::UTC);
+        Ok(Self(timezone))
+    }
+}
+
+/// The `tz` cookie's value, if it names a time zone css doesn't know, like one newer than
+/// its copy of the time zone database.
+#[must_use]
+pub fn unknown_timezone(cookies: &Cookies) -> Option<String> {
+    let name = cookies.get("tz")?.value().to_string();
+    name.parse::<Tz>().is_err().then_some(name)
+}
+
+/// Add the `local_time` and `relative_due` filters, which format a time in the time zone
+/// named by their `tz` argument (UTC if it's missing). Times can be RFC 3339 strings
+/// or millisecond timestamps, like [`crate::routes::Todo`]'s due dates.
+///
+/// `local_time` also takes an optional strftime `format`, like Tera's `date` filter.
+pub fn register_filters(tera: &mut tera::Tera) {
+    tera.register_filter(
+        "local_time",
+        |value: &Value, args: &HashMap<String, Value>| {
+            let (time, timezone) = filter_args(value, args)?;
+            let formatted = match args.get("format") {
+                Some(Value::String(format)) => {
+                    time.with_timezone(&timezone).format(format).to_string()
+                }
+                _ => local_time(time, timezone),
+            };
+            Ok(Value::String(formatted))
+        },
+    );
+    tera.register_filter(
+        "relative_due",
+        |value: &Value, args: &HashMap<String, Value>| {
+            let (time, timezone) = filter_args(value, args)?;
+            Ok(Value::String(relative_due(time, Utc::now(), timezone)))
+        },
+    );
+}
+
+fn filter_args(value: &Value, args: &HashMap<String, Value>) -> tera::Result<(DateTime<Utc>, Tz)> {
+    let time = match value {
+        Value::Number(millis) => millis
+            .as_i64()
+            .and_then(|millis| Utc.timestamp_millis_opt(millis).single()),
+        Value::String(time) => DateTime::parse_from_rfc3339(time)
+            .ok()
+            .map(|time| time.with_timezone(&Utc)),
+        _ => None,
+    }
+    .ok_or_else(|| tera::Error::msg(format!("{value} isn't a time")))?;
+    let timezone = match args.get("tz") {
+        Some(Value::String(name)) => name
+            .parse()
+            .map_err(|_| tera::Error::msg(format!("unknown time zone {name}")))?,
+        _ => Tz::UTC,
+    };
+    Ok((time, timezone))
+}
+
+/// `time` as a date and time in `timezone`, like "Sep 8, 2023 11:59 PM EDT".
+#[must_use]
+pub fn local_time(time: DateTime<Utc>, timezone: Tz) -> String {
+    time.with_timezone(&timezone)
+        .format("%b %-d, %Y %-I:%M %p %Z")
+        .to_string()
+}
+
+/// When something is due, in words, like "due tomorrow at 11:59 PM" or "3 days overdue".
+/// Days are counted by the calendar in `timezone`, not in 24 hour blocks.
+#[must_use]
+pub fn relative_due(due: DateTime<Utc>, now: DateTime<Utc>, timezone: Tz) -> String {
+    let due = due.with_timezone(&timezone);
+    let now = now.with_timezone(&timezone);
+    let days = (due.date_naive() - now.date_naive()).num_days();
+    let time = due.format("%-I:%M %p");
+    if due < now {
+        return match days {
+            0 => format!("was due today at {time}"),
+            -1 => "1 day overdue".to_string(),
+            days => format!("{} days overdue", -days),
+        };
+    }
+    match days {
+        0 => format!("due today at {time}"),
+        1 => format!("due tomorrow at {time}"),
+        2..=6 => format!("due {} at {time}", due.format("%A")),
+        _ if due.year() == now.year() => format!("due {} at {time}", due.format("%b %-d")),
+        _ => format!("due {} at {time}", due.format("%b %-d, %Y")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    #[test]
+    fn formats_local_times() {
+        let due = utc(2023, 9, 9, 3, 59);
+        assert_eq!(
+            local_time(due, chrono_tz::America::New_York),
+            "Sep 8, 2023 11:59 PM EDT"
+        );
+        assert_eq!(local_time(due, Tz::UTC), "Sep 9, 2023 3:59 AM UTC");
+    }
+
+    #[test]
+    fn knows_recently_renamed_zones() {
+        for name in ["Europe/Kyiv", "America/Ciudad_Juarez", "Pacific/Kanton"] {
+            assert_eq!(name.parse::<Tz>().unwrap().name(), name);
+        }
+    }
+
+    #[test]
+    fn describes_upcoming_due_dates() {
+        let tz = chrono_tz::America::New_York;
+        // Friday Sep 8, 10 AM in New York
+        let now = utc(2023, 9, 8, 14, 0);
+        let due = |y, m, d, h, min| relative_due(utc(y, m, d, h, min), now, tz);
+        assert_eq!(due(2023, 9, 9, 3, 59), "due today at 11:59 PM");
+        assert_eq!(due(2023, 9, 10, 3, 59), "due tomorrow at 11:59 PM");
+        assert_eq!(due(2023, 9, 12, 13, 0), "due Tuesday at 9:00 AM");
+        assert_eq!(due(2023, 10, 2, 13, 0), "due Oct 2 at 9:00 AM");
+        assert_eq!(due(2024, 1, 8, 14, 0), "due Jan 8, 2024 at 9:00 AM");
+    }
+
+    #[test]
+    fn describes_overdue_work() {
+        let tz = chrono_tz::America::New_York;
+        let now = utc(2023, 9, 8, 14, 0);
+        let due = |y, m, d, h, min| relative_due(utc(y, m, d, h, min), now, tz);
+        assert_eq!(due(2023, 9, 8, 12, 0), "was due today at 8:00 AM");
+        // 11:59 PM on Thursday in New York, even though it's already Friday in UTC
+        assert_eq!(due(2023, 9, 8, 3, 59), "1 day overdue");
+        assert_eq!(due(2023, 9, 5, 3, 59), "4 days overdue");
+    }
+
+    #[test]
+    fn filters_take_timestamps_and_strings() {
+        let mut tera = tera::Tera::default();
+        register_filters(&mut tera);
+        let mut context = tera::Context::new();
+        context.insert("millis", &1_694_231_940_000_i64);
+        context.insert("rfc3339", &utc(2023, 9, 9, 3, 59));
+        let rendered = tera
+            .render_str(
+                r#"{{ millis | local_time(tz="America/New_York") }} / {{ rfc3339 | local_time }}"#,
+                &context,
+            )
+            .unwrap();
+        assert_eq!(
+            rendered,
+            "Sep 8, 2023 11:59 PM EDT / Sep 9, 2023 3:59 AM UTC"
+        );
+        let bad_zone = tera.render_str(
+            r#"{{ millis | local_time(tz="Mars/Olympus_Mons") }}"#,
+            &context,
+        );
+        assert!(bad_zone.is_err());
+        let date_only = tera
+            .render_str(
+                r#"{{ millis | local_time(tz="Asia/Tokyo", format="%b %-d") }}"#,
+                &context,
+            )
+            .unwrap();
+        assert_eq!(date_only, "Sep 9");
+    }
+}